@@ -0,0 +1,114 @@
+//! Compares the cost of calling a pipeline composed with nested `convert` (one erased call per
+//! level), with `chain!` (monomorphized) and through `Pied` (a single erased call), and of a
+//! pipeline built stage by stage with `then` when every stage nests another erased call around
+//! the pipeline versus the flat list `then` keeps. The flat list boxes the value between stages,
+//! so it costs more when every stage is ready at once, but a nested pipeline is polled again from
+//! the outermost stage whenever a stage wakes up, which dominates once stages wait.
+//!
+//! Run with `cargo bench --bench pipeline`.
+
+use async_middleware::{chain, convert, pipe_fn, BoxMiddleware, Middleware, Piper, Transform};
+use std::{
+    hint::black_box,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+const ITERATIONS: u32 = 200_000;
+
+const STAGES: usize = 8;
+
+const YIELDING_STAGES: usize = 256;
+
+async fn incr(i: u64) -> u64 {
+    i + 1
+}
+
+async fn yielding(i: u64) -> u64 {
+    async_std::task::yield_now().await;
+    i + 1
+}
+
+async fn measure<M: Middleware<u64, u64> + ?Sized>(m: &M, iterations: u32) -> Duration {
+    let start = Instant::now();
+    for i in 0..iterations {
+        black_box(m.call(black_box(i as u64)).await);
+    }
+    start.elapsed() / iterations
+}
+
+/// Builds the same pipeline with `then` both ways: nesting an erased call around the pipeline
+/// so far for every stage, as `then` used to, and the flat list `then` keeps now
+fn build_then<S, Args>(
+    stage: S,
+    stages: usize,
+) -> (BoxMiddleware<u64, u64>, BoxMiddleware<u64, u64>)
+where
+    S: Transform<Args, u64, u64> + Clone,
+    Args: Send + Sync + 'static,
+{
+    let mut nested: BoxMiddleware<u64, u64> = Arc::new(chain(stage.clone(), stage.clone()));
+    let mut flat = pipe_fn(stage.clone()).then(stage.clone());
+    for _ in 2..stages {
+        nested = Arc::new(chain(nested, stage.clone()));
+        flat = flat.then(stage.clone());
+    }
+    (nested, flat.boxed())
+}
+
+fn main() {
+    async_std::task::block_on(async {
+        let mut nested: BoxMiddleware<u64, u64> = Arc::new(convert(incr, incr));
+        for _ in 2..STAGES {
+            nested = Arc::new(convert(nested, incr));
+        }
+        let chained = chain!(incr, incr, incr, incr, incr, incr, incr, incr);
+        let pied = (incr, incr, incr, incr, incr, incr, incr, incr).pipe();
+        let (nested_then, flat_then) = build_then(incr, STAGES);
+        let (nested_yielding, flat_yielding) = build_then(yielding, YIELDING_STAGES);
+        for m in [&nested, &nested_then, &flat_then] {
+            assert_eq!(STAGES as u64, m.call(0).await);
+        }
+        for m in [&nested_yielding, &flat_yielding] {
+            assert_eq!(YIELDING_STAGES as u64, m.call(0).await);
+        }
+        assert_eq!(STAGES as u64, chained.call(0).await);
+        assert_eq!(STAGES as u64, pied.call(0).await);
+
+        println!("{} stages, {} calls each", STAGES, ITERATIONS);
+        println!(
+            "nested convert: {:?}/call",
+            measure(&*nested, ITERATIONS).await
+        );
+        println!(
+            "chain!:         {:?}/call",
+            measure(&chained, ITERATIONS).await
+        );
+        println!(
+            "pied:           {:?}/call",
+            measure(&pied, ITERATIONS).await
+        );
+        println!(
+            "nested then:    {:?}/call",
+            measure(&*nested_then, ITERATIONS).await
+        );
+        println!(
+            "flat then:      {:?}/call",
+            measure(&*flat_then, ITERATIONS).await
+        );
+
+        let iterations = ITERATIONS / 100;
+        println!(
+            "{} yielding stages, {} calls each",
+            YIELDING_STAGES, iterations
+        );
+        println!(
+            "nested then:    {:?}/call",
+            measure(&*nested_yielding, iterations).await
+        );
+        println!(
+            "flat then:      {:?}/call",
+            measure(&*flat_yielding, iterations).await
+        );
+    });
+}
@@ -0,0 +1,144 @@
+//! Traffic splitting between a control and an experimental sub-pipeline.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::{util::random, DynTransform, Middleware, StageInfo, Transform};
+
+/// Shared counts of the calls routed to each arm of an A/B split
+#[derive(Clone, Debug, Default)]
+pub struct AbCounts {
+    control: Arc<AtomicUsize>,
+    experiment: Arc<AtomicUsize>,
+}
+
+impl AbCounts {
+    /// Returns the number of calls routed to the control arm so far
+    pub fn control(&self) -> usize {
+        self.control.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of calls routed to the experimental arm so far
+    pub fn experiment(&self) -> usize {
+        self.experiment.load(Ordering::Relaxed)
+    }
+}
+
+/// Routes a fraction of calls to an experimental stage and the rest to the control stage
+pub struct AbSplit<A, B, I, O> {
+    percent: f64,
+    control: Arc<dyn DynTransform<A, I, O>>,
+    experiment: Arc<dyn DynTransform<B, I, O>>,
+    counts: AbCounts,
+}
+
+/// Creates a new split routing `percent` (0 to 100) of calls to `experiment` and the rest to
+/// `control`
+pub fn ab_split<A, B, I, O>(
+    percent: f64,
+    control: impl Transform<A, I, O>,
+    experiment: impl Transform<B, I, O>,
+) -> AbSplit<A, B, I, O>
+where
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    AbSplit {
+        percent: percent.clamp(0.0, 100.0),
+        control: Arc::new(control),
+        experiment: Arc::new(experiment),
+        counts: AbCounts::default(),
+    }
+}
+
+impl<A, B, I, O> AbSplit<A, B, I, O> {
+    /// Returns the counters of the split, which keep updating after the split is moved into a
+    /// pipeline
+    pub fn counts(&self) -> AbCounts {
+        self.counts.clone()
+    }
+}
+
+/// Implements the transform trait on the split (for downstream)
+impl<A, B, I, O> Transform<(I, O), I, O> for AbSplit<A, B, I, O>
+where
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        if random() * 100.0 < self.percent {
+            self.counts.experiment.fetch_add(1, Ordering::Relaxed);
+            self.experiment.transform(input).await
+        } else {
+            self.counts.control.fetch_add(1, Ordering::Relaxed);
+            self.control.transform(input).await
+        }
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::branching::<I, O>(
+            "ab_split",
+            vec![
+                ("control".into(), self.control.stages()),
+                ("experiment".into(), self.experiment.stages()),
+            ],
+        )]
+    }
+}
+
+/// Implements the middleware trait on the split
+impl<A, B, I, O> Middleware<I, O> for AbSplit<A, B, I, O>
+where
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn control(i: i32) -> i32 {
+        i + 1
+    }
+
+    async fn experiment(i: i32) -> i32 {
+        i * 2
+    }
+
+    #[async_std::test]
+    async fn test_ab_split() {
+        let split = ab_split(0.0, control, experiment);
+        let counts = split.counts();
+        let m = (split, |i: i32| async move { i.to_string() }).pipe();
+        assert_eq!(String::from("4"), m.call(3).await);
+        assert_eq!((1, 0), (counts.control(), counts.experiment()));
+
+        let split = ab_split(100.0, control, experiment);
+        assert_eq!(6, split.call(3).await);
+
+        let split = ab_split(25.0, control, experiment);
+        for i in 0..1000 {
+            split.call(i).await;
+        }
+        let counts = split.counts();
+        assert_eq!(1000, counts.control() + counts.experiment());
+        assert!((150..350).contains(&counts.experiment()));
+    }
+}
@@ -0,0 +1,284 @@
+//! Type-erased pipelines assembled from stages whose types are only known at runtime.
+
+use futures::future::BoxFuture;
+use std::{
+    any::{type_name, TypeId},
+    error::Error,
+    fmt,
+    marker::PhantomData,
+    sync::Arc,
+};
+
+use crate::{into_middleware, sequence, AnyValue, DynMiddleware, Middleware, StageInfo, Transform};
+
+/// Error returned when a value does not have the type a stage expects
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeMismatch {
+    position: usize,
+    expected: &'static str,
+    found: Option<&'static str>,
+}
+
+impl TypeMismatch {
+    /// Returns the position of the stage that rejected the value
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the name of the type the stage expected
+    pub fn expected(&self) -> &'static str {
+        self.expected
+    }
+
+    /// Returns the name of the type that was found, when known
+    pub fn found(&self) -> Option<&'static str> {
+        self.found
+    }
+}
+
+impl fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stage {} expected `{}`", self.position, self.expected)?;
+        match self.found {
+            Some(found) => write!(f, ", found `{}`", found),
+            None => f.write_str(", found another type"),
+        }
+    }
+}
+
+impl Error for TypeMismatch {}
+
+type AnyCall =
+    Arc<dyn Fn(AnyValue) -> BoxFuture<'static, Result<AnyValue, AnyValue>> + Send + Sync>;
+
+/// Stage erased to take and return `AnyValue`, remembering its concrete input and output types
+#[derive(Clone)]
+pub struct AnyStage {
+    call: AnyCall,
+    input: (TypeId, &'static str),
+    output: (TypeId, &'static str),
+    stages: Vec<StageInfo>,
+}
+
+/// Creates a new type-erased stage from any transform
+pub fn any_stage<Args, I, O>(stage: impl Transform<Args, I, O>) -> AnyStage
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    let stages = stage.stages();
+    let stage = into_middleware(stage);
+    AnyStage {
+        call: Arc::new(move |input: AnyValue| {
+            let stage = stage.clone();
+            Box::pin(async move {
+                match input.downcast::<I>() {
+                    Ok(input) => Ok(Box::new(stage.call(*input).await) as AnyValue),
+                    Err(input) => Err(input),
+                }
+            })
+        }),
+        input: (TypeId::of::<I>(), type_name::<I>()),
+        output: (TypeId::of::<O>(), type_name::<O>()),
+        stages,
+    }
+}
+
+/// Type-erased pipeline over `AnyValue`, checking at assembly that each stage accepts the
+/// output of the previous one
+#[derive(Clone, Default)]
+pub struct AnyMiddleware {
+    stages: Vec<AnyStage>,
+}
+
+impl AnyMiddleware {
+    /// Creates a new empty pipeline, which passes its input through unchanged
+    pub fn new() -> Self {
+        AnyMiddleware::default()
+    }
+
+    /// Appends the stage, failing when it does not accept the output of the last stage
+    pub fn push(&mut self, stage: AnyStage) -> Result<(), TypeMismatch> {
+        if let Some(last) = self.stages.last() {
+            if last.output.0 != stage.input.0 {
+                return Err(TypeMismatch {
+                    position: self.stages.len(),
+                    expected: stage.input.1,
+                    found: Some(last.output.1),
+                });
+            }
+        }
+        self.stages.push(stage);
+        Ok(())
+    }
+
+    /// Returns the number of stages in the pipeline
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// Returns whether the pipeline has no stages
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Checks that the pipeline goes from `I` to `O`, returning a statically typed middleware
+    pub fn typed<I, O>(self) -> Result<Typed<I, O>, TypeMismatch>
+    where
+        I: Send + Sync + 'static,
+        O: Send + Sync + 'static,
+    {
+        let (input, output) = match (self.stages.first(), self.stages.last()) {
+            (Some(first), Some(last)) => (first.input, last.output),
+            _ => (
+                (TypeId::of::<I>(), type_name::<I>()),
+                (TypeId::of::<I>(), type_name::<I>()),
+            ),
+        };
+        if input.0 != TypeId::of::<I>() {
+            return Err(TypeMismatch {
+                position: 0,
+                expected: input.1,
+                found: Some(type_name::<I>()),
+            });
+        }
+        if output.0 != TypeId::of::<O>() {
+            return Err(TypeMismatch {
+                position: self.stages.len(),
+                expected: type_name::<O>(),
+                found: Some(output.1),
+            });
+        }
+        Ok(Typed {
+            middleware: Arc::new(self),
+            _phantom: PhantomData,
+        })
+    }
+}
+
+/// Implements the transform trait on the type-erased pipeline (for downstream)
+impl Transform<(AnyValue, Result<AnyValue, TypeMismatch>), AnyValue, Result<AnyValue, TypeMismatch>>
+    for AnyMiddleware
+{
+    async fn transform(&self, input: AnyValue) -> Result<AnyValue, TypeMismatch> {
+        let mut value = input;
+        for (position, stage) in self.stages.iter().enumerate() {
+            value = (stage.call)(value).await.map_err(|_| TypeMismatch {
+                position,
+                expected: stage.input.1,
+                found: None,
+            })?;
+        }
+        Ok(value)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stages.iter().fold(Vec::new(), |stages, stage| {
+            sequence(stages, stage.stages.clone())
+        })
+    }
+}
+
+/// Implements the middleware trait on the type-erased pipeline
+impl Middleware<AnyValue, Result<AnyValue, TypeMismatch>> for AnyMiddleware {
+    async fn call(&self, input: AnyValue) -> Result<AnyValue, TypeMismatch> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Type-erased pipeline whose input and output types were checked, called with concrete types
+pub struct Typed<I, O> {
+    middleware: Arc<dyn DynMiddleware<AnyValue, Result<AnyValue, TypeMismatch>>>,
+    _phantom: PhantomData<fn(I) -> O>,
+}
+
+/// Implements the transform trait on the checked pipeline (for downstream)
+impl<I, O> Transform<(I, O), I, O> for Typed<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let output = self
+            .middleware
+            .call(Box::new(input))
+            .await
+            .expect("stage types were checked when the pipeline was assembled");
+        *output
+            .downcast()
+            .expect("output type was checked when the pipeline was assembled")
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.middleware.stages()
+    }
+}
+
+/// Implements the middleware trait on the checked pipeline
+impl<I, O> Middleware<I, O> for Typed<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use std::collections::HashMap;
+
+    async fn parse(s: String) -> i32 {
+        s.parse().unwrap_or_default()
+    }
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_any_middleware() {
+        let mut registry = HashMap::new();
+        registry.insert("parse", any_stage(parse));
+        registry.insert("multipler", any_stage(multipler));
+        registry.insert("stringer", any_stage(stringer));
+
+        let mut m = AnyMiddleware::new();
+        for name in ["parse", "multipler", "stringer"] {
+            m.push(registry[name].clone()).unwrap();
+        }
+        let out = m.call(Box::new(String::from("2"))).await.unwrap();
+        assert_eq!(Some(&String::from("64")), out.downcast_ref::<String>());
+
+        let err = m.call(Box::new(2)).await.err().unwrap();
+        assert_eq!(
+            "stage 0 expected `alloc::string::String`, found another type",
+            err.to_string()
+        );
+
+        let typed = (m.clone().typed::<String, String>().unwrap(), parse).pipe();
+        assert_eq!(96, typed.call(String::from("3")).await);
+        assert!(m.clone().typed::<i32, String>().is_err());
+        assert_eq!(3, Transform::stages(&m).len());
+
+        let err = m.push(registry["multipler"].clone()).unwrap_err();
+        assert_eq!(3, err.position());
+        assert_eq!(Some("alloc::string::String"), err.found());
+    }
+}
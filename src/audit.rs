@@ -0,0 +1,294 @@
+//! Audit trails recording the inputs and outputs of stages.
+
+use futures::channel::mpsc::UnboundedSender;
+#[cfg(not(target_arch = "wasm32"))]
+use futures::channel::oneshot;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+#[cfg(not(feature = "wasm"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{any::type_name, borrow::Cow, sync::Arc};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::mpsc,
+};
+#[cfg(feature = "wasm")]
+use web_time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::rt;
+use crate::{util::random, DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// One audited call of a stage: what went in, what came out, when and under which correlation id
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Identifier shared by the records of the same request
+    pub correlation_id: String,
+    /// Name of the audited stage
+    pub stage: String,
+    /// Milliseconds since the unix epoch when the call started
+    pub started_ms: u64,
+    /// Milliseconds since the unix epoch when the call finished
+    pub finished_ms: u64,
+    /// Serialized input of the call
+    pub input: Value,
+    /// Serialized output of the call
+    pub output: Value,
+}
+
+/// Destination of audit records, e.g. an append-only file or a channel to an archiving task
+pub trait AuditSink: Send + Sync {
+    /// Stores a record; called once per audited call, after it completes, from within the call,
+    /// so it must not block (hand slow I/O off to another task, as `JsonLinesSink` does)
+    fn record(&self, record: AuditRecord);
+}
+
+/// Sends every record down an unbounded channel, dropping it if the receiver is gone
+impl AuditSink for UnboundedSender<AuditRecord> {
+    fn record(&self, record: AuditRecord) {
+        let _ = self.unbounded_send(record);
+    }
+}
+
+/// Writes every record as a line of JSON to a writer, flushing after each one, on the runtime's
+/// blocking pool; records are queued so audited calls never wait on the writes
+#[cfg(not(target_arch = "wasm32"))]
+pub struct JsonLinesSink<W> {
+    records: mpsc::Sender<AuditRecord>,
+    writer: oneshot::Receiver<W>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<W: Write + Send + 'static> JsonLinesSink<W> {
+    /// Creates a new sink writing to the writer, which must be called from within the runtime
+    /// to start the writing task
+    pub fn new(mut writer: W) -> Self {
+        let (records, queued) = mpsc::channel::<AuditRecord>();
+        let (written, done) = oneshot::channel();
+        rt::spawn(async move {
+            let writer = rt::spawn_blocking(move || {
+                // a failed write must not fail the audited call, so it is dropped like a full
+                // channel
+                for record in queued {
+                    let _ = serde_json::to_writer(&mut writer, &record)
+                        .map_err(io::Error::from)
+                        .and_then(|_| writer.write_all(b"\n"))
+                        .and_then(|_| writer.flush());
+                }
+                writer
+            })
+            .await;
+            let _ = written.send(writer);
+        });
+        JsonLinesSink {
+            records,
+            writer: done,
+        }
+    }
+
+    /// Waits for the queued records to be written and returns the writer back, e.g. to inspect
+    /// what was written
+    pub async fn close(self) -> W {
+        drop(self.records);
+        self.writer.await.expect("audit writer panicked")
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl JsonLinesSink<File> {
+    /// Creates a new sink appending to the file at the path, creating it if missing
+    pub fn append(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(JsonLinesSink::new(file))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<W: Send> AuditSink for JsonLinesSink<W> {
+    fn record(&self, record: AuditRecord) {
+        let _ = self.records.send(record);
+    }
+}
+
+type CorrelationFn<I> = Box<dyn Fn(&I) -> String + Send + Sync>;
+
+/// Records the serialized input and output of every call of a stage to an audit sink
+pub struct Audit<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    name: Cow<'static, str>,
+    sink: Arc<dyn AuditSink>,
+    correlation_id: CorrelationFn<I>,
+}
+
+/// Creates a new audited stage, named after the type of the stage, with a random correlation id
+/// per call
+pub fn audit<Args, I, O, S>(stage: S, sink: Arc<dyn AuditSink>) -> Audit<Args, I, O>
+where
+    S: Transform<Args, I, O>,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    Audit {
+        stage: Arc::new(stage),
+        name: Cow::Borrowed(type_name::<S>()),
+        sink,
+        correlation_id: Box::new(|_| random_id()),
+    }
+}
+
+impl<Args, I, O> Audit<Args, I, O> {
+    /// Sets the stage name recorded in the audit trail
+    pub fn named(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Takes the correlation id of each call from its input (e.g. a request id) so the records
+    /// of several audited stages can be joined
+    pub fn correlation_id(mut self, id: impl Fn(&I) -> String + Send + Sync + 'static) -> Self {
+        self.correlation_id = Box::new(id);
+        self
+    }
+}
+
+/// Returns a random 64-bit identifier in hex
+fn random_id() -> String {
+    format!("{:016x}", (random() * u64::MAX as f64) as u64)
+}
+
+/// Returns the current time in milliseconds since the unix epoch
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Serializes a value for the record, keeping the error message if it can't be
+fn to_value(value: &impl Serialize) -> Value {
+    serde_json::to_value(value).unwrap_or_else(|e| Value::String(format!("unserializable: {}", e)))
+}
+
+/// Implements the transform trait on the audited stage (for downstream)
+impl<Args, I, O> Transform<(I, O), I, O> for Audit<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Serialize + Send + Sync + 'static,
+    O: Serialize + Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let correlation_id = (self.correlation_id)(&input);
+        let serialized = to_value(&input);
+        let started_ms = now_ms();
+        let output = self.stage.transform(input).await;
+        self.sink.record(AuditRecord {
+            correlation_id,
+            stage: self.name.to_string(),
+            started_ms,
+            finished_ms: now_ms(),
+            input: serialized,
+            output: to_value(&output),
+        });
+        output
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the audited stage
+impl<Args, I, O> Middleware<I, O> for Audit<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Serialize + Send + Sync + 'static,
+    O: Serialize + Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Serialize + Send + Sync + 'static,
+    O: Serialize + Send + Sync + 'static,
+{
+    /// Records the input and output of every call of the whole pipeline under the given name
+    pub fn audit(
+        self,
+        name: impl Into<Cow<'static, str>>,
+        sink: Arc<dyn AuditSink>,
+    ) -> Pied<T, Args, I, O> {
+        self.rebuild(|pied| audit(pied, sink).named(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipe_fn;
+    use futures::{channel::mpsc, StreamExt};
+    use serde_json::json;
+
+    #[derive(Serialize)]
+    struct Order {
+        id: String,
+        amount: u32,
+    }
+
+    async fn approve(order: Order) -> bool {
+        order.amount < 100
+    }
+
+    #[async_std::test]
+    async fn test_audit_channel() {
+        let (tx, mut rx) = mpsc::unbounded();
+        let m = audit(approve, Arc::new(tx))
+            .named("approve")
+            .correlation_id(|order: &Order| order.id.clone());
+        let order = Order {
+            id: String::from("o-1"),
+            amount: 250,
+        };
+        assert!(!m.call(order).await);
+
+        let record = rx.next().await.unwrap();
+        assert_eq!("o-1", record.correlation_id);
+        assert_eq!("approve", record.stage);
+        assert!(record.started_ms <= record.finished_ms);
+        assert_eq!(json!({ "id": "o-1", "amount": 250 }), record.input);
+        assert_eq!(json!(false), record.output);
+    }
+
+    #[async_std::test]
+    async fn test_audit_json_lines() {
+        let sink = Arc::new(JsonLinesSink::new(Vec::new()));
+        let m = pipe_fn(|i: i32| async move { i * 2 }).audit("double", sink.clone());
+        assert_eq!(4, m.call(2).await);
+        assert_eq!(6, m.call(3).await);
+        drop(m);
+
+        let written = Arc::try_unwrap(sink).ok().unwrap().close().await;
+        let records: Vec<AuditRecord> = String::from_utf8(written)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(2, records.len());
+        assert_eq!(
+            (json!(3), json!(6)),
+            (records[1].input.clone(), records[1].output.clone())
+        );
+        assert_ne!(records[0].correlation_id, records[1].correlation_id);
+    }
+}
@@ -0,0 +1,186 @@
+//! Load balancing across equivalent stage instances.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::{
+    util::{random, InFlight},
+    DynTransform, Middleware, StageInfo, Transform,
+};
+
+/// How a balancer picks the instance for each call
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BalanceStrategy {
+    /// Cycles through the instances in order
+    RoundRobin,
+    /// Picks the instance with the fewest calls in flight
+    LeastOutstanding,
+    /// Picks an instance at random in proportion to its weight, in instance order; missing
+    /// weights count as 1
+    Weighted(Vec<u32>),
+}
+
+/// Distributes calls across equivalent instances of a stage (e.g. clients pointed at different
+/// replicas), tracking the calls in flight on each
+pub struct Balance<Args, I, O> {
+    instances: Vec<Arc<dyn DynTransform<Args, I, O>>>,
+    in_flight: Vec<AtomicUsize>,
+    strategy: BalanceStrategy,
+    next: AtomicUsize,
+}
+
+/// Creates a new balancer over the instances; panics if there are none
+pub fn balance<Args, I, O, S>(instances: Vec<S>, strategy: BalanceStrategy) -> Balance<Args, I, O>
+where
+    S: Transform<Args, I, O>,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    assert!(!instances.is_empty(), "balance needs at least one instance");
+    let instances: Vec<Arc<dyn DynTransform<Args, I, O>>> = instances
+        .into_iter()
+        .map(|instance| Arc::new(instance) as Arc<dyn DynTransform<Args, I, O>>)
+        .collect();
+    Balance {
+        in_flight: instances.iter().map(|_| AtomicUsize::new(0)).collect(),
+        instances,
+        strategy,
+        next: AtomicUsize::new(0),
+    }
+}
+
+impl<Args, I, O> Balance<Args, I, O> {
+    /// Returns the number of calls currently in flight on each instance, in instance order
+    pub fn in_flight(&self) -> Vec<usize> {
+        self.in_flight
+            .iter()
+            .map(|count| count.load(Ordering::SeqCst))
+            .collect()
+    }
+
+    /// Picks the instance for the next call
+    fn pick(&self) -> usize {
+        let len = self.instances.len();
+        match &self.strategy {
+            BalanceStrategy::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed) % len,
+            BalanceStrategy::LeastOutstanding => {
+                // start from a rotating offset so ties are spread over the instances
+                let start = self.next.fetch_add(1, Ordering::Relaxed);
+                (start..start + len)
+                    .map(|i| i % len)
+                    .min_by_key(|&i| self.in_flight[i].load(Ordering::SeqCst))
+                    .unwrap_or(0)
+            }
+            BalanceStrategy::Weighted(weights) => {
+                let weight = |i: usize| f64::from(weights.get(i).copied().unwrap_or(1));
+                let mut target = random() * (0..len).map(weight).sum::<f64>();
+                (0..len)
+                    .find(|&i| {
+                        target -= weight(i);
+                        target < 0.0
+                    })
+                    .unwrap_or(len - 1)
+            }
+        }
+    }
+}
+
+/// Implements the transform trait on the balancer (for downstream)
+impl<Args, I, O> Transform<(I, O), I, O> for Balance<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let i = self.pick();
+        self.in_flight[i].fetch_add(1, Ordering::SeqCst);
+        let _in_flight = InFlight(&self.in_flight[i]);
+        self.instances[i].transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        let branches = self.instances.iter().enumerate();
+        vec![StageInfo::branching::<I, O>(
+            "balance",
+            branches
+                .map(|(i, instance)| (i.to_string().into(), instance.stages()))
+                .collect(),
+        )]
+    }
+}
+
+/// Implements the middleware trait on the balancer
+impl<Args, I, O> Middleware<I, O> for Balance<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BoxMiddleware, Piper};
+    use async_std::task::sleep;
+    use futures::future::join_all;
+    use std::time::Duration;
+
+    fn replica(id: usize, delay: u64) -> BoxMiddleware<usize, usize> {
+        let call = move |i: usize| async move {
+            sleep(Duration::from_millis(delay)).await;
+            id * 100 + i
+        };
+        (call, |i: usize| async move { i }).pipe().boxed()
+    }
+
+    #[async_std::test]
+    async fn test_balance_round_robin() {
+        let m = balance(
+            vec![replica(1, 0), replica(2, 0), replica(3, 0)],
+            BalanceStrategy::RoundRobin,
+        );
+        let outputs = join_all((0..4).map(|i| m.call(i))).await;
+        assert_eq!(vec![100, 201, 302, 103], outputs);
+        assert_eq!(vec![0, 0, 0], m.in_flight());
+    }
+
+    #[async_std::test]
+    async fn test_balance_least_outstanding() {
+        let m = balance(
+            vec![replica(1, 40), replica(2, 5)],
+            BalanceStrategy::LeastOutstanding,
+        );
+        let slow = m.call(0);
+        let fast = async {
+            sleep(Duration::from_millis(10)).await;
+            let first = m.call(1).await;
+            (first, m.call(2).await)
+        };
+        let (slow, fast) = futures::join!(slow, fast);
+        assert_eq!(100, slow);
+        assert_eq!((201, 202), fast);
+    }
+
+    #[async_std::test]
+    async fn test_balance_weighted() {
+        let m = balance(
+            vec![replica(1, 0), replica(2, 0)],
+            BalanceStrategy::Weighted(vec![0, 1]),
+        );
+        for i in 0..10 {
+            assert_eq!(200 + i, m.call(i).await);
+        }
+    }
+}
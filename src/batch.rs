@@ -0,0 +1,132 @@
+//! Batching of items in spawned pipelines.
+
+use futures::{
+    channel::mpsc::{Receiver, Sender},
+    future::BoxFuture,
+    SinkExt, StreamExt,
+};
+use std::{mem, time::Duration};
+
+use crate::{
+    rt::{self, Instant},
+    Spawned,
+};
+
+/// Creates the task loop that groups received items, flushing on size or on the latency deadline
+fn batch_task<T>(
+    mut rx: Receiver<T>,
+    mut tx: Sender<Vec<T>>,
+    size: usize,
+    max_latency: Duration,
+) -> BoxFuture<'static, ()>
+where
+    T: Send + Sync + 'static,
+{
+    Box::pin(async move {
+        let mut batch = Vec::with_capacity(size);
+        let mut deadline = Instant::now();
+        loop {
+            let item = if batch.is_empty() {
+                rx.next().await
+            } else {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                match rt::timeout(remaining, rx.next()).await {
+                    Ok(item) => item,
+                    Err(_) => {
+                        let full = mem::replace(&mut batch, Vec::with_capacity(size));
+                        if tx.send(full).await.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                }
+            };
+            match item {
+                Some(item) => {
+                    if batch.is_empty() {
+                        deadline = Instant::now() + max_latency;
+                    }
+                    batch.push(item);
+                    if batch.len() >= size {
+                        let full = mem::replace(&mut batch, Vec::with_capacity(size));
+                        if tx.send(full).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                None => {
+                    if !batch.is_empty() {
+                        let _ = tx.send(batch).await;
+                    }
+                    return;
+                }
+            }
+        }
+    })
+}
+
+impl<I, O> Spawned<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Groups the outputs of the previous stage into batches of up to `size` items, flushing a
+    /// partial batch once its first item has waited `max_latency` or the input is closed
+    pub fn batch(self, size: usize, max_latency: Duration) -> Spawned<I, Vec<O>> {
+        let size = size.max(1);
+        self.task(move |rx, tx, _| batch_task(rx, tx, size, max_latency))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spawned;
+    use async_std::task::{self, sleep};
+    use futures::future::BoxFuture;
+    use std::time::Duration;
+
+    async fn multipler(i: u64) -> u64 {
+        i * 32
+    }
+
+    async fn total(batch: Vec<u64>) -> u64 {
+        batch.iter().sum()
+    }
+
+    fn spawner(future: BoxFuture<'static, ()>) {
+        task::spawn(future);
+    }
+
+    #[async_std::test]
+    async fn test_batch_by_size_and_latency() {
+        let mut handle = spawned(multipler)
+            .batch(3, Duration::from_millis(20))
+            .spawn(8, spawner);
+        for i in 0..4 {
+            handle.send(i).await.unwrap();
+        }
+        assert_eq!(Some(vec![0, 32, 64]), handle.recv().await);
+
+        // the fourth item is flushed alone by the latency timer
+        assert_eq!(Some(vec![96]), handle.recv().await);
+
+        handle.send(5).await.unwrap();
+        sleep(Duration::from_millis(5)).await;
+        handle.close();
+        assert_eq!(Some(vec![160]), handle.recv().await);
+        assert_eq!(None, handle.recv().await);
+    }
+
+    #[async_std::test]
+    async fn test_batch_into_stage() {
+        let mut handle = spawned(multipler)
+            .batch(2, Duration::from_secs(1))
+            .stage(total)
+            .spawn(4, spawner);
+        for i in 1..5 {
+            handle.send(i).await.unwrap();
+        }
+        assert_eq!(Some(96), handle.recv().await);
+        assert_eq!(Some(224), handle.recv().await);
+    }
+}
@@ -0,0 +1,86 @@
+//! CPU-bound work off the async executor.
+//!
+//! Not available on wasm32, where browser workers can't block or spawn threads; split the work
+//! across calls or hand it to a web worker instead.
+
+use std::sync::Arc;
+
+use crate::{rt, Middleware, StageInfo, Transform};
+
+/// Stage that runs a synchronous function on the runtime's blocking pool
+pub struct Blocking<F> {
+    f: Arc<F>,
+}
+
+/// Creates a new stage running the CPU-heavy function without starving the executor
+pub fn blocking<F>(f: F) -> Blocking<F> {
+    Blocking { f: Arc::new(f) }
+}
+
+/// Implements the transform trait on the blocking stage (for downstream)
+impl<F, T, O> Transform<(T, O), T, O> for Blocking<F>
+where
+    F: Fn(T) -> O + Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> O {
+        let f = self.f.clone();
+        rt::spawn_blocking(move || f(input)).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::of::<F, T, O>()]
+    }
+}
+
+/// Implements the middleware trait on the blocking stage
+impl<F, T, O> Middleware<T, O> for Blocking<F>
+where
+    F: Fn(T) -> O + Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use futures::{future::join_all, FutureExt};
+    use std::panic::AssertUnwindSafe;
+
+    async fn multipler(i: u64) -> u64 {
+        i * 32
+    }
+
+    fn fib(n: u64) -> u64 {
+        (0..n).fold((0, 1), |(a, b), _| (b, a + b)).0
+    }
+
+    #[async_std::test]
+    async fn test_blocking() {
+        let m = (multipler, blocking(fib)).pipe();
+        assert_eq!(2178309, m.call(1).await);
+    }
+
+    #[async_std::test]
+    async fn test_blocking_panic() {
+        let m = blocking(|i: u64| {
+            assert!(i > 0, "zero input");
+            i
+        });
+        assert!(AssertUnwindSafe(m.call(0)).catch_unwind().await.is_err());
+
+        // the pool keeps serving calls, queueing those beyond its threads
+        let outputs = join_all((1..=64).map(|i| m.call(i))).await;
+        assert_eq!((1..=64).collect::<Vec<_>>(), outputs);
+    }
+}
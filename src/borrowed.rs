@@ -0,0 +1,109 @@
+//! Stages over borrowed inputs.
+
+use std::{future::Future, marker::PhantomData, sync::Arc};
+
+use crate::{DynTransform, Transform};
+
+/// Async function from a reference with a lifetime to a future borrowing from it
+pub trait AsyncRefFn<'a, T: ?Sized + 'a, O>: Fn(&'a T) -> Self::Fut {
+    type Fut: Future<Output = O> + Send + 'a;
+}
+
+impl<'a, F, Fut, T, O> AsyncRefFn<'a, T, O> for F
+where
+    F: Fn(&'a T) -> Fut,
+    Fut: Future<Output = O> + Send + 'a,
+    T: ?Sized + 'a,
+{
+    type Fut = Fut;
+}
+
+/// Middleware that transforms a reference to caller-owned data into an owned output.
+pub trait TransformRef<T: ?Sized, O>: Send + Sync + 'static {
+    /// Asynchronously execute this handler while borrowing the input
+    fn transform_ref<'a>(&'a self, input: &'a T) -> impl Future<Output = O> + Send + 'a;
+}
+
+/// Borrowing implementation for an async function accepting a reference (e.g. `&str`)
+impl<F, T, O> TransformRef<T, O> for F
+where
+    F: for<'a> AsyncRefFn<'a, T, O> + Send + Sync + 'static,
+    T: ?Sized + Sync,
+{
+    fn transform_ref<'a>(&'a self, input: &'a T) -> impl Future<Output = O> + Send + 'a {
+        (self)(input)
+    }
+}
+
+/// Feeds the owned output of a borrowing stage into a regular transform
+pub struct ConvertRef<R, Args, T: ?Sized, B, C> {
+    first: R,
+    rest: Arc<dyn DynTransform<Args, B, C>>,
+    _phantom: PhantomData<fn(&T)>,
+}
+
+/// Creates a new pipeline over borrowed input from a borrowing stage and an owned stage
+pub fn convert_ref<R, Args, T, B, C>(
+    first: R,
+    rest: impl Transform<Args, B, C>,
+) -> ConvertRef<R, Args, T, B, C>
+where
+    R: TransformRef<T, B>,
+    Args: Send + Sync + 'static,
+    T: ?Sized,
+    B: Send + Sync + 'static,
+    C: Send + Sync + 'static,
+{
+    ConvertRef {
+        first,
+        rest: Arc::new(rest),
+        _phantom: PhantomData,
+    }
+}
+
+/// Implements the borrowing transform trait on the conversion (for downstream)
+impl<R, Args, T, B, C> TransformRef<T, C> for ConvertRef<R, Args, T, B, C>
+where
+    R: TransformRef<T, B>,
+    Args: Send + Sync + 'static,
+    T: ?Sized + Sync + 'static,
+    B: Send + Sync + 'static,
+    C: Send + Sync + 'static,
+{
+    async fn transform_ref<'a>(&'a self, input: &'a T) -> C {
+        let input = self.first.transform_ref(input).await;
+        self.rest.transform(input).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn word_count(text: &str) -> usize {
+        text.split_whitespace().count()
+    }
+
+    async fn checksum(bytes: &[u8]) -> u32 {
+        bytes.iter().map(|b| u32::from(*b)).sum()
+    }
+
+    async fn doubled(i: usize) -> usize {
+        i * 2
+    }
+
+    async fn stringer(i: usize) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_transform_ref() {
+        let text = String::from("the quick brown fox");
+        assert_eq!(4, word_count.transform_ref(text.as_str()).await);
+        assert_eq!(6, checksum.transform_ref(&[1, 2, 3][..]).await);
+
+        let m = convert_ref(word_count, (doubled, stringer).pipe());
+        assert_eq!(String::from("8"), m.transform_ref(&text).await);
+    }
+}
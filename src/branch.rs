@@ -0,0 +1,108 @@
+//! Conditional branching between sub-pipelines.
+
+use alloc::{boxed::Box, sync::Arc, vec, vec::Vec};
+
+use crate::{DynTransform, Middleware, StageInfo, Transform};
+
+/// Routes the input to one of two stages depending on a predicate
+pub struct Branch<A, A2, I, O> {
+    pred: Box<dyn Fn(&I) -> bool + Send + Sync>,
+    then: Arc<dyn DynTransform<A, I, O>>,
+    otherwise: Arc<dyn DynTransform<A2, I, O>>,
+}
+
+/// Creates a new branch running `then` when the predicate holds and `otherwise` when it doesn't
+pub fn branch<A, A2, I, O>(
+    pred: impl Fn(&I) -> bool + Send + Sync + 'static,
+    then: impl Transform<A, I, O>,
+    otherwise: impl Transform<A2, I, O>,
+) -> Branch<A, A2, I, O>
+where
+    A: Send + Sync + 'static,
+    A2: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    Branch {
+        pred: Box::new(pred),
+        then: Arc::new(then),
+        otherwise: Arc::new(otherwise),
+    }
+}
+
+/// Implements the transform trait on the branch (for downstream)
+impl<A, A2, I, O> Transform<(I, O), I, O> for Branch<A, A2, I, O>
+where
+    A: Send + Sync + 'static,
+    A2: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        if (self.pred)(&input) {
+            self.then.transform(input).await
+        } else {
+            self.otherwise.transform(input).await
+        }
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::branching::<I, O>(
+            "branch",
+            vec![
+                ("then".into(), self.then.stages()),
+                ("otherwise".into(), self.otherwise.stages()),
+            ],
+        )]
+    }
+}
+
+/// Implements the middleware trait on the branch
+impl<A, A2, I, O> Middleware<I, O> for Branch<A, A2, I, O>
+where
+    A: Send + Sync + 'static,
+    A2: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn negate(i: i32) -> i32 {
+        -i
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    async fn describe(i: i32) -> String {
+        format!("small {}", i)
+    }
+
+    #[async_std::test]
+    async fn test_branch() {
+        let m = (
+            multipler,
+            branch(|i: &i32| *i > 64, (negate, stringer).pipe(), describe),
+        )
+            .pipe();
+        assert_eq!(String::from("small 64"), m.call(2).await);
+        assert_eq!(String::from("-96"), m.call(3).await);
+    }
+}
@@ -0,0 +1,343 @@
+//! Circuit breaker middleware.
+
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{rt::Instant, DynTransform, Middleware, StageInfo, Transform};
+
+/// Error returned by a circuit breaker, either fast-failing or passing on the stage error
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BreakerError<E> {
+    /// The breaker is open and the stage was not called
+    Open,
+    /// The stage was called and failed
+    Inner(E),
+}
+
+impl<E: fmt::Display> fmt::Display for BreakerError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakerError::Open => f.write_str("circuit breaker is open"),
+            BreakerError::Inner(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for BreakerError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BreakerError::Open => None,
+            BreakerError::Inner(e) => Some(e),
+        }
+    }
+}
+
+/// Observable state of a circuit breaker
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls pass through while the outcomes of the latest calls are tracked
+    Closed,
+    /// Calls fast-fail until the reset timeout has passed
+    Open,
+    /// A single probe call is let through to decide whether to close again
+    HalfOpen,
+}
+
+enum BreakerState {
+    /// Outcomes of the latest calls, `true` for a failure, and how many of them failed
+    Closed {
+        outcomes: VecDeque<bool>,
+        failures: usize,
+    },
+    Open {
+        until: Instant,
+    },
+    HalfOpen {
+        until: Instant,
+    },
+}
+
+/// State of the breaker and how many times it changed, so a call that completes after a change
+/// (e.g. a slow call started before the breaker opened) doesn't count towards the new state
+struct Circuit {
+    state: BreakerState,
+    generation: u64,
+}
+
+impl Circuit {
+    fn closed() -> BreakerState {
+        BreakerState::Closed {
+            outcomes: VecDeque::new(),
+            failures: 0,
+        }
+    }
+
+    fn transition(&mut self, state: BreakerState) {
+        self.state = state;
+        self.generation += 1;
+    }
+}
+
+/// Fast-fails calls to a fallible stage while too many of its latest calls fail, probing again
+/// after a while
+pub struct CircuitBreaker<Args, I, O, E> {
+    stage: Arc<dyn DynTransform<Args, I, Result<O, E>>>,
+    circuit: Mutex<Circuit>,
+    failure_rate: f64,
+    window_size: usize,
+    minimum_calls: usize,
+    reset_timeout: Duration,
+}
+
+impl<Args, I, O, E> CircuitBreaker<Args, I, O, E>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    /// Creates a new breaker that opens for thirty seconds once half of the last twenty calls
+    /// failed, after at least ten calls
+    pub fn new(stage: impl Transform<Args, I, Result<O, E>>) -> Self {
+        CircuitBreaker {
+            stage: Arc::new(stage),
+            circuit: Mutex::new(Circuit {
+                state: Circuit::closed(),
+                generation: 0,
+            }),
+            failure_rate: 0.5,
+            window_size: 20,
+            minimum_calls: 10,
+            reset_timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Sets the share of failed calls in the window, between 0 and 1, that opens the breaker
+    pub fn failure_rate(mut self, failure_rate: f64) -> Self {
+        self.failure_rate = failure_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets how many of the latest calls the failure rate is computed over
+    pub fn window_size(mut self, window_size: usize) -> Self {
+        self.window_size = window_size.max(1);
+        self
+    }
+
+    /// Sets how many calls the window must hold before the failure rate may open the breaker
+    pub fn minimum_calls(mut self, minimum_calls: usize) -> Self {
+        self.minimum_calls = minimum_calls.max(1);
+        self
+    }
+
+    /// Sets how long the breaker stays open before letting a probe call through
+    pub fn reset_timeout(mut self, reset_timeout: Duration) -> Self {
+        self.reset_timeout = reset_timeout;
+        self
+    }
+
+    /// Returns the current state of the breaker
+    pub fn state(&self) -> CircuitState {
+        match self.circuit.lock().unwrap().state {
+            BreakerState::Closed { .. } => CircuitState::Closed,
+            BreakerState::Open { until } if Instant::now() >= until => CircuitState::HalfOpen,
+            BreakerState::Open { .. } => CircuitState::Open,
+            BreakerState::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Decides whether a call may go through, returning the generation it belongs to and moving
+    /// an expired open breaker to half-open. A probe that never reports back (e.g. it was
+    /// cancelled) is replaced after the timeout.
+    fn acquire(&self) -> Option<u64> {
+        let mut circuit = self.circuit.lock().unwrap();
+        let now = Instant::now();
+        match circuit.state {
+            BreakerState::Closed { .. } => Some(circuit.generation),
+            BreakerState::Open { until } | BreakerState::HalfOpen { until } if now >= until => {
+                circuit.transition(BreakerState::HalfOpen {
+                    until: now + self.reset_timeout,
+                });
+                Some(circuit.generation)
+            }
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => None,
+        }
+    }
+
+    /// Records the outcome of a call that went through, ignoring it if the breaker changed
+    /// state since the call was let through, so only the probe decides how half-open ends
+    fn record(&self, generation: u64, success: bool) {
+        let mut circuit = self.circuit.lock().unwrap();
+        if circuit.generation != generation {
+            return;
+        }
+        let open = match &mut circuit.state {
+            BreakerState::Closed { outcomes, failures } => {
+                outcomes.push_back(!success);
+                *failures += usize::from(!success);
+                if outcomes.len() > self.window_size && outcomes.pop_front() == Some(true) {
+                    *failures -= 1;
+                }
+                *failures > 0
+                    && outcomes.len() >= self.minimum_calls.min(self.window_size)
+                    && *failures as f64 >= self.failure_rate * outcomes.len() as f64
+            }
+            BreakerState::HalfOpen { .. } if success => {
+                circuit.transition(Circuit::closed());
+                return;
+            }
+            BreakerState::HalfOpen { .. } => true,
+            BreakerState::Open { .. } => false,
+        };
+        if open {
+            circuit.transition(BreakerState::Open {
+                until: Instant::now() + self.reset_timeout,
+            });
+        }
+    }
+}
+
+/// Implements the transform trait on the circuit breaker (for downstream)
+impl<Args, I, O, E> Transform<(I, Result<O, BreakerError<E>>), I, Result<O, BreakerError<E>>>
+    for CircuitBreaker<Args, I, O, E>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> Result<O, BreakerError<E>> {
+        let Some(generation) = self.acquire() else {
+            return Err(BreakerError::Open);
+        };
+        let result = self.stage.transform(input).await;
+        self.record(generation, result.is_ok());
+        result.map_err(BreakerError::Inner)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the circuit breaker
+impl<Args, I, O, E> Middleware<I, Result<O, BreakerError<E>>> for CircuitBreaker<Args, I, O, E>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> Result<O, BreakerError<E>> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task::sleep;
+
+    async fn checked(i: i32) -> Result<i32, String> {
+        if i < 0 {
+            Err(format!("negative {}", i))
+        } else {
+            Ok(i)
+        }
+    }
+
+    #[async_std::test]
+    async fn test_breaker_opens_and_probes() {
+        let breaker = CircuitBreaker::new(checked)
+            .failure_rate(0.5)
+            .minimum_calls(3)
+            .reset_timeout(Duration::from_millis(20));
+
+        assert_eq!(Ok(1), breaker.call(1).await);
+        assert_eq!(
+            Err(BreakerError::Inner(String::from("negative -1"))),
+            breaker.call(-1).await
+        );
+        assert_eq!(CircuitState::Closed, breaker.state());
+        assert!(breaker.call(-2).await.is_err());
+        assert_eq!(CircuitState::Open, breaker.state());
+
+        // fast-fails even for good inputs while open
+        assert_eq!(Err(BreakerError::Open), breaker.call(3).await);
+
+        // a failed probe opens the breaker again
+        sleep(Duration::from_millis(25)).await;
+        assert_eq!(CircuitState::HalfOpen, breaker.state());
+        assert!(matches!(
+            breaker.call(-3).await,
+            Err(BreakerError::Inner(_))
+        ));
+        assert_eq!(Err(BreakerError::Open), breaker.call(3).await);
+
+        // a successful probe closes it
+        sleep(Duration::from_millis(25)).await;
+        assert_eq!(Ok(4), breaker.call(4).await);
+        assert_eq!(CircuitState::Closed, breaker.state());
+    }
+
+    #[async_std::test]
+    async fn test_breaker_failure_rate_window() {
+        let breaker = CircuitBreaker::new(checked)
+            .failure_rate(0.5)
+            .window_size(4)
+            .minimum_calls(4);
+        for i in [-1, 1, 2, 3, -2, 4] {
+            let _ = breaker.call(i).await;
+            assert_eq!(CircuitState::Closed, breaker.state());
+        }
+        // the window now holds 3, -2, 4, -5
+        assert!(breaker.call(-5).await.is_err());
+        assert_eq!(CircuitState::Open, breaker.state());
+    }
+
+    async fn slow_success(i: i32) -> Result<i32, String> {
+        if i > 0 {
+            sleep(Duration::from_millis(20 * i as u64)).await;
+        }
+        checked(i).await
+    }
+
+    #[async_std::test]
+    async fn test_breaker_ignores_stale_outcomes() {
+        let breaker = CircuitBreaker::new(slow_success)
+            .failure_rate(1.0)
+            .minimum_calls(1)
+            .reset_timeout(Duration::from_millis(80));
+
+        let (stale, probe, rejected) = futures::join!(
+            // started while closed, finishes while the probe is in flight
+            breaker.call(6),
+            async {
+                sleep(Duration::from_millis(5)).await;
+                assert!(breaker.call(-1).await.is_err());
+                assert_eq!(CircuitState::Open, breaker.state());
+                sleep(Duration::from_millis(80)).await;
+                breaker.call(3).await
+            },
+            async {
+                sleep(Duration::from_millis(135)).await;
+                assert_eq!(CircuitState::HalfOpen, breaker.state());
+                breaker.call(1).await
+            }
+        );
+        assert_eq!(Ok(6), stale);
+        assert_eq!(Err(BreakerError::Open), rejected);
+        // only the probe closes the breaker
+        assert_eq!(Ok(3), probe);
+        assert_eq!(CircuitState::Closed, breaker.state());
+    }
+}
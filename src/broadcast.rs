@@ -0,0 +1,107 @@
+//! Fan-out of one value to several sinks.
+
+use std::marker::PhantomData;
+
+use crate::{Middleware, StageInfo, Transform};
+
+/// Delivers a clone of the input to every sink in a tuple concurrently
+pub struct Broadcast<S, Args> {
+    sinks: S,
+    _phantom: PhantomData<Args>,
+}
+
+/// Creates a new broadcast over a tuple of sinks, collecting their outputs into a tuple
+pub fn broadcast<S, Args>(sinks: S) -> Broadcast<S, Args> {
+    Broadcast {
+        sinks,
+        _phantom: PhantomData,
+    }
+}
+
+macro_rules! impl_broadcast {
+    ($($i:tt $S:ident $A:ident $O:ident),+) => {
+        /// Implements the transform trait on the broadcast (for downstream)
+        impl<I, $($S, $A, $O),+> Transform<(I, ($($O,)+)), I, ($($O,)+)>
+            for Broadcast<($($S,)+), ($($A,)+)>
+        where
+            I: Clone + Send + Sync + 'static,
+            $($S: Transform<$A, I, $O>,)+
+            $($A: Send + Sync + 'static,)+
+            $($O: Send + Sync + 'static,)+
+        {
+            async fn transform(&self, input: I) -> ($($O,)+) {
+                futures::join!($(self.sinks.$i.transform(input.clone())),+)
+            }
+
+            fn stages(&self) -> Vec<StageInfo> {
+                vec![StageInfo::branching::<I, ($($O,)+)>(
+                    "broadcast",
+                    vec![$((stringify!($i).into(), self.sinks.$i.stages())),+],
+                )]
+            }
+        }
+
+        /// Implements the middleware trait on the broadcast
+        impl<I, $($S, $A, $O),+> Middleware<I, ($($O,)+)> for Broadcast<($($S,)+), ($($A,)+)>
+        where
+            I: Clone + Send + Sync + 'static,
+            $($S: Transform<$A, I, $O>,)+
+            $($A: Send + Sync + 'static,)+
+            $($O: Send + Sync + 'static,)+
+        {
+            async fn call(&self, input: I) -> ($($O,)+) {
+                self.transform(input).await
+            }
+
+            fn stages(&self) -> Vec<StageInfo> {
+                Transform::stages(self)
+            }
+        }
+    };
+}
+
+impl_broadcast!(0 S0 A0 O0, 1 S1 A1 O1);
+impl_broadcast!(0 S0 A0 O0, 1 S1 A1 O1, 2 S2 A2 O2);
+impl_broadcast!(0 S0 A0 O0, 1 S1 A1 O1, 2 S2 A2 O2, 3 S3 A3 O3);
+impl_broadcast!(0 S0 A0 O0, 1 S1 A1 O1, 2 S2 A2 O2, 3 S3 A3 O3, 4 S4 A4 O4);
+impl_broadcast!(0 S0 A0 O0, 1 S1 A1 O1, 2 S2 A2 O2, 3 S3 A3 O3, 4 S4 A4 O4, 5 S5 A5 O5);
+impl_broadcast!(0 S0 A0 O0, 1 S1 A1 O1, 2 S2 A2 O2, 3 S3 A3 O3, 4 S4 A4 O4, 5 S5 A5 O5, 6 S6 A6 O6);
+impl_broadcast!(0 S0 A0 O0, 1 S1 A1 O1, 2 S2 A2 O2, 3 S3 A3 O3, 4 S4 A4 O4, 5 S5 A5 O5, 6 S6 A6 O6, 7 S7 A7 O7);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use std::sync::{Arc, Mutex};
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_broadcast() {
+        let disk = Arc::new(Mutex::new(Vec::new()));
+        let log = disk.clone();
+        let m = (
+            multipler,
+            broadcast((
+                move |i: i32| {
+                    let log = log.clone();
+                    async move { log.lock().unwrap().push(i) }
+                },
+                stringer,
+                (multipler, stringer).pipe(),
+            )),
+        )
+            .pipe();
+        assert_eq!(
+            ((), String::from("64"), String::from("2048")),
+            m.call(2).await
+        );
+        assert_eq!(vec![64], *disk.lock().unwrap());
+    }
+}
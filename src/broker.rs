@@ -0,0 +1,316 @@
+//! Message broker sources and sinks.
+
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    StreamExt,
+};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    error::Error,
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{Middleware, Pied};
+
+/// Message received from a source along with the token used to acknowledge it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Delivery<M, T> {
+    pub message: M,
+    pub token: T,
+}
+
+/// Delivery of the message type of a source
+pub type SourceDelivery<S> = Delivery<<S as MessageSource>::Message, <S as MessageSource>::Token>;
+
+/// Source of messages that must be acknowledged once processed (e.g. a queue subscription)
+pub trait MessageSource: Send + 'static {
+    type Message: Send + Sync + 'static;
+    type Token: Send + 'static;
+    type Error: Send + 'static;
+
+    /// Receives the next message, or `None` once the source is closed and drained
+    fn receive(
+        &mut self,
+    ) -> impl Future<Output = Result<Option<SourceDelivery<Self>>, Self::Error>> + Send;
+
+    /// Acknowledges that the message was processed successfully
+    fn ack(&mut self, token: Self::Token) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    /// Reports that processing the message failed, letting the broker redeliver or dead-letter it
+    fn nack(&mut self, token: Self::Token) -> impl Future<Output = Result<(), Self::Error>> + Send;
+}
+
+/// Destination that processed messages are published to
+pub trait MessageSink<M>: Send + 'static {
+    type Error: Send + 'static;
+
+    /// Publishes the message
+    fn publish(&mut self, message: M) -> impl Future<Output = Result<(), Self::Error>> + Send;
+}
+
+/// Error returned when consuming from a source into a sink fails
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BrokerError<S, K> {
+    /// Receiving or acknowledging a message failed
+    Source(S),
+    /// Publishing an output failed
+    Sink(K),
+}
+
+impl<S: fmt::Display, K: fmt::Display> fmt::Display for BrokerError<S, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BrokerError::Source(e) => write!(f, "message source failed: {}", e),
+            BrokerError::Sink(e) => write!(f, "message sink failed: {}", e),
+        }
+    }
+}
+
+impl<S: fmt::Debug + fmt::Display, K: fmt::Debug + fmt::Display> Error for BrokerError<S, K> {}
+
+/// Number of messages acknowledged and rejected while consuming
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConsumeCounts {
+    pub acked: usize,
+    pub nacked: usize,
+}
+
+impl<T, Args, I, O, E> Pied<T, Args, I, Result<O, E>>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    /// Runs every message of the source through the pipeline until the source is drained,
+    /// publishing each output to the sink before acknowledging the message and rejecting the
+    /// messages the pipeline failed on
+    pub async fn consume<S, K>(
+        &self,
+        mut source: S,
+        mut sink: K,
+    ) -> Result<ConsumeCounts, BrokerError<S::Error, K::Error>>
+    where
+        S: MessageSource<Message = I>,
+        K: MessageSink<O>,
+    {
+        let mut counts = ConsumeCounts::default();
+        while let Some(delivery) = source.receive().await.map_err(BrokerError::Source)? {
+            match self.middleware.call(delivery.message).await {
+                Ok(output) => {
+                    if let Err(e) = sink.publish(output).await {
+                        let _ = source.nack(delivery.token).await;
+                        return Err(BrokerError::Sink(e));
+                    }
+                    source
+                        .ack(delivery.token)
+                        .await
+                        .map_err(BrokerError::Source)?;
+                    counts.acked += 1;
+                }
+                Err(_) => {
+                    source
+                        .nack(delivery.token)
+                        .await
+                        .map_err(BrokerError::Source)?;
+                    counts.nacked += 1;
+                }
+            }
+        }
+        Ok(counts)
+    }
+}
+
+/// Error returned when publishing to a closed in-memory queue
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueClosed;
+
+impl fmt::Display for QueueClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("queue is closed")
+    }
+}
+
+impl Error for QueueClosed {}
+
+struct QueueState<T> {
+    tx: Mutex<Option<UnboundedSender<T>>>,
+    rx: async_lock::Mutex<UnboundedReceiver<T>>,
+    in_flight: Mutex<HashMap<u64, T>>,
+    dead_letters: Mutex<Vec<T>>,
+    next: AtomicU64,
+}
+
+/// In-memory reference queue usable as both a message source and sink; clones share the queue
+///
+/// Rejected messages are moved to the dead letters rather than redelivered.
+pub struct MemoryQueue<T> {
+    state: Arc<QueueState<T>>,
+}
+
+impl<T> MemoryQueue<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// Creates a new empty open queue
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::unbounded();
+        MemoryQueue {
+            state: Arc::new(QueueState {
+                tx: Mutex::new(Some(tx)),
+                rx: async_lock::Mutex::new(rx),
+                in_flight: Mutex::new(HashMap::new()),
+                dead_letters: Mutex::new(Vec::new()),
+                next: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Appends the message to the queue
+    pub fn push(&self, message: T) -> Result<(), QueueClosed> {
+        match &*self.state.tx.lock().unwrap() {
+            Some(tx) => tx.unbounded_send(message).map_err(|_| QueueClosed),
+            None => Err(QueueClosed),
+        }
+    }
+
+    /// Closes the queue, so receiving ends once the queued messages are drained
+    pub fn close(&self) {
+        self.state.tx.lock().unwrap().take();
+    }
+
+    /// Returns the number of messages received but not yet acknowledged or rejected
+    pub fn in_flight(&self) -> usize {
+        self.state.in_flight.lock().unwrap().len()
+    }
+
+    /// Returns the messages that were rejected
+    pub fn dead_letters(&self) -> Vec<T> {
+        self.state.dead_letters.lock().unwrap().clone()
+    }
+}
+
+impl<T> Default for MemoryQueue<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    fn default() -> Self {
+        MemoryQueue::new()
+    }
+}
+
+impl<T> Clone for MemoryQueue<T> {
+    fn clone(&self) -> Self {
+        MemoryQueue {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T> MessageSource for MemoryQueue<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    type Message = T;
+    type Token = u64;
+    type Error = Infallible;
+
+    async fn receive(&mut self) -> Result<Option<Delivery<T, u64>>, Infallible> {
+        let Some(message) = self.state.rx.lock().await.next().await else {
+            return Ok(None);
+        };
+        let token = self.state.next.fetch_add(1, Ordering::Relaxed);
+        self.state
+            .in_flight
+            .lock()
+            .unwrap()
+            .insert(token, message.clone());
+        Ok(Some(Delivery { message, token }))
+    }
+
+    async fn ack(&mut self, token: u64) -> Result<(), Infallible> {
+        self.state.in_flight.lock().unwrap().remove(&token);
+        Ok(())
+    }
+
+    async fn nack(&mut self, token: u64) -> Result<(), Infallible> {
+        if let Some(message) = self.state.in_flight.lock().unwrap().remove(&token) {
+            self.state.dead_letters.lock().unwrap().push(message);
+        }
+        Ok(())
+    }
+}
+
+impl<T> MessageSink<T> for MemoryQueue<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    type Error = QueueClosed;
+
+    async fn publish(&mut self, message: T) -> Result<(), QueueClosed> {
+        self.push(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn parse(s: String) -> Result<i32, String> {
+        s.parse().map_err(|_| s)
+    }
+
+    async fn double(i: Result<i32, String>) -> Result<i32, String> {
+        i.map(|i| i * 2)
+    }
+
+    #[async_std::test]
+    async fn test_consume_memory_queue() {
+        let (input, output) = (MemoryQueue::new(), MemoryQueue::new());
+        for s in ["1", "x", "3"] {
+            input.push(String::from(s)).unwrap();
+        }
+        input.close();
+
+        let m = (parse, double).pipe();
+        let counts = m.consume(input.clone(), output.clone()).await.unwrap();
+        assert_eq!(
+            ConsumeCounts {
+                acked: 2,
+                nacked: 1
+            },
+            counts
+        );
+        assert_eq!(0, input.in_flight());
+        assert_eq!(vec![String::from("x")], input.dead_letters());
+
+        output.close();
+        let mut output = output;
+        let mut published = Vec::new();
+        while let Some(delivery) = output.receive().await.unwrap() {
+            published.push(delivery.message);
+        }
+        assert_eq!(vec![2, 6], published);
+    }
+
+    #[async_std::test]
+    async fn test_consume_closed_sink() {
+        let (input, output) = (MemoryQueue::new(), MemoryQueue::<i32>::new());
+        input.push(String::from("1")).unwrap();
+        input.close();
+        output.close();
+
+        let m = (parse, double).pipe();
+        let err = m.consume(input.clone(), output).await.unwrap_err();
+        assert_eq!("message sink failed: queue is closed", err.to_string());
+        assert_eq!(vec![String::from("1")], input.dead_letters());
+    }
+}
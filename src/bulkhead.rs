@@ -0,0 +1,124 @@
+//! Isolation of a stage on its own bounded pool of tasks.
+
+use async_lock::{Mutex, Semaphore, SemaphoreGuardArc};
+use futures::{
+    channel::{mpsc, oneshot},
+    StreamExt,
+};
+use std::sync::Arc;
+
+use crate::{DynTransform, Middleware, Overloaded, Spawn, StageInfo, Transform};
+
+type Job<I, O> = (I, oneshot::Sender<O>, SemaphoreGuardArc);
+
+/// Runs a stage on a dedicated pool of worker tasks with a bounded queue, so a slow stage can
+/// only exhaust its own workers instead of the concurrency shared with other pipelines
+pub struct Bulkhead<I, O> {
+    jobs: mpsc::UnboundedSender<Job<I, O>>,
+    slots: Arc<Semaphore>,
+    stages: Vec<StageInfo>,
+}
+
+impl<I, O> Bulkhead<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Creates a new bulkhead spawning `workers` tasks that run the stage, queueing up to
+    /// `max_queued` calls beyond those and rejecting the rest with `Overloaded`. The workers
+    /// exit once the bulkhead is dropped.
+    pub fn new<Args>(
+        stage: impl Transform<Args, I, O>,
+        workers: usize,
+        max_queued: usize,
+        spawner: impl Spawn,
+    ) -> Self
+    where
+        Args: Send + Sync + 'static,
+    {
+        let stage: Arc<dyn DynTransform<Args, I, O>> = Arc::new(stage);
+        let stages = stage.stages();
+        let workers = workers.max(1);
+        let (jobs, rx) = mpsc::unbounded::<Job<I, O>>();
+        let rx = Arc::new(Mutex::new(rx));
+        for _ in 0..workers {
+            let (stage, rx) = (stage.clone(), rx.clone());
+            spawner.spawn(Box::pin(async move {
+                loop {
+                    let job = rx.lock().await.next().await;
+                    let Some((input, output, _slot)) = job else {
+                        break;
+                    };
+                    let _ = output.send(stage.transform(input).await);
+                }
+            }));
+        }
+        Bulkhead {
+            jobs,
+            slots: Arc::new(Semaphore::new(workers + max_queued)),
+            stages,
+        }
+    }
+}
+
+/// Implements the transform trait on the bulkhead (for downstream)
+impl<I, O> Transform<(I, Result<O, Overloaded>), I, Result<O, Overloaded>> for Bulkhead<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> Result<O, Overloaded> {
+        let slot = self.slots.try_acquire_arc().ok_or(Overloaded)?;
+        let (tx, rx) = oneshot::channel();
+        self.jobs
+            .unbounded_send((input, tx, slot))
+            .map_err(|_| Overloaded)?;
+        Ok(rx.await.expect("bulkhead worker panicked"))
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stages.clone()
+    }
+}
+
+/// Implements the middleware trait on the bulkhead
+impl<I, O> Middleware<I, Result<O, Overloaded>> for Bulkhead<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> Result<O, Overloaded> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task::{self, sleep};
+    use futures::future::{join_all, BoxFuture};
+    use std::time::Duration;
+
+    fn spawner(future: BoxFuture<'static, ()>) {
+        task::spawn(future);
+    }
+
+    async fn slow(i: i32) -> i32 {
+        sleep(Duration::from_millis(20)).await;
+        i * 2
+    }
+
+    #[async_std::test]
+    async fn test_bulkhead() {
+        let m = Bulkhead::new(slow, 2, 1, spawner);
+        let outputs = join_all((0..4).map(|i| m.call(i))).await;
+        assert_eq!(vec![Ok(0), Ok(2), Ok(4), Err(Overloaded)], outputs);
+
+        // slots are released once the calls complete
+        assert_eq!(Ok(8), m.call(4).await);
+    }
+}
@@ -0,0 +1,199 @@
+//! Memoizing cache middleware.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{rt::Instant, DynTransform, Middleware, Pied, StageInfo, Transform};
+
+struct Entry<O> {
+    output: O,
+    inserted: Instant,
+    used: u64,
+}
+
+/// Least-recently-used map of outputs keyed by input
+pub(crate) struct Lru<I, O> {
+    entries: HashMap<I, Entry<O>>,
+    order: BTreeMap<u64, I>,
+    tick: u64,
+}
+
+impl<I: Hash + Eq + Clone, O: Clone> Lru<I, O> {
+    /// Creates a new empty map
+    pub(crate) fn new() -> Self {
+        Lru {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Returns the cached output if it is still fresh, marking it as recently used
+    pub(crate) fn get(&mut self, input: &I, ttl: Option<Duration>) -> Option<O> {
+        let entry = self.entries.get_mut(input)?;
+        if ttl.is_some_and(|ttl| entry.inserted.elapsed() >= ttl) {
+            self.order.remove(&entry.used);
+            self.entries.remove(input);
+            return None;
+        }
+        self.tick += 1;
+        self.order.remove(&entry.used);
+        self.order.insert(self.tick, input.clone());
+        entry.used = self.tick;
+        Some(entry.output.clone())
+    }
+
+    /// Stores the output, evicting the least recently used entry when over capacity
+    pub(crate) fn insert(&mut self, input: I, output: O, capacity: usize) {
+        self.tick += 1;
+        let entry = Entry {
+            output,
+            inserted: Instant::now(),
+            used: self.tick,
+        };
+        if let Some(old) = self.entries.insert(input.clone(), entry) {
+            self.order.remove(&old.used);
+        }
+        self.order.insert(self.tick, input);
+        while self.entries.len() > capacity {
+            match self.order.pop_first() {
+                Some((_, oldest)) => self.entries.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+}
+
+/// Caches the outputs of a stage by input, bounded by capacity and optionally by age
+pub struct Cached<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    cache: Mutex<Lru<I, O>>,
+    capacity: usize,
+    ttl: Option<Duration>,
+}
+
+impl<Args, I, O> Cached<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Hash + Eq + Clone + Send + Sync + 'static,
+    O: Clone + Send + Sync + 'static,
+{
+    /// Creates a new cache holding up to `capacity` outputs that never expire
+    pub fn new(stage: impl Transform<Args, I, O>, capacity: usize) -> Self {
+        Cached {
+            stage: Arc::new(stage),
+            cache: Mutex::new(Lru::new()),
+            capacity: capacity.max(1),
+            ttl: None,
+        }
+    }
+
+    /// Sets how long a cached output is served before the stage is called again
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+}
+
+/// Implements the transform trait on the cache (for downstream)
+impl<Args, I, O> Transform<(I, O), I, O> for Cached<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Hash + Eq + Clone + Send + Sync + 'static,
+    O: Clone + Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        if let Some(output) = self.cache.lock().unwrap().get(&input, self.ttl) {
+            return output;
+        }
+        let output = self.stage.transform(input.clone()).await;
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(input, output.clone(), self.capacity);
+        output
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the cache
+impl<Args, I, O> Middleware<I, O> for Cached<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Hash + Eq + Clone + Send + Sync + 'static,
+    O: Clone + Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Hash + Eq + Clone + Send + Sync + 'static,
+    O: Clone + Send + Sync + 'static,
+{
+    /// Caches up to `capacity` outputs of the whole pipeline, each served for at most `ttl`
+    pub fn cached(self, capacity: usize, ttl: Duration) -> Pied<T, Args, I, O> {
+        self.rebuild(|pied| Cached::new(pied, capacity).ttl(ttl))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use async_std::task::sleep;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn counting(calls: Arc<AtomicUsize>) -> impl Fn(i32) -> futures::future::Ready<String> {
+        move |i| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            futures::future::ready(i.to_string())
+        }
+    }
+
+    #[async_std::test]
+    async fn test_cached_lru() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let m = Cached::new(counting(calls.clone()), 2);
+        assert_eq!(String::from("1"), m.call(1).await);
+        assert_eq!(String::from("2"), m.call(2).await);
+        assert_eq!(String::from("1"), m.call(1).await);
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+
+        // 2 is the least recently used, so it is evicted for 3
+        m.call(3).await;
+        m.call(1).await;
+        assert_eq!(3, calls.load(Ordering::SeqCst));
+        m.call(2).await;
+        assert_eq!(4, calls.load(Ordering::SeqCst));
+    }
+
+    #[async_std::test]
+    async fn test_cached_ttl() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let m = (|i: i32| async move { i * 32 }, counting(calls.clone()))
+            .pipe()
+            .cached(8, Duration::from_millis(20));
+        assert_eq!(String::from("64"), m.call(2).await);
+        assert_eq!(String::from("64"), m.call(2).await);
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+        sleep(Duration::from_millis(25)).await;
+        assert_eq!(String::from("64"), m.call(2).await);
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+}
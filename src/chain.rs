@@ -0,0 +1,379 @@
+//! Statically dispatched composition of stages.
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::{marker::PhantomData, ops::Shr};
+
+use crate::{sequence, AnyValue, DynTransform, Middleware, Pied, StageInfo, Transform};
+#[cfg(feature = "std")]
+use crate::{Profiler, Timed};
+
+/// Composes two stages by value, so the pipeline is monomorphized into a single unboxed future
+/// instead of going through `Arc<dyn DynTransform>` for every stage
+pub struct Chain<A, B, ArgsA, ArgsB, M> {
+    a: A,
+    b: B,
+    _phantom: PhantomData<(ArgsA, ArgsB, M)>,
+}
+
+/// Creates a new chain feeding the output of the first stage into the second
+pub fn chain<A, B, ArgsA, ArgsB, M>(a: A, b: B) -> Chain<A, B, ArgsA, ArgsB, M> {
+    Chain {
+        a,
+        b,
+        _phantom: PhantomData,
+    }
+}
+
+/// Chains any number of stages together by expanding into nested `chain` calls
+#[macro_export]
+macro_rules! chain {
+    ($first:expr, $second:expr $(, $rest:expr)* $(,)?) => {
+        $crate::chain!(@acc $crate::chain($first, $second) $(, $rest)*)
+    };
+    (@acc $acc:expr, $next:expr $(, $rest:expr)*) => {
+        $crate::chain!(@acc $crate::chain($acc, $next) $(, $rest)*)
+    };
+    (@acc $acc:expr) => {
+        $acc
+    };
+}
+
+/// Implements the transform trait on the chain (for downstream)
+impl<A, B, ArgsA, ArgsB, I, M, O> Transform<(I, O), I, O> for Chain<A, B, ArgsA, ArgsB, M>
+where
+    A: Transform<ArgsA, I, M>,
+    B: Transform<ArgsB, M, O>,
+    ArgsA: Send + Sync + 'static,
+    ArgsB: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    M: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let input = self.a.transform(input).await;
+        self.b.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        sequence(self.a.stages(), self.b.stages())
+    }
+}
+
+/// Implements the middleware trait on the chain
+impl<A, B, ArgsA, ArgsB, I, M, O> Middleware<I, O> for Chain<A, B, ArgsA, ArgsB, M>
+where
+    A: Transform<ArgsA, I, M>,
+    B: Transform<ArgsB, M, O>,
+    ArgsA: Send + Sync + 'static,
+    ArgsB: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    M: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Stage erased to take and return `AnyValue`, so stages of different types share a `FlatChain`
+struct Erased<S, Args, I, O> {
+    stage: S,
+    _phantom: PhantomData<(Args, I, O)>,
+}
+
+/// Implements the transform trait on the erased stage, downcasting its input
+impl<S, Args, I, O> Transform<(AnyValue, AnyValue), AnyValue, AnyValue> for Erased<S, Args, I, O>
+where
+    S: Transform<Args, I, O>,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: AnyValue) -> AnyValue {
+        let input = input
+            .downcast::<I>()
+            .expect("flat chain stages are appended with matching types");
+        Box::new(self.stage.transform(*input).await)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+type ErasedStage = Arc<dyn DynTransform<(AnyValue, AnyValue), AnyValue, AnyValue>>;
+
+/// Erased stages run one after the other in a loop, so appending with `then` or `>>` pushes onto
+/// a `Vec` instead of wrapping the whole pipeline in another erased call
+pub(crate) struct FlatChain<I, O> {
+    stages: Vec<ErasedStage>,
+    _phantom: PhantomData<(I, O)>,
+}
+
+impl<I, O> FlatChain<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Creates a new chain of a single stage
+    pub(crate) fn new<Args>(stage: impl Transform<Args, I, O>) -> Self
+    where
+        Args: Send + Sync + 'static,
+    {
+        FlatChain {
+            stages: Vec::new(),
+            _phantom: PhantomData,
+        }
+        .push(stage)
+    }
+
+    /// Appends a stage to the end of the chain
+    pub(crate) fn push<Args, O2>(mut self, stage: impl Transform<Args, O, O2>) -> FlatChain<I, O2>
+    where
+        Args: Send + Sync + 'static,
+        O2: Send + Sync + 'static,
+    {
+        self.stages.push(Arc::new(Erased {
+            stage,
+            _phantom: PhantomData,
+        }));
+        FlatChain {
+            stages: self.stages,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Appends the stages of another chain to the end of the chain
+    pub(crate) fn append<O2>(mut self, other: FlatChain<O, O2>) -> FlatChain<I, O2> {
+        self.stages.extend(other.stages);
+        FlatChain {
+            stages: self.stages,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<I, O> Clone for FlatChain<I, O> {
+    fn clone(&self) -> Self {
+        FlatChain {
+            stages: self.stages.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Implements the transform trait on the flat chain (for downstream)
+impl<I, O> Transform<(I, O), I, O> for FlatChain<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let mut value: AnyValue = Box::new(input);
+        for stage in &self.stages {
+            value = stage.transform(value).await;
+        }
+        *value
+            .downcast::<O>()
+            .expect("flat chain stages are appended with matching types")
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        let stages = self.stages.iter().flat_map(|stage| stage.stages_dyn());
+        sequence(Vec::new(), stages.collect())
+    }
+}
+
+/// Implements the middleware trait on the flat chain
+impl<I, O> Middleware<I, O> for FlatChain<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Wraps a flat chain as a pipeline, keeping the chain so later stages are appended to it
+    pub(crate) fn from_chain(chain: FlatChain<I, O>) -> Self {
+        let chain = Arc::new(chain);
+        Pied {
+            middleware: chain.clone(),
+            chain: Some(chain),
+            #[cfg(feature = "std")]
+            profiler: None,
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
+        }
+    }
+
+    /// Returns the flat chain of the pipeline, or a chain of the whole pipeline as one stage;
+    /// the stages are only copied when the pipeline was cloned
+    fn into_chain(self) -> FlatChain<I, O> {
+        let Pied {
+            middleware, chain, ..
+        } = self;
+        match chain {
+            Some(chain) => {
+                drop(middleware);
+                Arc::try_unwrap(chain).unwrap_or_else(|chain| (*chain).clone())
+            }
+            None => FlatChain::new(middleware),
+        }
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Appends a stage to the end of the pipeline, keeping the pipeline type parameters so the
+    /// result can be reassigned while building a pipeline in a loop; the stages are kept in a
+    /// flat list run in a loop, so waking a stage doesn't poll through every stage before it
+    /// and long pipelines don't grow the stack
+    pub fn then<ArgsN, O2>(self, next: impl Transform<ArgsN, O, O2>) -> Pied<T, Args, I, O2>
+    where
+        ArgsN: Send + Sync + 'static,
+        O2: Send + Sync + 'static,
+    {
+        #[cfg(feature = "std")]
+        if let Some(profiler) = self.profiler.as_ref().map(|profiler| profiler.fork()) {
+            let chain = self.into_chain().push(Timed::new(next, &profiler));
+            return Pied {
+                profiler: Some(profiler),
+                ..Pied::from_chain(chain)
+            };
+        }
+        Pied::from_chain(self.into_chain().push(next))
+    }
+}
+
+/// Composes two pipelines with `a >> b`, feeding the output of the left into the right
+impl<T, Args, I, O, T2, Args2, O2> Shr<Pied<T2, Args2, O, O2>> for Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    T2: Send + Sync + 'static,
+    Args2: Send + Sync + 'static,
+    O2: Send + Sync + 'static,
+{
+    type Output = Pied<(T, T2), (Args, Args2), I, O2>;
+
+    fn shr(self, next: Pied<T2, Args2, O, O2>) -> Self::Output {
+        #[cfg(feature = "std")]
+        if self.profiler.is_some() || next.profiler.is_some() {
+            // a side without a profiler is timed as a single stage
+            let profiler = Arc::new(Profiler::default());
+            let first = match self.profiler.clone() {
+                Some(first) => {
+                    profiler.extend(&first);
+                    self.into_chain()
+                }
+                None => FlatChain::new(Timed::new(self, &profiler)),
+            };
+            let second = match next.profiler.clone() {
+                Some(second) => {
+                    profiler.extend(&second);
+                    next.into_chain()
+                }
+                None => FlatChain::new(Timed::new(next, &profiler)),
+            };
+            return Pied {
+                profiler: Some(profiler),
+                ..Pied::from_chain(first.append(second))
+            };
+        }
+        Pied::from_chain(self.into_chain().append(next.into_chain()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DynMiddleware, Piper};
+    use std::sync::Arc;
+
+    async fn producer() -> i32 {
+        3
+    }
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_chain() {
+        let m = chain(multipler, stringer);
+        assert_eq!(String::from("64"), m.call(2).await);
+
+        let m = chain!(producer, multipler, multipler, stringer);
+        assert_eq!(String::from("3072"), m.call(()).await);
+        assert_eq!(4, Transform::stages(&m).len());
+
+        // mixes with dynamically dispatched pipelines
+        let m = (chain!(multipler, multipler), stringer).pipe();
+        assert_eq!(String::from("1024"), m.call(1).await);
+    }
+
+    #[async_std::test]
+    async fn test_shr_pipelines() {
+        let parse = (multipler, multipler).pipe();
+        let format = (multipler, stringer).pipe();
+        let m = parse >> format;
+        assert_eq!(String::from("32768"), m.call(1).await);
+        assert_eq!(4, m.stages().len());
+    }
+
+    #[async_std::test]
+    async fn test_then() {
+        let mut m = (multipler, multipler).pipe();
+        for _ in 0..2 {
+            m = m.then(multipler);
+        }
+        let m = m.then(stringer);
+        assert_eq!(String::from("1048576"), m.call(1).await);
+        assert_eq!(5, m.stages().len());
+    }
+
+    #[async_std::test]
+    async fn test_then_flat() {
+        // deep enough to overflow the stack if every stage nested another erased call
+        let mut m = crate::pipe_fn(multipler);
+        for _ in 0..20_000 {
+            m = m.then(|i: i32| async move { i + 1 });
+        }
+        let m = (m.clone() >> m).then(stringer);
+        assert_eq!(String::from("661024"), m.call(1).await);
+        assert_eq!(40_003, m.stages().len());
+        assert_eq!(40_002, m.stages()[40_002].position);
+    }
+
+    #[async_std::test]
+    async fn test_chain_erased() {
+        let m: Arc<dyn DynMiddleware<i32, String>> = Arc::new(chain!(multipler, stringer));
+        assert_eq!(String::from("96"), m.call(3).await);
+    }
+}
@@ -0,0 +1,248 @@
+//! Driving pipelines from channel receivers into channel senders.
+
+use futures::{
+    channel::mpsc,
+    future::BoxFuture,
+    stream::{self, BoxStream},
+    SinkExt, StreamExt,
+};
+use std::{
+    future::{Future, IntoFuture},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::{BoxMiddleware, Middleware, Pied};
+
+/// Receiving half of a channel that a pipeline can be driven from
+pub trait ChannelReceiver<T>: Send + 'static {
+    /// Receives the next item, or `None` once every sender is gone
+    fn recv(&mut self) -> impl Future<Output = Option<T>> + Send;
+}
+
+/// Sending half of a channel that a pipeline can write its outputs into
+pub trait ChannelSender<T>: Send + 'static {
+    /// Sends the item, waiting for capacity; returns false once the receiver is gone
+    fn send(&mut self, item: T) -> impl Future<Output = bool> + Send;
+}
+
+impl<T: Send + 'static> ChannelReceiver<T> for mpsc::Receiver<T> {
+    async fn recv(&mut self) -> Option<T> {
+        self.next().await
+    }
+}
+
+impl<T: Send + 'static> ChannelReceiver<T> for mpsc::UnboundedReceiver<T> {
+    async fn recv(&mut self) -> Option<T> {
+        self.next().await
+    }
+}
+
+impl<T: Send + 'static> ChannelSender<T> for mpsc::Sender<T> {
+    async fn send(&mut self, item: T) -> bool {
+        SinkExt::send(self, item).await.is_ok()
+    }
+}
+
+impl<T: Send + 'static> ChannelSender<T> for mpsc::UnboundedSender<T> {
+    async fn send(&mut self, item: T) -> bool {
+        self.unbounded_send(item).is_ok()
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl<T: Send + 'static> ChannelReceiver<T> for async_std::channel::Receiver<T> {
+    async fn recv(&mut self) -> Option<T> {
+        async_std::channel::Receiver::recv(self).await.ok()
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl<T: Send + 'static> ChannelSender<T> for async_std::channel::Sender<T> {
+    async fn send(&mut self, item: T) -> bool {
+        async_std::channel::Sender::send(self, item).await.is_ok()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T: Send + 'static> ChannelReceiver<T> for tokio::sync::mpsc::Receiver<T> {
+    async fn recv(&mut self) -> Option<T> {
+        tokio::sync::mpsc::Receiver::recv(self).await
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T: Send + 'static> ChannelReceiver<T> for tokio::sync::mpsc::UnboundedReceiver<T> {
+    async fn recv(&mut self) -> Option<T> {
+        tokio::sync::mpsc::UnboundedReceiver::recv(self).await
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T: Send + 'static> ChannelSender<T> for tokio::sync::mpsc::Sender<T> {
+    async fn send(&mut self, item: T) -> bool {
+        tokio::sync::mpsc::Sender::send(self, item).await.is_ok()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T: Send + 'static> ChannelSender<T> for tokio::sync::mpsc::UnboundedSender<T> {
+    async fn send(&mut self, item: T) -> bool {
+        tokio::sync::mpsc::UnboundedSender::send(self, item).is_ok()
+    }
+}
+
+/// Shared counts of the items a channel-driven pipeline has received and sent
+#[derive(Clone, Debug, Default)]
+pub struct ChannelCounts {
+    received: Arc<AtomicUsize>,
+    sent: Arc<AtomicUsize>,
+}
+
+impl ChannelCounts {
+    /// Returns the number of items received from the channel so far
+    pub fn received(&self) -> usize {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of outputs sent into the channel so far
+    pub fn sent(&self) -> usize {
+        self.sent.load(Ordering::Relaxed)
+    }
+}
+
+/// Pipeline reading its inputs from a channel receiver, waiting for a sender for its outputs
+pub struct FromReceiver<I, O> {
+    middleware: BoxMiddleware<I, O>,
+    inputs: BoxStream<'static, I>,
+    concurrency: usize,
+    counts: ChannelCounts,
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Drives the pipeline from the receiver, with up to `concurrency` calls in flight at once
+    pub fn from_receiver(
+        &self,
+        rx: impl ChannelReceiver<I>,
+        concurrency: usize,
+    ) -> FromReceiver<I, O> {
+        let counts = ChannelCounts::default();
+        let received = counts.received.clone();
+        let inputs = stream::unfold(rx, |mut rx| async move {
+            let input = rx.recv().await?;
+            Some((input, rx))
+        })
+        .inspect(move |_| {
+            received.fetch_add(1, Ordering::Relaxed);
+        });
+        FromReceiver {
+            middleware: self.middleware.clone(),
+            inputs: inputs.boxed(),
+            concurrency: concurrency.max(1),
+            counts,
+        }
+    }
+}
+
+impl<I, O> FromReceiver<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Writes the outputs into the sender, returning the handle that runs the pipeline
+    pub fn to_sender(self, mut tx: impl ChannelSender<O>) -> ChannelHandle {
+        let FromReceiver {
+            middleware,
+            inputs,
+            concurrency,
+            counts,
+        } = self;
+        let sent = counts.sent.clone();
+        let mut outputs = inputs
+            .map(move |input| {
+                let middleware = middleware.clone();
+                async move { middleware.call(input).await }
+            })
+            .buffered(concurrency);
+        ChannelHandle {
+            counts,
+            run: Box::pin(async move {
+                while let Some(output) = outputs.next().await {
+                    if !tx.send(output).await {
+                        break;
+                    }
+                    sent.fetch_add(1, Ordering::Relaxed);
+                }
+            }),
+        }
+    }
+}
+
+/// Handle to a channel-driven pipeline; awaiting it (or spawning it) runs the pipeline until the
+/// receiver is drained or the sender is closed, resolving to the final counts
+pub struct ChannelHandle {
+    counts: ChannelCounts,
+    run: BoxFuture<'static, ()>,
+}
+
+impl ChannelHandle {
+    /// Returns the live counts of the pipeline, which remain readable after it was spawned
+    pub fn counts(&self) -> ChannelCounts {
+        self.counts.clone()
+    }
+}
+
+impl IntoFuture for ChannelHandle {
+    type Output = ChannelCounts;
+    type IntoFuture = BoxFuture<'static, ChannelCounts>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            self.run.await;
+            self.counts
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn multipler(i: u64) -> u64 {
+        i * 32
+    }
+
+    async fn stringer(i: u64) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_channel_pipeline() {
+        let m = (multipler, stringer).pipe();
+        let (mut input, rx) = mpsc::channel(4);
+        let (tx, output) = mpsc::unbounded();
+        let handle = m.from_receiver(rx, 2).to_sender(tx);
+        let counts = handle.counts();
+        let run = async_std::task::spawn(handle.into_future());
+
+        for i in 0..3 {
+            SinkExt::send(&mut input, i).await.unwrap();
+        }
+        drop(input);
+        let outputs: Vec<String> = output.collect().await;
+        assert_eq!(vec!["0", "32", "64"], outputs);
+
+        let done = run.await;
+        assert_eq!((3, 3), (done.received(), done.sent()));
+        assert_eq!(3, counts.sent());
+    }
+}
@@ -0,0 +1,201 @@
+//! Concurrency limiting middleware.
+
+use async_lock::Semaphore;
+use std::{
+    error::Error,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::{util::InFlight, DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// Error returned when a call is rejected because the stage is saturated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Overloaded;
+
+impl fmt::Display for Overloaded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("service overloaded")
+    }
+}
+
+impl Error for Overloaded {}
+
+/// Bounds how many calls execute a stage concurrently, queueing the rest
+pub struct ConcurrencyLimit<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    semaphore: Semaphore,
+}
+
+impl<Args, I, O> ConcurrencyLimit<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Creates a new limit letting at most `max` calls run the stage at once
+    pub fn new(stage: impl Transform<Args, I, O>, max: usize) -> Self {
+        ConcurrencyLimit {
+            stage: Arc::new(stage),
+            semaphore: Semaphore::new(max.max(1)),
+        }
+    }
+
+    /// Bounds the queue of waiting calls, rejecting calls with `Overloaded` once it is full.
+    /// A queue of zero sheds every call that cannot run immediately.
+    pub fn with_queue(self, max_queued: usize) -> BoundedConcurrencyLimit<Args, I, O> {
+        BoundedConcurrencyLimit {
+            limit: self,
+            queued: AtomicUsize::new(0),
+            max_queued,
+        }
+    }
+}
+
+/// Implements the transform trait on the concurrency limit (for downstream)
+impl<Args, I, O> Transform<(I, O), I, O> for ConcurrencyLimit<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let _permit = self.semaphore.acquire().await;
+        self.stage.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the concurrency limit
+impl<Args, I, O> Middleware<I, O> for ConcurrencyLimit<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Concurrency limit with a bounded queue that sheds calls once saturated
+pub struct BoundedConcurrencyLimit<Args, I, O> {
+    limit: ConcurrencyLimit<Args, I, O>,
+    queued: AtomicUsize,
+    max_queued: usize,
+}
+
+/// Implements the transform trait on the bounded concurrency limit (for downstream)
+impl<Args, I, O> Transform<(I, Result<O, Overloaded>), I, Result<O, Overloaded>>
+    for BoundedConcurrencyLimit<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> Result<O, Overloaded> {
+        let _permit = match self.limit.semaphore.try_acquire() {
+            Some(permit) => permit,
+            None => {
+                if self.queued.fetch_add(1, Ordering::SeqCst) >= self.max_queued {
+                    self.queued.fetch_sub(1, Ordering::SeqCst);
+                    return Err(Overloaded);
+                }
+                let _queued = InFlight(&self.queued);
+                self.limit.semaphore.acquire().await
+            }
+        };
+        Ok(self.limit.stage.transform(input).await)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.limit.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the bounded concurrency limit
+impl<Args, I, O> Middleware<I, Result<O, Overloaded>> for BoundedConcurrencyLimit<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> Result<O, Overloaded> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Bounds how many calls execute the whole pipeline concurrently
+    pub fn concurrency_limit(self, max: usize) -> Pied<T, Args, I, O> {
+        self.rebuild(|pied| ConcurrencyLimit::new(pied, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use async_std::task::sleep;
+    use futures::future::join_all;
+    use std::time::Duration;
+
+    #[async_std::test]
+    async fn test_concurrency_limit() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (r, p) = (running.clone(), peak.clone());
+        let tracked = move |i: i32| {
+            let (running, peak) = (r.clone(), p.clone());
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                sleep(Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                i
+            }
+        };
+        let m = (tracked, |i: i32| async move { i * 2 })
+            .pipe()
+            .concurrency_limit(2);
+        let outputs = join_all((0..6).map(|i| m.call(i))).await;
+        assert_eq!(vec![0, 2, 4, 6, 8, 10], outputs);
+        assert_eq!(2, peak.load(Ordering::SeqCst));
+    }
+
+    async fn slow(i: i32) -> i32 {
+        sleep(Duration::from_millis(20)).await;
+        i
+    }
+
+    #[async_std::test]
+    async fn test_concurrency_limit_sheds() {
+        let m = ConcurrencyLimit::new(slow, 1).with_queue(1);
+        let outputs = join_all((0..3).map(|i| m.call(i))).await;
+        assert_eq!(vec![Ok(0), Ok(1), Err(Overloaded)], outputs);
+
+        let m = ConcurrencyLimit::new(slow, 2).with_queue(0);
+        let outputs = join_all((0..3).map(|i| m.call(i))).await;
+        assert_eq!(vec![Ok(0), Ok(1), Err(Overloaded)], outputs);
+    }
+}
@@ -0,0 +1,194 @@
+//! Per-call context flowing alongside the value.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::Arc,
+};
+
+use crate::{DynTransform, Middleware, StageInfo, Transform};
+
+/// Type map of metadata attached to a call, keyed by the type of each value
+#[derive(Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    /// Creates an empty set of extensions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a value, returning the previous value of the same type if there was one
+    pub fn insert<V: Send + Sync + 'static>(&mut self, value: V) -> Option<V> {
+        self.map
+            .insert(TypeId::of::<V>(), Box::new(value))
+            .and_then(|prev| prev.downcast().ok().map(|prev| *prev))
+    }
+
+    /// Returns a reference to the value of the given type
+    pub fn get<V: Send + Sync + 'static>(&self) -> Option<&V> {
+        self.map
+            .get(&TypeId::of::<V>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    /// Returns a mutable reference to the value of the given type
+    pub fn get_mut<V: Send + Sync + 'static>(&mut self) -> Option<&mut V> {
+        self.map
+            .get_mut(&TypeId::of::<V>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    /// Removes and returns the value of the given type
+    pub fn remove<V: Send + Sync + 'static>(&mut self) -> Option<V> {
+        self.map
+            .remove(&TypeId::of::<V>())
+            .and_then(|value| value.downcast().ok().map(|value| *value))
+    }
+
+    /// Returns true if a value of the given type is present
+    pub fn contains<V: Send + Sync + 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<V>())
+    }
+
+    /// Returns the number of values stored
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns true if no values are stored
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+/// Value flowing through a pipeline together with the extensions of the call
+#[derive(Default)]
+pub struct Context<T> {
+    pub value: T,
+    pub extensions: Extensions,
+}
+
+impl<T> Context<T> {
+    /// Creates a new context around the value with no extensions
+    pub fn new(value: T) -> Self {
+        Context {
+            value,
+            extensions: Extensions::new(),
+        }
+    }
+
+    /// Replaces the value, keeping the extensions
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Context<U> {
+        Context {
+            value: f(self.value),
+            extensions: self.extensions,
+        }
+    }
+
+    /// Splits the context into the value and its extensions
+    pub fn into_parts(self) -> (T, Extensions) {
+        (self.value, self.extensions)
+    }
+}
+
+/// Runs a plain stage on the value of a context, carrying the extensions through untouched
+pub struct InContext<Args, T, O> {
+    stage: Arc<dyn DynTransform<Args, T, O>>,
+}
+
+/// Creates a new context-aware stage from a plain transform
+pub fn in_context<Args, T, O>(stage: impl Transform<Args, T, O>) -> InContext<Args, T, O>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    InContext {
+        stage: Arc::new(stage),
+    }
+}
+
+/// Implements the transform trait on the context-aware stage (for downstream)
+impl<Args, T, O> Transform<(Context<T>, Context<O>), Context<T>, Context<O>>
+    for InContext<Args, T, O>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: Context<T>) -> Context<O> {
+        let (value, extensions) = input.into_parts();
+        Context {
+            value: self.stage.transform(value).await,
+            extensions,
+        }
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the context-aware stage
+impl<Args, T, O> Middleware<Context<T>, Context<O>> for InContext<Args, T, O>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: Context<T>) -> Context<O> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    #[derive(Debug, PartialEq)]
+    struct RequestId(u32);
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    async fn tag(mut ctx: Context<i32>) -> Context<i32> {
+        ctx.extensions.insert(RequestId(ctx.value as u32));
+        ctx
+    }
+
+    #[test]
+    fn test_extensions() {
+        let mut ext = Extensions::new();
+        assert!(ext.is_empty());
+        assert_eq!(None, ext.insert(RequestId(1)));
+        assert_eq!(Some(RequestId(1)), ext.insert(RequestId(2)));
+        ext.insert(String::from("claims"));
+        assert_eq!(2, ext.len());
+
+        ext.get_mut::<RequestId>().unwrap().0 += 1;
+        assert_eq!(Some(&RequestId(3)), ext.get());
+        assert_eq!(Some(String::from("claims")), ext.remove());
+        assert!(!ext.contains::<String>());
+    }
+
+    #[async_std::test]
+    async fn test_context_pipeline() {
+        let m = (in_context(multipler), tag, in_context(stringer)).pipe();
+        let out = m.call(Context::new(2)).await;
+        assert_eq!("64", out.value);
+        assert_eq!(Some(&RequestId(64)), out.extensions.get());
+    }
+}
@@ -0,0 +1,140 @@
+//! End-to-end deadline budgets carried in the call context.
+
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    rt::{self, Instant},
+    Context, DynTransform, Elapsed, Middleware, StageInfo, Transform,
+};
+
+/// Point in time by which the whole call must complete, stored in the extensions of a context
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    /// Creates a deadline the duration from now
+    pub fn after(duration: Duration) -> Self {
+        Deadline(Instant::now() + duration)
+    }
+
+    /// Returns the instant of the deadline
+    pub fn instant(&self) -> Instant {
+        self.0
+    }
+
+    /// Returns the budget left before the deadline, zero once it has passed
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+
+    /// Returns true once the deadline has passed
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+}
+
+impl<T> Context<T> {
+    /// Attaches an overall deadline the duration from now to the call
+    pub fn with_deadline(mut self, duration: Duration) -> Self {
+        self.extensions.insert(Deadline::after(duration));
+        self
+    }
+
+    /// Returns the budget left for the call, or `None` when it has no deadline
+    pub fn remaining(&self) -> Option<Duration> {
+        self.extensions.get::<Deadline>().map(Deadline::remaining)
+    }
+}
+
+/// Runs a context stage within the remaining deadline budget of the call
+pub struct Budgeted<Args, T, O> {
+    stage: Arc<dyn DynTransform<Args, Context<T>, Context<O>>>,
+}
+
+/// Creates a new budgeted stage, skipped once the budget is exhausted and cut off when it runs out
+pub fn budgeted<Args, T, O>(
+    stage: impl Transform<Args, Context<T>, Context<O>>,
+) -> Budgeted<Args, T, O>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    Budgeted {
+        stage: Arc::new(stage),
+    }
+}
+
+/// Implements the transform trait on the budgeted stage (for downstream)
+impl<Args, T, O>
+    Transform<(Context<T>, Result<Context<O>, Elapsed>), Context<T>, Result<Context<O>, Elapsed>>
+    for Budgeted<Args, T, O>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: Context<T>) -> Result<Context<O>, Elapsed> {
+        match input.remaining() {
+            None => Ok(self.stage.transform(input).await),
+            Some(remaining) if remaining.is_zero() => Err(Elapsed(())),
+            Some(remaining) => rt::timeout(remaining, self.stage.transform(input)).await,
+        }
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the budgeted stage
+impl<Args, T, O> Middleware<Context<T>, Result<Context<O>, Elapsed>> for Budgeted<Args, T, O>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: Context<T>) -> Result<Context<O>, Elapsed> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::try_convert;
+    use async_std::task::sleep;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    async fn slow(ctx: Context<u64>) -> Context<u64> {
+        CALLS.fetch_add(1, Ordering::SeqCst);
+        sleep(Duration::from_millis(ctx.value)).await;
+        ctx
+    }
+
+    #[async_std::test]
+    async fn test_deadline_budget() {
+        let m = try_convert(budgeted(slow), budgeted(slow));
+        let out = m.call(Context::new(20)).await.unwrap();
+        assert_eq!(None, out.remaining());
+
+        let out = m.call(Context::new(1).with_deadline(Duration::from_secs(1)));
+        assert!(out.await.unwrap().remaining().unwrap() > Duration::ZERO);
+
+        CALLS.store(0, Ordering::SeqCst);
+        let out = m.call(Context::new(30).with_deadline(Duration::from_millis(45)));
+        assert_eq!(Some(Elapsed(())), out.await.err());
+        assert_eq!(2, CALLS.load(Ordering::SeqCst));
+
+        CALLS.store(0, Ordering::SeqCst);
+        let out = m.call(Context::new(30).with_deadline(Duration::ZERO));
+        assert_eq!(Some(Elapsed(())), out.await.err());
+        assert_eq!(0, CALLS.load(Ordering::SeqCst));
+    }
+}
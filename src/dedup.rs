@@ -0,0 +1,106 @@
+//! Dropping repeated items.
+
+use std::{hash::Hash, sync::Mutex, time::Duration};
+
+use crate::{cache::Lru, Middleware, StageInfo, Transform};
+
+/// Drops items whose key was already seen, remembering a bounded number of recent keys
+pub struct DedupByKey<T, K> {
+    key: Box<dyn Fn(&T) -> K + Send + Sync>,
+    seen: Mutex<Lru<K, ()>>,
+    capacity: usize,
+    window: Option<Duration>,
+}
+
+/// Creates a new dedup stage remembering up to `capacity` keys, emitting `None` for duplicates
+pub fn dedup_by_key<T, K>(
+    key: impl Fn(&T) -> K + Send + Sync + 'static,
+    capacity: usize,
+) -> DedupByKey<T, K>
+where
+    T: Send + Sync + 'static,
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+{
+    DedupByKey {
+        key: Box::new(key),
+        seen: Mutex::new(Lru::new()),
+        capacity: capacity.max(1),
+        window: None,
+    }
+}
+
+impl<T, K> DedupByKey<T, K> {
+    /// Sets how long a key is remembered after it was first seen
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = Some(window);
+        self
+    }
+}
+
+/// Implements the transform trait on the dedup stage (for downstream)
+impl<T, K> Transform<(T, Option<T>), T, Option<T>> for DedupByKey<T, K>
+where
+    T: Send + Sync + 'static,
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> Option<T> {
+        let key = (self.key)(&input);
+        let mut seen = self.seen.lock().unwrap();
+        if seen.get(&key, self.window).is_some() {
+            return None;
+        }
+        seen.insert(key, (), self.capacity);
+        Some(input)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::branching::<T, Option<T>>("dedup", Vec::new())]
+    }
+}
+
+/// Implements the middleware trait on the dedup stage
+impl<T, K> Middleware<T, Option<T>> for DedupByKey<T, K>
+where
+    T: Send + Sync + 'static,
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> Option<T> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{on_some, Piper};
+    use async_std::task::sleep;
+    use futures::{stream, StreamExt};
+
+    async fn stringer(i: u32) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_dedup_by_key() {
+        let m = (dedup_by_key(|i: &u32| i % 10, 2), on_some(stringer)).pipe();
+        let outputs: Vec<_> = m
+            .call_stream(stream::iter([1, 11, 2, 3, 21, 12]), 1)
+            .filter_map(|o| async move { o })
+            .collect()
+            .await;
+        assert_eq!(vec!["1", "2", "3", "21", "12"], outputs);
+    }
+
+    #[async_std::test]
+    async fn test_dedup_window() {
+        let m = dedup_by_key(|s: &&str| s.len(), 8).window(Duration::from_millis(20));
+        assert_eq!(Some("a"), m.call("a").await);
+        assert_eq!(None, m.call("b").await);
+        sleep(Duration::from_millis(30)).await;
+        assert_eq!(Some("c"), m.call("c").await);
+    }
+}
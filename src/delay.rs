@@ -0,0 +1,101 @@
+//! Latency injection for pacing and chaos testing.
+
+use std::{marker::PhantomData, ops::Range, time::Duration};
+
+use crate::{rt, util::random, Middleware, Transform};
+
+/// Stage sleeping for a fixed or randomly jittered duration before forwarding its input unchanged
+pub struct Delay<T> {
+    min: Duration,
+    spread: Duration,
+    _phantom: PhantomData<fn(T) -> T>,
+}
+
+/// Creates a new stage sleeping for the duration before every call
+pub fn delay<T>(duration: Duration) -> Delay<T> {
+    Delay {
+        min: duration,
+        spread: Duration::ZERO,
+        _phantom: PhantomData,
+    }
+}
+
+/// Creates a new stage sleeping for a random duration within the range before every call
+pub fn jitter<T>(range: Range<Duration>) -> Delay<T> {
+    Delay {
+        min: range.start,
+        spread: range.end.saturating_sub(range.start),
+        _phantom: PhantomData,
+    }
+}
+
+impl<T> Delay<T> {
+    /// Picks the duration of the next sleep
+    fn next_delay(&self) -> Duration {
+        self.min + self.spread.mul_f64(random())
+    }
+}
+
+impl<T> Clone for Delay<T> {
+    fn clone(&self) -> Self {
+        Delay {
+            min: self.min,
+            spread: self.spread,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Implements the transform trait on the delay stage (for downstream)
+impl<T> Transform<(T, T), T, T> for Delay<T>
+where
+    T: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> T {
+        rt::sleep(self.next_delay()).await;
+        input
+    }
+}
+
+/// Implements the middleware trait on the delay stage
+impl<T> Middleware<T, T> for Delay<T>
+where
+    T: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> T {
+        self.transform(input).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use std::time::Instant;
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    #[async_std::test]
+    async fn test_delay() {
+        let m = (delay(Duration::from_millis(20)), multipler).pipe();
+        let start = Instant::now();
+        assert_eq!(64, m.call(2).await);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[async_std::test]
+    async fn test_jitter() {
+        let stage = jitter::<i32>(Duration::from_millis(5)..Duration::from_millis(15));
+        for _ in 0..20 {
+            let d = stage.next_delay();
+            assert!(d >= Duration::from_millis(5) && d < Duration::from_millis(15));
+        }
+
+        let m = (multipler, stage).pipe();
+        let start = Instant::now();
+        assert_eq!(96, m.call(3).await);
+        assert!(start.elapsed() >= Duration::from_millis(5));
+    }
+}
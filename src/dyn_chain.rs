@@ -0,0 +1,141 @@
+//! Runtime-assembled chains of same-typed stages.
+
+use crate::{into_middleware, sequence, BoxMiddleware, Middleware, StageInfo, Transform};
+
+/// Chain of `T -> T` stages whose length is decided at runtime (e.g. from configuration)
+pub struct DynChain<T> {
+    stages: Vec<BoxMiddleware<T, T>>,
+}
+
+impl<T> DynChain<T>
+where
+    T: Send + Sync + 'static,
+{
+    /// Creates a new empty chain, which passes its input through unchanged
+    pub fn new() -> Self {
+        DynChain { stages: Vec::new() }
+    }
+
+    /// Appends a stage to the end of the chain
+    pub fn push<Args>(&mut self, stage: impl Transform<Args, T, T>)
+    where
+        Args: Send + Sync + 'static,
+    {
+        self.stages.push(into_middleware(stage));
+    }
+
+    /// Inserts a stage at the position, shifting the later stages back
+    pub fn insert<Args>(&mut self, index: usize, stage: impl Transform<Args, T, T>)
+    where
+        Args: Send + Sync + 'static,
+    {
+        self.stages.insert(index, into_middleware(stage));
+    }
+
+    /// Removes and returns the stage at the position
+    pub fn remove(&mut self, index: usize) -> BoxMiddleware<T, T> {
+        self.stages.remove(index)
+    }
+
+    /// Returns the number of stages in the chain
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// Returns whether the chain has no stages
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+}
+
+impl<T> Default for DynChain<T>
+where
+    T: Send + Sync + 'static,
+{
+    fn default() -> Self {
+        DynChain::new()
+    }
+}
+
+impl<T> Clone for DynChain<T> {
+    fn clone(&self) -> Self {
+        DynChain {
+            stages: self.stages.clone(),
+        }
+    }
+}
+
+/// Implements the transform trait on the chain (for downstream)
+impl<T> Transform<(T, T), T, T> for DynChain<T>
+where
+    T: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> T {
+        let mut value = input;
+        for stage in &self.stages {
+            value = stage.call(value).await;
+        }
+        value
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stages.iter().fold(Vec::new(), |stages, stage| {
+            sequence(stages, stage.stages_dyn())
+        })
+    }
+}
+
+/// Implements the middleware trait on the chain
+impl<T> Middleware<T, T> for DynChain<T>
+where
+    T: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> T {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn incr(i: i32) -> i32 {
+        i + 1
+    }
+
+    async fn double(i: i32) -> i32 {
+        i * 2
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_dyn_chain() {
+        let mut chain = DynChain::new();
+        assert_eq!(5, chain.call(5).await);
+
+        for name in ["incr", "double", "incr"] {
+            match name {
+                "incr" => chain.push(incr),
+                _ => chain.push(double),
+            }
+        }
+        assert_eq!(3, chain.len());
+        assert_eq!(13, chain.call(5).await);
+
+        chain.insert(0, double);
+        assert_eq!(23, chain.call(5).await);
+        chain.remove(2);
+        assert_eq!(12, chain.call(5).await);
+
+        let m = (chain, stringer).pipe();
+        assert_eq!(String::from("4"), m.call(1).await);
+    }
+}
@@ -0,0 +1,137 @@
+//! Fan-in of sum-typed outputs.
+
+use alloc::{sync::Arc, vec, vec::Vec};
+
+use crate::{DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// Sum type of two alternatives, re-exported for stages branching their output
+pub use futures::future::Either;
+
+/// Routes each variant of an `Either` input to its own stage, merging into a common output
+pub struct Unify<AL, AR, L, R, O> {
+    left: Arc<dyn DynTransform<AL, L, O>>,
+    right: Arc<dyn DynTransform<AR, R, O>>,
+}
+
+/// Creates a new stage running `left` on `Either::Left` inputs and `right` on `Either::Right`
+/// inputs
+pub fn unify<AL, AR, L, R, O>(
+    left: impl Transform<AL, L, O>,
+    right: impl Transform<AR, R, O>,
+) -> Unify<AL, AR, L, R, O>
+where
+    AL: Send + Sync + 'static,
+    AR: Send + Sync + 'static,
+    L: Send + Sync + 'static,
+    R: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    Unify {
+        left: Arc::new(left),
+        right: Arc::new(right),
+    }
+}
+
+/// Implements the transform trait on the unifying stage (for downstream)
+impl<AL, AR, L, R, O> Transform<(Either<L, R>, O), Either<L, R>, O> for Unify<AL, AR, L, R, O>
+where
+    AL: Send + Sync + 'static,
+    AR: Send + Sync + 'static,
+    L: Send + Sync + 'static,
+    R: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: Either<L, R>) -> O {
+        match input {
+            Either::Left(input) => self.left.transform(input).await,
+            Either::Right(input) => self.right.transform(input).await,
+        }
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::branching::<Either<L, R>, O>(
+            "unify",
+            vec![
+                ("left".into(), self.left.stages()),
+                ("right".into(), self.right.stages()),
+            ],
+        )]
+    }
+}
+
+/// Implements the middleware trait on the unifying stage
+impl<AL, AR, L, R, O> Middleware<Either<L, R>, O> for Unify<AL, AR, L, R, O>
+where
+    AL: Send + Sync + 'static,
+    AR: Send + Sync + 'static,
+    L: Send + Sync + 'static,
+    R: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: Either<L, R>) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I, L, R> Pied<T, Args, I, Either<L, R>>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    L: Send + Sync + 'static,
+    R: Send + Sync + 'static,
+{
+    /// Routes each variant of the pipeline output to its own sub-pipeline, merging the results
+    pub fn unify<AL, AR, O>(
+        self,
+        left: impl Transform<AL, L, O>,
+        right: impl Transform<AR, R, O>,
+    ) -> Pied<T, Args, I, O>
+    where
+        AL: Send + Sync + 'static,
+        AR: Send + Sync + 'static,
+        O: Send + Sync + 'static,
+    {
+        self.then(unify(left, right))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn classify(s: String) -> Either<i32, String> {
+        match s.parse() {
+            Ok(i) => Either::Left(i),
+            Err(_) => Either::Right(s),
+        }
+    }
+
+    async fn describe_number(i: i32) -> String {
+        format!("number {}", i * 2)
+    }
+
+    async fn describe_word(s: String) -> String {
+        format!("word {}", s.to_uppercase())
+    }
+
+    #[async_std::test]
+    async fn test_unify() {
+        let m = (classify, unify(describe_number, describe_word)).pipe();
+        assert_eq!(String::from("number 42"), m.call(String::from("21")).await);
+        assert_eq!(String::from("word HI"), m.call(String::from("hi")).await);
+
+        let m = (|s: String| async move { s }, classify).pipe().unify(
+            (describe_number, |s: String| async move { s.len() }).pipe(),
+            |s: String| async move { s.len() },
+        );
+        assert_eq!(8, m.call(String::from("4")).await);
+        assert_eq!(3, m.call(String::from("abc")).await);
+        assert_eq!(3, m.stages().len());
+    }
+}
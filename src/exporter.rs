@@ -0,0 +1,107 @@
+//! Reporting stage metrics to the `metrics` facade, e.g. for a Prometheus exporter.
+
+use metrics_facade::{counter, describe_counter, describe_histogram, histogram, Unit};
+use std::{borrow::Cow, time::Duration};
+
+use crate::MetricsObserver;
+
+/// Metrics observer recording per-stage call and error counters and a duration histogram with
+/// the installed `metrics` recorder, labelled by pipeline and stage name
+#[derive(Clone, Debug)]
+pub struct MetricsExporter {
+    pipeline: Cow<'static, str>,
+}
+
+impl MetricsExporter {
+    /// Creates a new exporter labelling every metric with the pipeline name
+    pub fn new(pipeline: impl Into<Cow<'static, str>>) -> Self {
+        describe_counter!("pipeline_stage_calls_total", "Calls started per stage");
+        describe_counter!("pipeline_stage_errors_total", "Calls failed per stage");
+        describe_histogram!(
+            "pipeline_stage_duration_seconds",
+            Unit::Seconds,
+            "Duration of the calls per stage"
+        );
+        MetricsExporter {
+            pipeline: pipeline.into(),
+        }
+    }
+
+    /// Returns the labels of a stage of this pipeline
+    fn labels(&self, stage: &str) -> [(&'static str, String); 2] {
+        [
+            ("pipeline", self.pipeline.to_string()),
+            ("stage", stage.to_owned()),
+        ]
+    }
+}
+
+impl MetricsObserver for MetricsExporter {
+    fn on_stage_start(&self, stage: &str) {
+        counter!("pipeline_stage_calls_total", &self.labels(stage)).increment(1);
+    }
+
+    fn on_stage_end(&self, stage: &str, elapsed: Duration) {
+        histogram!("pipeline_stage_duration_seconds", &self.labels(stage)).record(elapsed);
+    }
+
+    fn on_error(&self, stage: &str) {
+        counter!("pipeline_stage_errors_total", &self.labels(stage)).increment(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{try_observed, Middleware};
+    use futures::executor::block_on;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use std::sync::Arc;
+
+    async fn checked(i: i32) -> Result<i32, String> {
+        if i < 0 {
+            Err(String::from("negative"))
+        } else {
+            Ok(i)
+        }
+    }
+
+    #[test]
+    fn test_metrics_exporter() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics_facade::with_local_recorder(&recorder, || {
+            let exporter = Arc::new(MetricsExporter::new("orders"));
+            let m = try_observed(checked, exporter).named("checked");
+            block_on(async {
+                assert_eq!(Ok(1), m.call(1).await);
+                assert!(m.call(-1).await.is_err());
+            });
+        });
+
+        let mut metrics: Vec<(String, Vec<String>, DebugValue)> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels = key.labels().map(|l| format!("{}={}", l.key(), l.value()));
+                (key.name().to_owned(), labels.collect(), value)
+            })
+            .collect();
+        metrics.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let labels = vec![
+            String::from("pipeline=orders"),
+            String::from("stage=checked"),
+        ];
+        assert_eq!(3, metrics.len());
+        assert_eq!("pipeline_stage_calls_total", metrics[0].0);
+        assert_eq!(labels, metrics[0].1);
+        assert_eq!(DebugValue::Counter(2), metrics[0].2);
+        assert_eq!("pipeline_stage_duration_seconds", metrics[1].0);
+        assert!(matches!(&metrics[1].2, DebugValue::Histogram(values) if values.len() == 2));
+        assert_eq!("pipeline_stage_errors_total", metrics[2].0);
+        assert_eq!(DebugValue::Counter(1), metrics[2].2);
+    }
+}
@@ -0,0 +1,664 @@
+//! Fallible middleware types.
+
+use alloc::{boxed::Box, sync::Arc, vec, vec::Vec};
+use core::{future::Future, marker::PhantomData};
+use futures::future::BoxFuture;
+
+use crate::{sequence, DynMiddleware, DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// Middleware that transforms around an input to a fallible output type.
+pub trait TryTransform<Args, T, O, E>: Send + Sync + 'static {
+    /// Asynchronously execute this handler, returning early with the error on failure
+    fn try_transform(&self, input: T) -> impl Future<Output = Result<O, E>> + Send;
+
+    /// Describes the stages making up this transform, in order
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::of::<Self, T, Result<O, E>>()]
+    }
+}
+
+/// Object-safe form of a fallible transform that boxes its future, used where stages are erased
+pub trait DynTryTransform<Args, T, O, E>: Send + Sync + 'static {
+    /// Execute this handler, boxing the returned future
+    fn try_transform_dyn(&self, input: T) -> BoxFuture<'_, Result<O, E>>;
+
+    /// Describes the stages making up this transform, in order
+    fn stages_dyn(&self) -> Vec<StageInfo>;
+}
+
+/// Every fallible transform can be erased by boxing its future
+impl<X, Args, T, O, E> DynTryTransform<Args, T, O, E> for X
+where
+    X: TryTransform<Args, T, O, E>,
+    Args: 'static,
+    T: 'static,
+    O: 'static,
+    E: 'static,
+{
+    fn try_transform_dyn(&self, input: T) -> BoxFuture<'_, Result<O, E>> {
+        Box::pin(self.try_transform(input))
+    }
+
+    fn stages_dyn(&self) -> Vec<StageInfo> {
+        self.stages()
+    }
+}
+
+/// Fallible transform implementation for any transform that produces a result
+impl<F, Args, T, O, E> TryTransform<Args, T, O, E> for F
+where
+    F: Transform<Args, T, Result<O, E>>,
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    fn try_transform(&self, input: T) -> impl Future<Output = Result<O, E>> + Send {
+        self.transform(input)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Middleware that performs a fallible operation.
+pub trait TryMiddleware<I, O, E>: Send + Sync + 'static {
+    fn try_call(&self, input: I) -> impl Future<Output = Result<O, E>> + Send;
+}
+
+/// Fallible middleware implementation for any middleware that produces a result
+impl<M, I, O, E> TryMiddleware<I, O, E> for M
+where
+    M: Middleware<I, Result<O, E>>,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    fn try_call(&self, input: I) -> impl Future<Output = Result<O, E>> + Send {
+        self.call(input)
+    }
+}
+
+/// Encapsulates the conversion between two fallible transforms, short-circuiting on error
+pub struct TryConvertMiddleware<T, T2, A, B, C, E, E2> {
+    t: Arc<dyn DynTryTransform<T, A, B, E>>,
+    t2: Arc<dyn DynTryTransform<T2, B, C, E2>>,
+}
+
+impl<T, T2, A, B, C, E, E2> TryConvertMiddleware<T, T2, A, B, C, E, E2>
+where
+    T: Send + Sync + 'static,
+    T2: Send + Sync + 'static,
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    C: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+    E2: From<E> + Send + Sync + 'static,
+{
+    /// Describes the stages making up the pipeline, in order
+    pub fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Implements the transform trait on the fallible conversion middleware (for downstream)
+impl<T, T2, A, B, C, E, E2> Transform<(A, Result<C, E2>), A, Result<C, E2>>
+    for TryConvertMiddleware<T, T2, A, B, C, E, E2>
+where
+    T: Send + Sync + 'static,
+    T2: Send + Sync + 'static,
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    C: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+    E2: From<E> + Send + Sync + 'static,
+{
+    async fn transform(&self, input: A) -> Result<C, E2> {
+        let input = self.t.try_transform_dyn(input).await?;
+        self.t2.try_transform_dyn(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        sequence(self.t.stages_dyn(), self.t2.stages_dyn())
+    }
+}
+
+/// Implements the middleware trait on the fallible conversion middleware to make it A -> C
+impl<T, T2, A, B, C, E, E2> Middleware<A, Result<C, E2>>
+    for TryConvertMiddleware<T, T2, A, B, C, E, E2>
+where
+    T: Send + Sync + 'static,
+    T2: Send + Sync + 'static,
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    C: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+    E2: From<E> + Send + Sync + 'static,
+{
+    async fn call(&self, input: A) -> Result<C, E2> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Creates a new fallible conversion middleware from two existing fallible transforms,
+/// converting the error of the first into the error of the second via `From`
+pub fn try_convert<T, T2, A, B, C, E, E2>(
+    t: impl TryTransform<T, A, B, E>,
+    t2: impl TryTransform<T2, B, C, E2>,
+) -> TryConvertMiddleware<T, T2, A, B, C, E, E2>
+where
+    T: Send + Sync + 'static,
+    T2: Send + Sync + 'static,
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    C: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+    E2: From<E> + Send + Sync + 'static,
+{
+    TryConvertMiddleware {
+        t: Arc::new(t),
+        t2: Arc::new(t2),
+    }
+}
+
+/// Pipes any number of fallible stages together by expanding into nested `try_convert` calls;
+/// with a leading `into E;` every stage error is converted into `E` via `From`, so each stage
+/// may fail with its own error type
+#[macro_export]
+macro_rules! try_pipe {
+    (into $err:ty; $($stage:expr),+ $(,)?) => {
+        $crate::try_pipe!($($crate::err_into::<$err, _, _, _, _, _>($stage)),+)
+    };
+    ($first:expr, $second:expr $(, $rest:expr)* $(,)?) => {
+        $crate::try_pipe!(@acc $crate::try_convert($first, $second) $(, $rest)*)
+    };
+    (@acc $acc:expr, $next:expr $(, $rest:expr)*) => {
+        $crate::try_pipe!(@acc $crate::try_convert($acc, $next) $(, $rest)*)
+    };
+    (@acc $acc:expr) => {
+        $acc
+    };
+}
+
+/// Converts the error of a fallible stage into another error type via `From`
+pub struct ErrInto<Args, I, O, E, E2> {
+    stage: Arc<dyn DynTransform<Args, I, Result<O, E>>>,
+    _phantom: PhantomData<fn() -> E2>,
+}
+
+/// Creates a new stage converting the error of the stage into `E2`, which is usually named
+/// explicitly, e.g. `err_into::<AppError, _, _, _, _, _>(parse)`
+pub fn err_into<E2, S, Args, I, O, E>(stage: S) -> ErrInto<Args, I, O, E, E2>
+where
+    S: Transform<Args, I, Result<O, E>>,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+    E2: From<E> + Send + Sync + 'static,
+{
+    ErrInto {
+        stage: Arc::new(stage),
+        _phantom: PhantomData,
+    }
+}
+
+/// Implements the transform trait on the error conversion (for downstream)
+impl<Args, I, O, E, E2> Transform<(I, Result<O, E2>), I, Result<O, E2>>
+    for ErrInto<Args, I, O, E, E2>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+    E2: From<E> + Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> Result<O, E2> {
+        Ok(self.stage.transform(input).await?)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the error conversion
+impl<Args, I, O, E, E2> Middleware<I, Result<O, E2>> for ErrInto<Args, I, O, E, E2>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+    E2: From<E> + Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> Result<O, E2> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Lifts an infallible stage into a fallible one that always succeeds
+pub struct Lift<Args, I, O, E> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    _phantom: PhantomData<fn() -> E>,
+}
+
+/// Creates a new never-failing stage from an infallible one so it mixes with fallible stages in
+/// `try_pipe!`, e.g. `lift::<AppError, _, _, _>(double)`
+pub fn lift<E, Args, I, O>(stage: impl Transform<Args, I, O>) -> Lift<Args, I, O, E>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    Lift {
+        stage: Arc::new(stage),
+        _phantom: PhantomData,
+    }
+}
+
+/// Implements the transform trait on the lifted stage (for downstream)
+impl<Args, I, O, E> Transform<(I, Result<O, E>), I, Result<O, E>> for Lift<Args, I, O, E>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> Result<O, E> {
+        Ok(self.stage.transform(input).await)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the lifted stage
+impl<Args, I, O, E> Middleware<I, Result<O, E>> for Lift<Args, I, O, E>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> Result<O, E> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Runs an infallible stage on the value of successful results, passing errors through
+pub struct OnOk<Args, I, O, E> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    _phantom: PhantomData<fn() -> E>,
+}
+
+/// Creates a new stage applying the infallible stage to successful results only, e.g. to
+/// append it to a fallible pipeline with `then`
+pub fn on_ok<E, Args, I, O>(stage: impl Transform<Args, I, O>) -> OnOk<Args, I, O, E>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    OnOk {
+        stage: Arc::new(stage),
+        _phantom: PhantomData,
+    }
+}
+
+/// Implements the transform trait on the stage for successful results (for downstream)
+impl<Args, I, O, E> Transform<(Result<I, E>, Result<O, E>), Result<I, E>, Result<O, E>>
+    for OnOk<Args, I, O, E>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    async fn transform(&self, input: Result<I, E>) -> Result<O, E> {
+        match input {
+            Ok(input) => Ok(self.stage.transform(input).await),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the stage for successful results
+impl<Args, I, O, E> Middleware<Result<I, E>, Result<O, E>> for OnOk<Args, I, O, E>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    async fn call(&self, input: Result<I, E>) -> Result<O, E> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Stage that maps the error of a result, passing successful values through
+pub struct MapErr<F> {
+    f: F,
+}
+
+/// Creates a new stage applying the function to the error of every failed result
+pub fn map_err<F>(f: F) -> MapErr<F> {
+    MapErr { f }
+}
+
+/// Implements the transform trait on the error mapping stage (for downstream)
+impl<F, O, E, E2> Transform<(Result<O, E>, Result<O, E2>), Result<O, E>, Result<O, E2>>
+    for MapErr<F>
+where
+    F: Fn(E) -> E2 + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+    E2: Send + Sync + 'static,
+{
+    async fn transform(&self, input: Result<O, E>) -> Result<O, E2> {
+        input.map_err(&self.f)
+    }
+}
+
+/// Implements the middleware trait on the error mapping stage
+impl<F, O, E, E2> Middleware<Result<O, E>, Result<O, E2>> for MapErr<F>
+where
+    F: Fn(E) -> E2 + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+    E2: Send + Sync + 'static,
+{
+    async fn call(&self, input: Result<O, E>) -> Result<O, E2> {
+        self.transform(input).await
+    }
+}
+
+/// Recovers from the error of a fallible middleware by running a fallback on the error
+pub struct OrElseMiddleware<FArgs, I, O, E, E2> {
+    middleware: Arc<dyn DynMiddleware<I, Result<O, E>>>,
+    fallback: Arc<dyn DynTransform<FArgs, E, Result<O, E2>>>,
+}
+
+impl<FArgs, I, O, E, E2> Middleware<I, Result<O, E2>> for OrElseMiddleware<FArgs, I, O, E, E2>
+where
+    FArgs: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+    E2: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> Result<O, E2> {
+        match self.middleware.call(input).await {
+            Ok(output) => Ok(output),
+            Err(e) => self.fallback.transform(e).await,
+        }
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::branching::<I, Result<O, E2>>(
+            "or_else",
+            vec![
+                ("ok".into(), (*self.middleware).stages_dyn()),
+                ("err".into(), self.fallback.stages_dyn()),
+            ],
+        )]
+    }
+}
+
+impl<T, Args, I, O, E> Pied<T, Args, I, Result<O, E>>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    /// Runs the given fallback only when the pipeline fails, similar to `Result::or_else`
+    pub fn or_else<FArgs, E2>(
+        self,
+        fallback: impl Transform<FArgs, E, Result<O, E2>>,
+    ) -> Pied<(T, FArgs), Args, I, Result<O, E2>>
+    where
+        FArgs: Send + Sync + 'static,
+        E2: Send + Sync + 'static,
+    {
+        self.rebuild(|pied| OrElseMiddleware {
+            middleware: pied.middleware,
+            fallback: Arc::new(fallback),
+        })
+    }
+
+    /// Appends an infallible stage run on successful outputs only, passing errors through, so
+    /// infallible stages mix into fallible pipelines without `Ok` wrappers
+    pub fn map_ok<ArgsN, O2>(
+        self,
+        next: impl Transform<ArgsN, O, O2>,
+    ) -> Pied<T, Args, I, Result<O2, E>>
+    where
+        ArgsN: Send + Sync + 'static,
+        O2: Send + Sync + 'static,
+    {
+        self.then(on_ok::<E, _, _, _>(next))
+    }
+
+    /// Maps the error of the pipeline with the function, similar to `Result::map_err`
+    pub fn map_err<E2>(
+        self,
+        f: impl Fn(E) -> E2 + Send + Sync + 'static,
+    ) -> Pied<T, Args, I, Result<O, E2>>
+    where
+        E2: Send + Sync + 'static,
+    {
+        self.then(map_err(f))
+    }
+
+    /// Converts the error of the pipeline into `E2` via `From`
+    pub fn err_into<E2>(self) -> Pied<T, Args, I, Result<O, E2>>
+    where
+        E2: From<E> + Send + Sync + 'static,
+    {
+        self.map_err(E2::from)
+    }
+
+    /// Observes the error of every failed call without changing it, e.g. for logging or alerting
+    pub fn inspect_err(
+        self,
+        f: impl Fn(&E) + Send + Sync + 'static,
+    ) -> Pied<T, Args, I, Result<O, E>> {
+        self.inspect(move |output: &Result<O, E>| {
+            if let Err(e) = output {
+                f(e);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pipe_fn, Piper};
+
+    #[derive(Debug, PartialEq)]
+    struct ParseError(String);
+
+    #[derive(Debug, PartialEq)]
+    struct RangeError(i32);
+
+    #[derive(Debug, PartialEq)]
+    enum AppError {
+        Parse(ParseError),
+        Range(RangeError),
+    }
+
+    impl From<ParseError> for AppError {
+        fn from(e: ParseError) -> Self {
+            AppError::Parse(e)
+        }
+    }
+
+    impl From<RangeError> for AppError {
+        fn from(e: RangeError) -> Self {
+            AppError::Range(e)
+        }
+    }
+
+    async fn positive(i: i32) -> Result<i32, RangeError> {
+        if i > 0 {
+            Ok(i)
+        } else {
+            Err(RangeError(i))
+        }
+    }
+
+    async fn parse(s: String) -> Result<i32, ParseError> {
+        s.parse().map_err(|_| ParseError(s))
+    }
+
+    async fn halve(i: i32) -> Result<i32, AppError> {
+        Ok(i / 2)
+    }
+
+    async fn stringer(i: i32) -> Result<String, AppError> {
+        Ok(i.to_string())
+    }
+
+    #[async_std::test]
+    async fn test_try_convert() {
+        let m = try_convert(parse, halve);
+        assert_eq!(Ok(21), m.call(String::from("42")).await);
+        assert_eq!(
+            Err(AppError::Parse(ParseError(String::from("nope")))),
+            m.try_call(String::from("nope")).await
+        );
+    }
+
+    #[async_std::test]
+    async fn test_try_pipe_short_circuits() {
+        let m = try_pipe!(parse, halve, halve, stringer);
+        assert_eq!(Ok(String::from("10")), m.call(String::from("40")).await);
+        assert_eq!(
+            Err(AppError::Parse(ParseError(String::from("x")))),
+            m.call(String::from("x")).await
+        );
+    }
+
+    #[test]
+    fn test_try_pipe_stages() {
+        let m = try_pipe!(parse, halve, stringer);
+        let stages = m.stages();
+        assert_eq!(3, stages.len());
+        assert!(stages[1].name.ends_with("halve"));
+        assert_eq!(2, stages[2].position);
+
+        let m = pipe_fn(try_pipe!(parse, halve, stringer)).or_else(fallback);
+        assert_eq!(3, m.stages()[0].branches[0].1.len());
+    }
+
+    #[async_std::test]
+    async fn test_try_pipe_into_error() {
+        let m = try_pipe!(into AppError; parse, positive, halve, positive);
+        assert_eq!(Ok(2), m.call(String::from("4")).await);
+        assert_eq!(
+            Err(AppError::Range(RangeError(0))),
+            m.call(String::from("1")).await
+        );
+        assert_eq!(
+            Err(AppError::Parse(ParseError(String::from("x")))),
+            m.call(String::from("x")).await
+        );
+    }
+
+    #[async_std::test]
+    async fn test_error_combinators() {
+        let failures = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let logged = failures.clone();
+        let m = (|s: String| async move { s }, parse)
+            .pipe()
+            .inspect_err(move |e: &ParseError| logged.lock().unwrap().push(e.0.clone()))
+            .err_into::<AppError>();
+        assert_eq!(Ok(3), m.call(String::from("3")).await);
+        assert_eq!(
+            Err(AppError::Parse(ParseError(String::from("x")))),
+            m.call(String::from("x")).await
+        );
+        assert_eq!(vec![String::from("x")], *failures.lock().unwrap());
+
+        let m = (positive, |i: Result<i32, RangeError>| async move { i })
+            .pipe()
+            .map_err(|RangeError(i)| format!("{} is out of range", i));
+        assert_eq!(Err(String::from("-2 is out of range")), m.call(-2).await);
+    }
+
+    async fn double(i: i32) -> i32 {
+        i * 2
+    }
+
+    #[async_std::test]
+    async fn test_lift_infallible() {
+        let m = try_pipe!(parse, lift::<AppError, _, _, _>(double), halve, stringer);
+        assert_eq!(Ok(String::from("5")), m.call(String::from("5")).await);
+        assert!(m.call(String::from("x")).await.is_err());
+
+        let m = (|s: String| async move { s }, parse)
+            .pipe()
+            .map_ok(double)
+            .map_ok(|i: i32| async move { i.to_string() });
+        assert_eq!(Ok(String::from("8")), m.call(String::from("4")).await);
+        assert_eq!(
+            Err(ParseError(String::from("x"))),
+            m.call(String::from("x")).await
+        );
+
+        // appended to the flat list of stages, so a long chain doesn't grow the stack
+        let mut m = pipe_fn(parse).map_ok(double);
+        for _ in 0..20_000 {
+            m = m.map_ok(|i: i32| async move { i + 1 });
+        }
+        assert_eq!(Ok(20_008), m.call(String::from("4")).await);
+        assert_eq!(20_002, m.stages().len());
+    }
+
+    async fn fallback(e: AppError) -> Result<String, String> {
+        match e {
+            AppError::Parse(ParseError(s)) if s.is_empty() => Ok(String::from("0")),
+            AppError::Parse(ParseError(s)) => Err(s),
+            AppError::Range(RangeError(i)) => Err(i.to_string()),
+        }
+    }
+
+    #[async_std::test]
+    async fn test_or_else() {
+        let m = pipe_fn(try_pipe!(parse, halve, stringer)).or_else(fallback);
+        assert_eq!(Ok(String::from("2")), m.call(String::from("4")).await);
+        assert_eq!(Ok(String::from("0")), m.call(String::new()).await);
+        assert_eq!(Err(String::from("x")), m.call(String::from("x")).await);
+
+        let m = (|| async { String::from("?") }, parse)
+            .pipe()
+            .or_else(|_| async { Ok::<_, ()>(-1) });
+        assert_eq!(Ok(-1), m.call(()).await);
+    }
+}
@@ -0,0 +1,132 @@
+//! Feedback edges in spawned pipelines.
+
+use futures::{
+    channel::mpsc::{Receiver, Sender},
+    future::BoxFuture,
+    SinkExt, StreamExt,
+};
+use std::{collections::VecDeque, error::Error, fmt, sync::Arc};
+
+use crate::{DynTransform, Spawned, Transform};
+
+/// Output of a stage with a feedback edge, either passed downstream or fed back into the stage
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Feedback<T, O> {
+    /// Passes the output on to the next stage
+    Emit(O),
+    /// Pushes the item back onto the queue of the stage
+    Requeue(T),
+}
+
+/// Error emitted for an item that was requeued more often than the depth limit allows
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DepthExceeded<T> {
+    pub item: T,
+    pub depth: usize,
+}
+
+impl<T> fmt::Display for DepthExceeded<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "item requeued more than {} times", self.depth)
+    }
+}
+
+impl<T: fmt::Debug> Error for DepthExceeded<T> {}
+
+/// Creates the task loop that runs the stage, serving requeued items before new input
+fn feedback_task<Args, T, O>(
+    stage: Arc<dyn DynTransform<Args, T, Feedback<T, O>>>,
+    max_depth: usize,
+    mut rx: Receiver<T>,
+    mut tx: Sender<Result<O, DepthExceeded<T>>>,
+) -> BoxFuture<'static, ()>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    Box::pin(async move {
+        let mut requeued = VecDeque::new();
+        loop {
+            let (item, depth) = match requeued.pop_front() {
+                Some(requeued) => requeued,
+                None => match rx.next().await {
+                    Some(item) => (item, 0),
+                    None => return,
+                },
+            };
+            let output = match stage.transform(item).await {
+                Feedback::Emit(output) => Ok(output),
+                Feedback::Requeue(item) if depth < max_depth => {
+                    requeued.push_back((item, depth + 1));
+                    continue;
+                }
+                Feedback::Requeue(item) => Err(DepthExceeded {
+                    item,
+                    depth: max_depth,
+                }),
+            };
+            if tx.send(output).await.is_err() {
+                return;
+            }
+        }
+    })
+}
+
+impl<I, O> Spawned<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Appends a stage that can push items back onto its own queue, e.g. to retry transient
+    /// failures or to generate follow-up work; items requeued more than `max_depth` times are
+    /// emitted as `DepthExceeded`
+    pub fn feedback<Args, O2>(
+        self,
+        stage: impl Transform<Args, O, Feedback<O, O2>>,
+        max_depth: usize,
+    ) -> Spawned<I, Result<O2, DepthExceeded<O>>>
+    where
+        Args: Send + Sync + 'static,
+        O2: Send + Sync + 'static,
+    {
+        let stage: Arc<dyn DynTransform<Args, O, Feedback<O, O2>>> = Arc::new(stage);
+        self.task(move |rx, tx, _| feedback_task(stage, max_depth, rx, tx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawned;
+    use async_std::task;
+
+    async fn parse(s: &'static str) -> (u32, &'static str) {
+        (0, s)
+    }
+
+    async fn attempt((tries, s): (u32, &'static str)) -> Feedback<(u32, &'static str), u32> {
+        match s.parse() {
+            Ok(n) if tries >= n => Feedback::Emit(n),
+            _ => Feedback::Requeue((tries + 1, s)),
+        }
+    }
+
+    fn spawner(future: BoxFuture<'static, ()>) {
+        task::spawn(future);
+    }
+
+    #[async_std::test]
+    async fn test_feedback_requeue() {
+        let mut handle = spawned(parse).feedback(attempt, 3).spawn(4, spawner);
+        handle.send("2").await.unwrap();
+        assert_eq!(Some(Ok(2)), handle.recv().await);
+
+        handle.send("0").await.unwrap();
+        handle.send("x").await.unwrap();
+        assert_eq!(Some(Ok(0)), handle.recv().await);
+        let err = handle.recv().await.unwrap().unwrap_err();
+        assert_eq!((4, "x"), err.item);
+        assert_eq!("item requeued more than 3 times", err.to_string());
+    }
+}
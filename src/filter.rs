@@ -0,0 +1,147 @@
+//! Dropping items mid-pipeline.
+
+use alloc::{boxed::Box, sync::Arc, vec, vec::Vec};
+
+use crate::{DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// Keeps the input when the predicate holds, dropping it as `None` otherwise
+pub struct Filter<T> {
+    pred: Box<dyn Fn(&T) -> bool + Send + Sync>,
+}
+
+/// Creates a new filter stage keeping the inputs the predicate holds for
+pub fn filter<T>(pred: impl Fn(&T) -> bool + Send + Sync + 'static) -> Filter<T>
+where
+    T: Send + Sync + 'static,
+{
+    Filter {
+        pred: Box::new(pred),
+    }
+}
+
+/// Implements the transform trait on the filter (for downstream)
+impl<T> Transform<(T, Option<T>), T, Option<T>> for Filter<T>
+where
+    T: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> Option<T> {
+        (self.pred)(&input).then_some(input)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::branching::<T, Option<T>>("filter", Vec::new())]
+    }
+}
+
+/// Implements the middleware trait on the filter
+impl<T> Middleware<T, Option<T>> for Filter<T>
+where
+    T: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> Option<T> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Runs the stage on kept items only, skipping it for items dropped upstream
+pub struct OnSome<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+}
+
+/// Creates a new stage running on the value of `Some` inputs and passing `None` through
+pub fn on_some<Args, I, O>(stage: impl Transform<Args, I, O>) -> OnSome<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    OnSome {
+        stage: Arc::new(stage),
+    }
+}
+
+/// Implements the transform trait on the skipping stage (for downstream)
+impl<Args, I, O> Transform<(Option<I>, Option<O>), Option<I>, Option<O>> for OnSome<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: Option<I>) -> Option<O> {
+        match input {
+            Some(input) => Some(self.stage.transform(input).await),
+            None => None,
+        }
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the skipping stage
+impl<Args, I, O> Middleware<Option<I>, Option<O>> for OnSome<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: Option<I>) -> Option<O> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Drops the output of the pipeline as `None` unless the predicate holds for it
+    pub fn filter(
+        self,
+        pred: impl Fn(&O) -> bool + Send + Sync + 'static,
+    ) -> Pied<T, Args, I, Option<O>> {
+        self.then(filter(pred))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn double(i: i32) -> i32 {
+        i * 2
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_filter() {
+        let m = (
+            double,
+            filter(|i: &i32| i % 3 == 0),
+            on_some(double),
+            on_some(stringer),
+        )
+            .pipe();
+        assert_eq!(Some(String::from("12")), m.call(3).await);
+        assert_eq!(None, m.call(4).await);
+
+        let m = (double, stringer).pipe().filter(|s: &String| s.len() > 1);
+        assert_eq!(Some(String::from("12")), m.call(6).await);
+        assert_eq!(None, m.call(4).await);
+    }
+}
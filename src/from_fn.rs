@@ -0,0 +1,78 @@
+//! Synchronous functions as stages.
+
+use core::future::{ready, Future};
+
+use crate::{Middleware, Transform};
+
+/// Stage that calls a plain synchronous function, resolving immediately with its result
+pub struct FromFn<F> {
+    f: F,
+}
+
+/// Creates a new stage from a synchronous `fn(T) -> O` (or `fn() -> O` for a source)
+pub fn from_fn<F>(f: F) -> FromFn<F> {
+    FromFn { f }
+}
+
+/// Implements the transform trait on a synchronous source (for downstream)
+impl<F, O> Transform<(), (), O> for FromFn<F>
+where
+    F: Fn() -> O + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    fn transform(&self, _input: ()) -> impl Future<Output = O> + Send {
+        ready((self.f)())
+    }
+}
+
+/// Implements the transform trait on a synchronous function (for downstream)
+impl<F, T, O> Transform<(T, O), T, O> for FromFn<F>
+where
+    F: Fn(T) -> O + Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    fn transform(&self, input: T) -> impl Future<Output = O> + Send {
+        ready((self.f)(input))
+    }
+}
+
+/// Implements the middleware trait on a synchronous function
+impl<F, T, O> Middleware<T, O> for FromFn<F>
+where
+    F: Fn(T) -> O + Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    fn call(&self, input: T) -> impl Future<Output = O> + Send {
+        ready((self.f)(input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    fn producer() -> i32 {
+        3
+    }
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_from_fn() {
+        let m = (from_fn(producer), multipler, from_fn(stringer)).pipe();
+        assert_eq!(String::from("96"), m.call(()).await);
+
+        let m = (multipler, from_fn(|i: i32| i + 1)).pipe();
+        assert_eq!(33, m.call(1).await);
+        assert_eq!(String::from("7"), from_fn(stringer).call(7).await);
+    }
+}
@@ -0,0 +1,295 @@
+//! Tonic interceptor and client interop.
+
+use futures::{future::BoxFuture, FutureExt};
+use std::{
+    future::Future,
+    marker::PhantomData,
+    task::{Context, Poll},
+};
+use tonic::{metadata::MetadataMap, service::Interceptor, Request, Response, Status};
+use tower::{Layer, Service};
+
+use crate::{Middleware, Pied, Transform};
+
+/// Runs a pipeline over the request metadata as a tonic interceptor
+///
+/// Tonic interceptors are synchronous and run on an executor thread, so the pipeline is polled
+/// once and must complete without waiting (auth headers, tracing ids); a pipeline that waits on
+/// a timer or I/O fails the request with `Status::internal` instead of blocking the executor.
+/// Use `into_layer` for pipelines that wait.
+pub struct PiedInterceptor<T, Args> {
+    pied: Pied<T, Args, Request<()>, Result<Request<()>, Status>>,
+}
+
+impl<T, Args> Clone for PiedInterceptor<T, Args> {
+    fn clone(&self) -> Self {
+        PiedInterceptor {
+            pied: self.pied.clone(),
+        }
+    }
+}
+
+/// Implements the tonic interceptor trait, rejecting the request with the status of an error
+impl<T, Args> Interceptor for PiedInterceptor<T, Args>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+{
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        self.pied.call(request).now_or_never().unwrap_or_else(|| {
+            Err(Status::internal(
+                "interceptor pipeline waited, use a layer instead",
+            ))
+        })
+    }
+}
+
+/// Tower layer running a pipeline over the metadata of every gRPC request before the service,
+/// awaiting it so stages may wait on timers or I/O (e.g. a token lookup with a timeout)
+pub struct GrpcLayer<T, Args> {
+    pied: Pied<T, Args, Request<()>, Result<Request<()>, Status>>,
+}
+
+impl<T, Args> Clone for GrpcLayer<T, Args> {
+    fn clone(&self) -> Self {
+        GrpcLayer {
+            pied: self.pied.clone(),
+        }
+    }
+}
+
+impl<S, T, Args> Layer<S> for GrpcLayer<T, Args> {
+    type Service = GrpcService<S, T, Args>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcService {
+            inner,
+            pied: self.pied.clone(),
+        }
+    }
+}
+
+/// Service running the pipeline of a `GrpcLayer` before the inner service
+pub struct GrpcService<S, T, Args> {
+    inner: S,
+    pied: Pied<T, Args, Request<()>, Result<Request<()>, Status>>,
+}
+
+impl<S: Clone, T, Args> Clone for GrpcService<S, T, Args> {
+    fn clone(&self) -> Self {
+        GrpcService {
+            inner: self.inner.clone(),
+            pied: self.pied.clone(),
+        }
+    }
+}
+
+/// Implements the tower service trait, answering with the status of an error without calling
+/// the inner service
+impl<S, T, Args, ReqBody, ResBody> Service<http::Request<ReqBody>> for GrpcService<S, T, Args>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    ReqBody: Send + 'static,
+    ResBody: Default,
+{
+    type Response = http::Response<ResBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        // the clone isn't ready yet, so keep it and call the inner service that was polled
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let pied = self.pied.clone();
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let metadata = MetadataMap::from_headers(parts.headers);
+            let request = Request::from_parts(metadata, parts.extensions, ());
+            match pied.call(request).await {
+                Ok(request) => {
+                    let (metadata, extensions, ()) = request.into_parts();
+                    let mut request = http::Request::new(body);
+                    *request.method_mut() = parts.method;
+                    *request.uri_mut() = parts.uri;
+                    *request.version_mut() = parts.version;
+                    *request.headers_mut() = metadata.into_headers();
+                    *request.extensions_mut() = extensions;
+                    inner.call(request).await
+                }
+                Err(status) => Ok(status.into_http()),
+            }
+        })
+    }
+}
+
+impl<T, Args> Pied<T, Args, Request<()>, Result<Request<()>, Status>>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+{
+    /// Converts the pipeline into a tonic interceptor, e.g. for `with_interceptor`, for pipelines
+    /// that never wait
+    pub fn into_interceptor(self) -> PiedInterceptor<T, Args> {
+        PiedInterceptor { pied: self }
+    }
+
+    /// Converts the pipeline into a tower layer, e.g. for `Server::layer`, awaiting the pipeline
+    /// before every call of the service
+    pub fn into_layer(self) -> GrpcLayer<T, Args> {
+        GrpcLayer { pied: self }
+    }
+}
+
+/// Wraps a call on a generated gRPC client as a transform from request to response message
+pub struct GrpcCall<C, F, Req, Resp> {
+    client: C,
+    f: F,
+    _phantom: PhantomData<fn(Req) -> Resp>,
+}
+
+/// Creates a new transform calling the client method for every input, cloning the client per call
+pub fn grpc_call<C, F, Fut, Req, Resp>(client: C, f: F) -> GrpcCall<C, F, Req, Resp>
+where
+    F: Fn(C, Request<Req>) -> Fut,
+    Fut: Future<Output = Result<Response<Resp>, Status>>,
+{
+    GrpcCall {
+        client,
+        f,
+        _phantom: PhantomData,
+    }
+}
+
+/// Implements the transform trait on the client call (for downstream)
+impl<C, F, Fut, Req, Resp> Transform<(Req, Result<Resp, Status>), Req, Result<Resp, Status>>
+    for GrpcCall<C, F, Req, Resp>
+where
+    C: Clone + Send + Sync + 'static,
+    F: Fn(C, Request<Req>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Response<Resp>, Status>> + Send + 'static,
+    Req: Send + Sync + 'static,
+    Resp: Send + Sync + 'static,
+{
+    async fn transform(&self, input: Req) -> Result<Resp, Status> {
+        let response = (self.f)(self.client.clone(), Request::new(input)).await?;
+        Ok(response.into_inner())
+    }
+}
+
+/// Implements the middleware trait on the client call
+impl<C, F, Fut, Req, Resp> Middleware<Req, Result<Resp, Status>> for GrpcCall<C, F, Req, Resp>
+where
+    C: Clone + Send + Sync + 'static,
+    F: Fn(C, Request<Req>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Response<Resp>, Status>> + Send + 'static,
+    Req: Send + Sync + 'static,
+    Resp: Send + Sync + 'static,
+{
+    async fn call(&self, input: Req) -> Result<Resp, Status> {
+        self.transform(input).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{try_convert, Piper};
+    use std::convert::Infallible;
+    use tonic::Code;
+    use tower::{service_fn, ServiceExt};
+
+    #[derive(Clone)]
+    struct EchoClient;
+
+    impl EchoClient {
+        async fn echo(self, req: Request<String>) -> Result<Response<String>, Status> {
+            match req.into_inner() {
+                s if s.is_empty() => Err(Status::invalid_argument("empty message")),
+                s => Ok(Response::new(s.to_uppercase())),
+            }
+        }
+    }
+
+    async fn authorize(req: Request<()>) -> Result<Request<()>, Status> {
+        match req.metadata().get("authorization") {
+            Some(_) => Ok(req),
+            None => Err(Status::unauthenticated("missing token")),
+        }
+    }
+
+    async fn tag(mut req: Result<Request<()>, Status>) -> Result<Request<()>, Status> {
+        if let Ok(req) = &mut req {
+            req.metadata_mut()
+                .insert("x-request-id", "1".parse().unwrap());
+        }
+        req
+    }
+
+    async fn trim(s: String) -> Result<String, Status> {
+        Ok(s.trim().to_string())
+    }
+
+    #[test]
+    fn test_pied_interceptor() {
+        let mut interceptor = (authorize, tag).pipe().into_interceptor();
+        let mut req = Request::new(());
+        req.metadata_mut()
+            .insert("authorization", "Bearer token".parse().unwrap());
+        let req = interceptor.call(req).unwrap();
+        assert_eq!("1", req.metadata().get("x-request-id").unwrap());
+
+        let err = interceptor.call(Request::new(())).unwrap_err();
+        assert_eq!(Code::Unauthenticated, err.code());
+
+        let slow = |req: Request<()>| async move {
+            async_std::task::sleep(std::time::Duration::from_millis(1)).await;
+            Ok(req)
+        };
+        let mut interceptor = (slow, tag).pipe().into_interceptor();
+        let err = interceptor.call(Request::new(())).unwrap_err();
+        assert_eq!(Code::Internal, err.code());
+    }
+
+    #[async_std::test]
+    async fn test_pied_layer() {
+        let slow_authorize = |req: Request<()>| async move {
+            async_std::task::sleep(std::time::Duration::from_millis(1)).await;
+            authorize(req).await
+        };
+        let echo = service_fn(|req: http::Request<String>| async move {
+            let id = req.headers().get("x-request-id").cloned();
+            let body = format!("{} {:?}", req.into_body(), id);
+            Ok::<_, Infallible>(http::Response::new(body))
+        });
+        let service = (slow_authorize, tag).pipe().into_layer().layer(echo);
+
+        let request = http::Request::builder()
+            .uri("/echo.Echo/Echo")
+            .header("authorization", "Bearer token")
+            .body(String::from("hi"))
+            .unwrap();
+        let response = service.clone().oneshot(request).await.unwrap();
+        assert_eq!("hi Some(\"1\")", response.into_body());
+
+        let response = service.oneshot(http::Request::new(String::new())).await;
+        let status = response.unwrap().headers()["grpc-status"].clone();
+        assert_eq!(format!("{}", Code::Unauthenticated as i32), status);
+    }
+
+    #[async_std::test]
+    async fn test_grpc_call() {
+        let m = try_convert(trim, grpc_call(EchoClient, EchoClient::echo));
+        assert_eq!("HELLO", m.call(String::from(" hello ")).await.unwrap());
+        assert_eq!(
+            Code::InvalidArgument,
+            m.call(String::new()).await.unwrap_err().code()
+        );
+    }
+}
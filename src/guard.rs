@@ -0,0 +1,138 @@
+//! Stages that only run when a predicate on their input holds.
+
+use alloc::{boxed::Box, sync::Arc, vec, vec::Vec};
+
+use crate::{DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// Runs the stage only when the predicate holds, otherwise mapping the input with a fallback
+pub struct Guard<Args, I, O> {
+    pred: Box<dyn Fn(&I) -> bool + Send + Sync>,
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    fallback: Box<dyn Fn(I) -> O + Send + Sync>,
+}
+
+/// Creates a new guarded stage, passing the input straight through when the predicate fails
+pub fn guard<Args, T>(
+    pred: impl Fn(&T) -> bool + Send + Sync + 'static,
+    stage: impl Transform<Args, T, T>,
+) -> Guard<Args, T, T>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+{
+    guard_or(pred, stage, |input| input)
+}
+
+/// Creates a new guarded stage, passing the input straight through when the predicate holds
+pub fn skip_if<Args, T>(
+    pred: impl Fn(&T) -> bool + Send + Sync + 'static,
+    stage: impl Transform<Args, T, T>,
+) -> Guard<Args, T, T>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+{
+    guard(move |input| !pred(input), stage)
+}
+
+/// Creates a new guarded stage, mapping the input with `fallback` when the predicate fails
+pub fn guard_or<Args, I, O>(
+    pred: impl Fn(&I) -> bool + Send + Sync + 'static,
+    stage: impl Transform<Args, I, O>,
+    fallback: impl Fn(I) -> O + Send + Sync + 'static,
+) -> Guard<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    Guard {
+        pred: Box::new(pred),
+        stage: Arc::new(stage),
+        fallback: Box::new(fallback),
+    }
+}
+
+/// Implements the transform trait on the guarded stage (for downstream)
+impl<Args, I, O> Transform<(I, O), I, O> for Guard<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        if (self.pred)(&input) {
+            self.stage.transform(input).await
+        } else {
+            (self.fallback)(input)
+        }
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::branching::<I, O>(
+            "guard",
+            vec![("then".into(), self.stage.stages())],
+        )]
+    }
+}
+
+/// Implements the middleware trait on the guarded stage
+impl<Args, I, O> Middleware<I, O> for Guard<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I> Pied<T, Args, I, I>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+{
+    /// Runs the pipeline only when the predicate holds, otherwise passing the input through
+    pub fn guard(self, pred: impl Fn(&I) -> bool + Send + Sync + 'static) -> Pied<T, Args, I, I> {
+        self.rebuild(|pied| guard(pred, pied))
+    }
+
+    /// Passes the input straight through when the predicate holds, otherwise running the pipeline
+    pub fn skip_if(self, pred: impl Fn(&I) -> bool + Send + Sync + 'static) -> Pied<T, Args, I, I> {
+        self.rebuild(|pied| skip_if(pred, pied))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn double(i: i32) -> i32 {
+        i * 2
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_guard() {
+        let m = (guard(|i: &i32| *i > 0, double), stringer).pipe();
+        assert_eq!(String::from("6"), m.call(3).await);
+        assert_eq!(String::from("-3"), m.call(-3).await);
+
+        let m = guard_or(|i: &i32| *i > 0, stringer, |_| String::from("skipped"));
+        assert_eq!(String::from("skipped"), m.call(-3).await);
+
+        let m = (double, double).pipe().skip_if(|i: &i32| *i > 10);
+        assert_eq!(12, m.call(3).await);
+        assert_eq!(11, m.call(11).await);
+    }
+}
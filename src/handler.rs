@@ -0,0 +1,122 @@
+//! Axum handler interop.
+
+use axum::{
+    body::{to_bytes, Bytes},
+    extract::Request,
+    handler::Handler,
+    http::{self, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::future::BoxFuture;
+
+use crate::{Middleware, Pied};
+
+/// Default limit on the size of a buffered request body, matching the axum default
+const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Mounts a pipeline over buffered requests as an axum handler
+///
+/// Axum request bodies are not `Sync`, so the body is buffered into `Bytes` before the
+/// pipeline is called; bodies over the limit are rejected with `413 Payload Too Large`.
+pub struct PiedHandler<T, Args, O> {
+    pied: Pied<T, Args, http::Request<Bytes>, O>,
+    body_limit: usize,
+}
+
+impl<T, Args, O> PiedHandler<T, Args, O> {
+    /// Sets the maximum size of a request body buffered for the pipeline
+    pub fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = limit;
+        self
+    }
+}
+
+impl<T, Args, O> Clone for PiedHandler<T, Args, O> {
+    fn clone(&self) -> Self {
+        PiedHandler {
+            pied: self.pied.clone(),
+            body_limit: self.body_limit,
+        }
+    }
+}
+
+/// Implements the axum handler trait, buffering the body and converting the output to a response
+impl<T, Args, O, S> Handler<(T, Args), S> for PiedHandler<T, Args, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    O: IntoResponse + Send + Sync + 'static,
+{
+    type Future = BoxFuture<'static, Response>;
+
+    fn call(self, req: Request, _state: S) -> Self::Future {
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            match to_bytes(body, self.body_limit).await {
+                Ok(body) => {
+                    let req = http::Request::from_parts(parts, body);
+                    self.pied.call(req).await.into_response()
+                }
+                Err(e) => (StatusCode::PAYLOAD_TOO_LARGE, e.to_string()).into_response(),
+            }
+        })
+    }
+}
+
+impl<T, Args, O> Pied<T, Args, http::Request<Bytes>, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    O: IntoResponse + Send + Sync + 'static,
+{
+    /// Converts the pipeline into an axum handler, e.g. for `axum::routing::post`
+    pub fn into_handler(self) -> PiedHandler<T, Args, O> {
+        PiedHandler {
+            pied: self,
+            body_limit: DEFAULT_BODY_LIMIT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use axum::{body::Body, routing::post, Router};
+    use tower::ServiceExt;
+
+    async fn parse(req: http::Request<Bytes>) -> Result<i32, String> {
+        let body = String::from_utf8_lossy(req.body()).into_owned();
+        body.parse().map_err(|_| format!("invalid number {}", body))
+    }
+
+    async fn respond(i: Result<i32, String>) -> Result<String, (StatusCode, String)> {
+        i.map(|i| (i * 2).to_string())
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))
+    }
+
+    async fn send(router: &Router, body: &'static str) -> (StatusCode, String) {
+        let req = http::Request::post("/double")
+            .body(Body::from(body))
+            .unwrap();
+        let res = router.clone().oneshot(req).await.unwrap();
+        let status = res.status();
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[async_std::test]
+    async fn test_pied_handler() {
+        let handler = (parse, respond).pipe().into_handler().body_limit(4);
+        let router = Router::new().route("/double", post(handler));
+        assert_eq!(
+            (StatusCode::OK, String::from("42")),
+            send(&router, "21").await
+        );
+        assert_eq!(StatusCode::BAD_REQUEST, send(&router, "x").await.0);
+        assert_eq!(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            send(&router, "123456").await.0
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! Hedged execution of slow calls.
+
+use futures::future::{select, Either};
+use std::{pin::pin, sync::Arc, time::Duration};
+
+use crate::{rt, DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// Starts a second call of the stage when the first is still running after the delay, keeping
+/// whichever finishes first
+pub struct Hedge<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    delay: Duration,
+}
+
+/// Creates a new hedged stage issuing a backup call on a clone of the input after `delay`
+pub fn hedge<Args, I, O>(stage: impl Transform<Args, I, O>, delay: Duration) -> Hedge<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    Hedge {
+        stage: Arc::new(stage),
+        delay,
+    }
+}
+
+/// Implements the transform trait on the hedged stage (for downstream)
+impl<Args, I, O> Transform<(I, O), I, O> for Hedge<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let mut first = pin!(self.stage.transform(input.clone()));
+        if let Ok(output) = rt::timeout(self.delay, first.as_mut()).await {
+            return output;
+        }
+        let second = self.stage.transform(input);
+        match select(first, second).await {
+            Either::Left((output, _)) | Either::Right((output, _)) => output,
+        }
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the hedged stage
+impl<Args, I, O> Middleware<I, O> for Hedge<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Issues a backup call of the whole pipeline when a call is still running after the delay
+    pub fn hedge(self, delay: Duration) -> Pied<T, Args, I, O> {
+        self.rebuild(|pied| hedge(pied, delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use async_std::task::sleep;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[async_std::test]
+    async fn test_hedge() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        // the first call stalls, the backup call answers quickly
+        let flaky = move |i: i32| {
+            let call = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if call.is_multiple_of(2) {
+                    sleep(Duration::from_millis(200)).await;
+                }
+                format!("{} from call {}", i, call)
+            }
+        };
+        let m = hedge(flaky, Duration::from_millis(10));
+        assert_eq!(String::from("1 from call 1"), m.call(1).await);
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+
+        let m = (|i: i32| async move { i * 2 }, |i: i32| async move { i + 1 })
+            .pipe()
+            .hedge(Duration::from_millis(50));
+        assert_eq!(7, m.call(3).await);
+    }
+}
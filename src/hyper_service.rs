@@ -0,0 +1,118 @@
+//! Hyper service interop.
+
+use futures::future::BoxFuture;
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    body::{Body, Bytes},
+    service::Service,
+    Request, Response, StatusCode,
+};
+use std::{convert::Infallible, fmt::Display};
+
+use crate::{Middleware, Pied};
+
+/// Serves a pipeline over buffered requests behind a hyper server
+///
+/// The request body is collected into `Bytes` before the pipeline is called. A body that fails
+/// to be read is answered with `400 Bad Request` and an error from the pipeline with
+/// `500 Internal Server Error`, so the service itself never fails.
+pub struct PiedService<T, Args, E> {
+    pied: Pied<T, Args, Request<Bytes>, Result<Response<Bytes>, E>>,
+}
+
+impl<T, Args, E> PiedService<T, Args, E> {
+    /// Creates a new hyper service from the pipeline
+    pub fn new(pied: Pied<T, Args, Request<Bytes>, Result<Response<Bytes>, E>>) -> Self {
+        PiedService { pied }
+    }
+}
+
+impl<T, Args, E> Clone for PiedService<T, Args, E> {
+    fn clone(&self) -> Self {
+        PiedService {
+            pied: self.pied.clone(),
+        }
+    }
+}
+
+/// Builds a plain text response with the status
+fn text(status: StatusCode, body: String) -> Response<Full<Bytes>> {
+    let mut res = Response::new(Full::new(Bytes::from(body)));
+    *res.status_mut() = status;
+    res
+}
+
+/// Implements the hyper service trait for the pipeline, mapping failures to error responses
+impl<T, Args, E, B> Service<Request<B>> for PiedService<T, Args, E>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    E: Display + Send + Sync + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Display,
+{
+    type Response = Response<Full<Bytes>>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Infallible>>;
+
+    fn call(&self, req: Request<B>) -> Self::Future {
+        let pied = self.pied.clone();
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let body = match body.collect().await {
+                Ok(body) => body.to_bytes(),
+                Err(e) => return Ok(text(StatusCode::BAD_REQUEST, e.to_string())),
+            };
+            Ok(match pied.call(Request::from_parts(parts, body)).await {
+                Ok(res) => res.map(Full::new),
+                Err(e) => text(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn parse(req: Request<Bytes>) -> Result<i32, String> {
+        let body = String::from_utf8_lossy(req.body()).into_owned();
+        body.parse().map_err(|_| format!("invalid number {}", body))
+    }
+
+    async fn respond(i: Result<i32, String>) -> Result<Response<Bytes>, String> {
+        i.map(|i| Response::new(Bytes::from((i * 2).to_string())))
+    }
+
+    async fn send(
+        service: &impl Service<
+            Request<Full<Bytes>>,
+            Response = Response<Full<Bytes>>,
+            Error = Infallible,
+        >,
+        body: &'static str,
+    ) -> (StatusCode, Bytes) {
+        let req = Request::new(Full::new(Bytes::from(body)));
+        let res = service.call(req).await.unwrap();
+        let status = res.status();
+        (status, res.into_body().collect().await.unwrap().to_bytes())
+    }
+
+    #[async_std::test]
+    async fn test_pied_service() {
+        let service = PiedService::new((parse, respond).pipe());
+        assert_eq!(
+            (StatusCode::OK, Bytes::from("42")),
+            send(&service, "21").await
+        );
+        assert_eq!(
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Bytes::from("invalid number x")
+            ),
+            send(&service, "x").await
+        );
+    }
+}
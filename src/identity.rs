@@ -0,0 +1,43 @@
+//! Pass-through stage.
+
+use core::marker::PhantomData;
+
+use crate::{Middleware, Transform};
+
+/// Stage returning its input unchanged, a neutral starting point for conditionally built pipelines
+pub struct Identity<T> {
+    _phantom: PhantomData<fn(T) -> T>,
+}
+
+/// Creates a new identity stage for the type
+pub fn identity<T>() -> Identity<T> {
+    Identity {
+        _phantom: PhantomData,
+    }
+}
+
+impl<T> Clone for Identity<T> {
+    fn clone(&self) -> Self {
+        identity()
+    }
+}
+
+/// Implements the transform trait on the identity stage (for downstream)
+impl<T> Transform<(T, T), T, T> for Identity<T>
+where
+    T: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> T {
+        input
+    }
+}
+
+/// Implements the middleware trait on the identity stage
+impl<T> Middleware<T, T> for Identity<T>
+where
+    T: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> T {
+        input
+    }
+}
@@ -0,0 +1,257 @@
+//! Dependency injection of stage parameters by type.
+
+use futures::future::BoxFuture;
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    error::Error,
+    fmt,
+    future::Future,
+    sync::Arc,
+};
+
+use crate::{Middleware, StageInfo, Transform};
+
+/// Error returned when a stage depends on a type that was never provided to the injector
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MissingDependency {
+    type_name: &'static str,
+}
+
+impl MissingDependency {
+    /// Returns the name of the missing type
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+impl fmt::Display for MissingDependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "missing dependency `{}`", self.type_name)
+    }
+}
+
+impl Error for MissingDependency {}
+
+/// Container of dependencies keyed by type, resolving the parameters of stages declared as
+/// `async fn(db: Db, cache: Cache, input: T) -> O` when they are injected
+#[derive(Clone, Default)]
+pub struct Injector {
+    values: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Injector {
+    /// Creates a new empty injector
+    pub fn new() -> Self {
+        Injector::default()
+    }
+
+    /// Registers the dependency, replacing any value previously provided for its type
+    pub fn provide<D>(mut self, value: D) -> Self
+    where
+        D: Clone + Send + Sync + 'static,
+    {
+        self.values.insert(TypeId::of::<D>(), Arc::new(value));
+        self
+    }
+
+    /// Returns a clone of the dependency provided for the type
+    pub fn get<D>(&self) -> Result<D, MissingDependency>
+    where
+        D: Clone + Send + Sync + 'static,
+    {
+        self.values
+            .get(&TypeId::of::<D>())
+            .and_then(|value| value.downcast_ref::<D>())
+            .cloned()
+            .ok_or(MissingDependency {
+                type_name: type_name::<D>(),
+            })
+    }
+
+    /// Resolves the dependencies of the stage, turning it into a regular transform
+    pub fn inject<Deps, T, O>(
+        &self,
+        stage: impl InjectTransform<Deps, T, O>,
+    ) -> Result<Injected<Deps, T, O>, MissingDependency>
+    where
+        Deps: Dependencies,
+        T: Send + Sync + 'static,
+        O: Send + Sync + 'static,
+    {
+        Ok(Injected {
+            deps: Deps::resolve(self)?,
+            stage: Arc::new(stage),
+        })
+    }
+}
+
+/// Tuple of dependency types that can be resolved from an injector
+pub trait Dependencies: Clone + Send + Sync + 'static {
+    /// Resolves every dependency, failing on the first type that was not provided
+    fn resolve(injector: &Injector) -> Result<Self, MissingDependency>;
+}
+
+/// Middleware that transforms an input to output type with injected dependencies.
+pub trait InjectTransform<Deps, T, O>: Send + Sync + 'static {
+    /// Asynchronously execute this handler with the resolved dependencies
+    fn transform_with(&self, deps: Deps, input: T) -> impl Future<Output = O> + Send;
+
+    /// Describes the stages making up this transform, in order
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::of::<Self, T, O>()]
+    }
+}
+
+/// Object-safe form of an injectable transform that boxes its future, used where stages are
+/// stored
+pub trait DynInjectTransform<Deps, T, O>: Send + Sync + 'static {
+    /// Execute this handler with the resolved dependencies, boxing the returned future
+    fn transform_with_dyn(&self, deps: Deps, input: T) -> BoxFuture<'_, O>;
+
+    /// Describes the stages making up this transform, in order
+    fn stages_dyn(&self) -> Vec<StageInfo>;
+}
+
+/// Every injectable transform can be erased by boxing its future
+impl<X, Deps, T, O> DynInjectTransform<Deps, T, O> for X
+where
+    X: InjectTransform<Deps, T, O>,
+    Deps: 'static,
+    T: 'static,
+    O: 'static,
+{
+    fn transform_with_dyn(&self, deps: Deps, input: T) -> BoxFuture<'_, O> {
+        Box::pin(self.transform_with(deps, input))
+    }
+
+    fn stages_dyn(&self) -> Vec<StageInfo> {
+        self.stages()
+    }
+}
+
+/// Erased injectable transforms are injectable transforms themselves, so stored stages are called
+/// the same way
+impl<Deps, T, O> InjectTransform<Deps, T, O> for dyn DynInjectTransform<Deps, T, O>
+where
+    Deps: Send + 'static,
+    T: Send + 'static,
+    O: Send + 'static,
+{
+    fn transform_with(&self, deps: Deps, input: T) -> impl Future<Output = O> + Send {
+        self.transform_with_dyn(deps, input)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stages_dyn()
+    }
+}
+
+macro_rules! impl_inject {
+    ($($dep:ident),+) => {
+        /// Resolves each dependency of the tuple by type
+        impl<$($dep),+> Dependencies for ($($dep,)+)
+        where
+            $($dep: Clone + Send + Sync + 'static,)+
+        {
+            fn resolve(injector: &Injector) -> Result<Self, MissingDependency> {
+                Ok(($(injector.get::<$dep>()?,)+))
+            }
+        }
+
+        /// Injectable implementation for an async function taking its dependencies then the input
+        impl<Func, Fut, $($dep,)+ T, O> InjectTransform<($($dep,)+), T, O> for Func
+        where
+            Func: Send + Sync + 'static + Fn($($dep,)+ T) -> Fut,
+            Fut: Future<Output = O> + Send + 'static,
+            $($dep: Send + 'static,)+
+            T: Send + 'static,
+            O: Send + 'static,
+        {
+            #[allow(non_snake_case)]
+            fn transform_with(&self, deps: ($($dep,)+), input: T) -> impl Future<Output = O> + Send {
+                let ($($dep,)+) = deps;
+                (self)($($dep,)+ input)
+            }
+        }
+    };
+}
+
+impl_inject!(D1);
+impl_inject!(D1, D2);
+impl_inject!(D1, D2, D3);
+impl_inject!(D1, D2, D3, D4);
+
+/// Encapsulates a stage with the dependencies resolved for it
+pub struct Injected<Deps, T, O> {
+    deps: Deps,
+    stage: Arc<dyn DynInjectTransform<Deps, T, O>>,
+}
+
+/// Implements the transform trait on the injected stage (for downstream)
+impl<Deps, T, O> Transform<(T, O), T, O> for Injected<Deps, T, O>
+where
+    Deps: Dependencies,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> O {
+        self.stage.transform_with(self.deps.clone(), input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the injected stage
+impl<Deps, T, O> Middleware<T, O> for Injected<Deps, T, O>
+where
+    Deps: Dependencies,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    #[derive(Clone)]
+    struct Db(Arc<Vec<&'static str>>);
+
+    #[derive(Clone)]
+    struct Prefix(&'static str);
+
+    async fn lookup(db: Db, i: usize) -> String {
+        db.0[i].to_string()
+    }
+
+    async fn render(prefix: Prefix, db: Db, name: String) -> String {
+        format!("{}{} of {}", prefix.0, name, db.0.len())
+    }
+
+    #[async_std::test]
+    async fn test_injector() {
+        let injector = Injector::new()
+            .provide(Db(Arc::new(vec!["alice", "bob"])))
+            .provide(Prefix("user "));
+        let m = (
+            injector.inject(lookup).unwrap(),
+            injector.inject(render).unwrap(),
+        )
+            .pipe();
+        assert_eq!(String::from("user bob of 2"), m.call(1).await);
+
+        let err = Injector::new().inject(lookup).err().unwrap();
+        assert!(err.type_name().ends_with("Db"));
+    }
+}
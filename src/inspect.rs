@@ -0,0 +1,167 @@
+//! Tap stages that observe values without consuming them.
+
+use core::future::Future;
+
+use crate::{Middleware, Pied, Transform};
+
+/// Stage that passes its input through after handing a reference to a function
+pub struct Inspect<F> {
+    f: F,
+}
+
+/// Creates a new stage that observes every value flowing through it
+pub fn inspect<F>(f: F) -> Inspect<F> {
+    Inspect { f }
+}
+
+/// Implements the transform trait on the inspect stage (for downstream)
+impl<F, T> Transform<(T, T), T, T> for Inspect<F>
+where
+    F: Fn(&T) + Send + Sync + 'static,
+    T: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> T {
+        (self.f)(&input);
+        input
+    }
+}
+
+/// Implements the middleware trait on the inspect stage
+impl<F, T> Middleware<T, T> for Inspect<F>
+where
+    F: Fn(&T) + Send + Sync + 'static,
+    T: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> T {
+        self.transform(input).await
+    }
+}
+
+/// Stage that passes its input through after awaiting the future built from a reference to it
+pub struct InspectAsync<F> {
+    f: F,
+}
+
+/// Creates a new stage that asynchronously observes every value flowing through it
+pub fn inspect_async<F>(f: F) -> InspectAsync<F> {
+    InspectAsync { f }
+}
+
+/// Implements the transform trait on the async inspect stage (for downstream)
+impl<F, Fut, T> Transform<(T, T), T, T> for InspectAsync<F>
+where
+    F: Fn(&T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+    T: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> T {
+        (self.f)(&input).await;
+        input
+    }
+}
+
+/// Implements the middleware trait on the async inspect stage
+impl<F, Fut, T> Middleware<T, T> for InspectAsync<F>
+where
+    F: Fn(&T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+    T: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> T {
+        self.transform(input).await
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Observes every output of the pipeline without consuming it; appended last, it runs once
+    /// per call, so cross-cutting concerns such as metrics are attached once per pipeline
+    pub fn inspect<F>(self, f: F) -> Pied<T, Args, I, O>
+    where
+        F: Fn(&O) + Send + Sync + 'static,
+    {
+        self.then(inspect(f))
+    }
+
+    /// Asynchronously observes every output of the pipeline without consuming it
+    pub fn inspect_async<F, Fut>(self, f: F) -> Pied<T, Args, I, O>
+    where
+        F: Fn(&O) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.then(inspect_async(f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use std::sync::{Arc, Mutex};
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_inspect() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let m = (
+            multipler,
+            inspect(move |i: &i32| log.lock().unwrap().push(*i)),
+            stringer,
+        )
+            .pipe();
+        assert_eq!(String::from("64"), m.call(2).await);
+
+        let log = seen.clone();
+        let m = m.inspect(move |s: &String| log.lock().unwrap().push(s.len() as i32));
+        assert_eq!(String::from("96"), m.call(3).await);
+        assert_eq!(vec![64, 96, 2], *seen.lock().unwrap());
+    }
+
+    #[async_std::test]
+    async fn test_inspect_async() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let m = (multipler, stringer)
+            .pipe()
+            .inspect_async(move |s: &String| {
+                let (log, s) = (log.clone(), s.clone());
+                async move { log.lock().unwrap().push(s) }
+            });
+        assert_eq!(String::from("32"), m.call(1).await);
+        assert_eq!(vec![String::from("32")], *seen.lock().unwrap());
+    }
+
+    #[async_std::test]
+    async fn test_completion_hooks() {
+        let completed = Arc::new(Mutex::new(0));
+        let failed = Arc::new(Mutex::new(Vec::new()));
+        let (c, f) = (completed.clone(), failed.clone());
+        let m = (multipler, |i: i32| async move {
+            if i > 100 {
+                Err(format!("{} too large", i))
+            } else {
+                Ok(i)
+            }
+        })
+            .pipe()
+            .inspect(move |_| *c.lock().unwrap() += 1)
+            .inspect_err(move |e: &String| f.lock().unwrap().push(e.clone()));
+        assert_eq!(Ok(64), m.call(2).await);
+        assert!(m.call(4).await.is_err());
+        assert_eq!(2, *completed.lock().unwrap());
+        assert_eq!(vec![String::from("128 too large")], *failed.lock().unwrap());
+    }
+}
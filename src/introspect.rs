@@ -0,0 +1,356 @@
+//! Stage names and pipeline introspection.
+
+use alloc::{borrow::Cow, string::String, sync::Arc, vec, vec::Vec};
+use core::{
+    any::type_name,
+    fmt::{self, Write},
+};
+
+use crate::{ConvertMiddleware, DynTransform, Middleware, Pied, Transform};
+
+/// Description of a single stage within a pipeline
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StageInfo {
+    /// Name of the stage, defaulting to its type name
+    pub name: Cow<'static, str>,
+    /// Type name of the input of the stage
+    pub input: &'static str,
+    /// Type name of the output of the stage
+    pub output: &'static str,
+    /// Position of the stage within the pipeline, starting at zero
+    pub position: usize,
+    /// Labelled sub-pipelines the stage dispatches to (e.g. the arms of a branch)
+    pub branches: Vec<(Cow<'static, str>, Vec<StageInfo>)>,
+}
+
+impl StageInfo {
+    /// Describes a stage by its type name and the type names of its input and output
+    pub(crate) fn of<S: ?Sized, I, O>() -> Self {
+        StageInfo {
+            name: Cow::Borrowed(type_name::<S>()),
+            input: type_name::<I>(),
+            output: type_name::<O>(),
+            position: 0,
+            branches: Vec::new(),
+        }
+    }
+
+    /// Describes a combinator stage by name along with the sub-pipelines it dispatches to
+    pub(crate) fn branching<I, O>(
+        name: &'static str,
+        branches: Vec<(Cow<'static, str>, Vec<StageInfo>)>,
+    ) -> Self {
+        StageInfo {
+            name: Cow::Borrowed(name),
+            branches,
+            ..StageInfo::of::<(), I, O>()
+        }
+    }
+}
+
+/// Joins the stages of two sequential parts of a pipeline, renumbering their positions
+pub(crate) fn sequence(first: Vec<StageInfo>, second: Vec<StageInfo>) -> Vec<StageInfo> {
+    first
+        .into_iter()
+        .chain(second)
+        .enumerate()
+        .map(|(position, stage)| StageInfo { position, ..stage })
+        .collect()
+}
+
+/// Writes the nodes and edges of a sequence of stages, connecting its first stage to the
+/// given exits of whatever came before, and returns the exits of its last stage
+fn render(
+    stages: &[StageInfo],
+    mut exits: Vec<(usize, Option<Cow<'static, str>>)>,
+    next_id: &mut usize,
+    out: &mut String,
+) -> Vec<(usize, Option<Cow<'static, str>>)> {
+    for stage in stages {
+        let id = *next_id;
+        *next_id += 1;
+        let label = stage.name.replace('\\', "\\\\").replace('"', "\\\"");
+        let _ = writeln!(out, "    n{} [label=\"{}\"];", id, label);
+        for (from, edge) in exits.drain(..) {
+            match edge {
+                Some(edge) => {
+                    let _ = writeln!(out, "    n{} -> n{} [label=\"{}\"];", from, id, edge);
+                }
+                None => {
+                    let _ = writeln!(out, "    n{} -> n{};", from, id);
+                }
+            }
+        }
+        if stage.branches.is_empty() {
+            exits.push((id, None));
+        }
+        for (edge, branch) in &stage.branches {
+            if branch.is_empty() {
+                exits.push((id, Some(edge.clone())));
+            } else {
+                exits.extend(render(branch, vec![(id, Some(edge.clone()))], next_id, out));
+            }
+        }
+    }
+    exits
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Renders the stage graph of the pipeline, including its branches, as Graphviz DOT
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph pipeline {\n    rankdir=LR;\n");
+        render(&self.stages(), Vec::new(), &mut 0, &mut out);
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Shortens every path within a type name to its last segment, e.g.
+/// `app::Filter<alloc::string::String>` to `Filter<String>`
+fn short_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut segment_start = 0;
+    for (i, c) in name.char_indices() {
+        if c == ':' && name[i..].starts_with("::") {
+            out.truncate(segment_start);
+        } else if c != ':' {
+            if !(c.is_alphanumeric() || c == '_' || c == '{' || c == '}') {
+                segment_start = out.len() + c.len_utf8();
+            }
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Writes the stages as a chain of short names, with the sub-pipelines of branching stages in
+/// brackets, e.g. `parse -> branch[then: scale | otherwise: clamp] -> render`
+fn write_chain(stages: &[StageInfo], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (i, stage) in stages.iter().enumerate() {
+        if i > 0 {
+            f.write_str(" -> ")?;
+        }
+        f.write_str(&short_name(&stage.name))?;
+        if !stage.branches.is_empty() {
+            f.write_str("[")?;
+            for (j, (label, branch)) in stage.branches.iter().enumerate() {
+                if j > 0 {
+                    f.write_str(" | ")?;
+                }
+                write!(f, "{}: ", label)?;
+                write_chain(branch, f)?;
+            }
+            f.write_str("]")?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints the chain of stage names making up the pipeline
+impl<T, Args, I, O> fmt::Debug for Pied<T, Args, I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Pied(")?;
+        write_chain(&self.stages(), f)?;
+        f.write_str(")")
+    }
+}
+
+/// Prints the chain of stage names making up the conversion
+impl<T, T2, A, B, C> fmt::Debug for ConvertMiddleware<T, T2, A, B, C>
+where
+    T: Send + Sync + 'static,
+    T2: Send + Sync + 'static,
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    C: Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ConvertMiddleware(")?;
+        write_chain(&Transform::stages(self), f)?;
+        f.write_str(")")
+    }
+}
+
+/// Stage with a name attached, reported by introspection instead of its type name
+pub struct Named<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    name: Cow<'static, str>,
+}
+
+/// Extension methods available on every transform
+pub trait TransformExt<Args, I, O>: Transform<Args, I, O> + Sized
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Attaches a name to the stage (e.g. `"decode"`) used by introspection
+    fn named(self, name: impl Into<Cow<'static, str>>) -> Named<Args, I, O> {
+        Named {
+            stage: Arc::new(self),
+            name: name.into(),
+        }
+    }
+}
+
+impl<S, Args, I, O> TransformExt<Args, I, O> for S
+where
+    S: Transform<Args, I, O>,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+}
+
+/// Implements the transform trait on the named stage (for downstream)
+impl<Args, I, O> Transform<(I, O), I, O> for Named<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        self.stage.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        // a single inner stage keeps its branches, a whole pipeline collapses into one stage
+        let mut inner = self.stage.stages();
+        let stage = match inner.len() {
+            1 => inner.remove(0),
+            _ => StageInfo::of::<Self, I, O>(),
+        };
+        vec![StageInfo {
+            name: self.name.clone(),
+            position: 0,
+            ..stage
+        }]
+    }
+}
+
+/// Implements the middleware trait on the named stage
+impl<Args, I, O> Middleware<I, O> for Named<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn parse(s: String) -> i32 {
+        s.parse().unwrap_or_default()
+    }
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_stages() {
+        let m = (parse.named("decode"), multipler, stringer.named("encode")).pipe();
+        assert_eq!(String::from("64"), m.call(String::from("2")).await);
+
+        let stages = m.stages();
+        let names: Vec<&str> = stages.iter().map(|s| s.name.as_ref()).collect();
+        assert_eq!(
+            vec![
+                "decode",
+                "async_middleware::introspect::tests::multipler",
+                "encode"
+            ],
+            names
+        );
+        assert_eq!(
+            vec![0, 1, 2],
+            stages.iter().map(|s| s.position).collect::<Vec<_>>()
+        );
+        assert_eq!("alloc::string::String", stages[0].input);
+        assert_eq!("i32", stages[0].output);
+
+        // nested pipelines are flattened
+        let m = (m, parse).pipe();
+        assert_eq!(4, m.stages().len());
+        assert_eq!(3, m.stages()[3].position);
+    }
+
+    #[async_std::test]
+    async fn test_to_dot() {
+        let m = (
+            parse.named("decode"),
+            crate::branch(
+                |i: &i32| *i > 0,
+                multipler.named("scale"),
+                (multipler.named("a"), multipler.named("b")).pipe(),
+            )
+            .named("check"),
+            stringer.named("encode"),
+        )
+            .pipe();
+        assert_eq!(
+            vec![
+                "digraph pipeline {",
+                "    rankdir=LR;",
+                "    n0 [label=\"decode\"];",
+                "    n1 [label=\"check\"];",
+                "    n0 -> n1;",
+                "    n2 [label=\"scale\"];",
+                "    n1 -> n2 [label=\"then\"];",
+                "    n3 [label=\"a\"];",
+                "    n1 -> n3 [label=\"otherwise\"];",
+                "    n4 [label=\"b\"];",
+                "    n3 -> n4;",
+                "    n5 [label=\"encode\"];",
+                "    n2 -> n5;",
+                "    n4 -> n5;",
+                "}",
+            ],
+            m.to_dot().lines().collect::<Vec<_>>()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_debug() {
+        let m = (parse, multipler, stringer).pipe();
+        assert_eq!("Pied(parse -> multipler -> stringer)", format!("{:?}", m));
+
+        let m = (
+            parse.named("decode"),
+            crate::branch(|i: &i32| *i > 0, multipler, crate::identity::<i32>()),
+            crate::convert(stringer, parse),
+        )
+            .pipe();
+        assert_eq!(
+            "Pied(decode -> branch[then: multipler | otherwise: Identity<i32>] -> stringer -> parse)",
+            format!("{:?}", m)
+        );
+        let c = crate::convert(multipler, stringer);
+        assert_eq!(
+            "ConvertMiddleware(multipler -> stringer)",
+            format!("{:?}", c)
+        );
+    }
+}
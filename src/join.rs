@@ -0,0 +1,98 @@
+//! Concurrent execution of two pipelines on the same input.
+
+use alloc::{sync::Arc, vec, vec::Vec};
+
+use crate::{DynTransform, Middleware, StageInfo, Transform};
+
+/// Runs two stages concurrently on clones of the input and pairs up their outputs
+pub struct Join<A, A2, I, O, O2> {
+    left: Arc<dyn DynTransform<A, I, O>>,
+    right: Arc<dyn DynTransform<A2, I, O2>>,
+}
+
+/// Creates a new join of two stages, producing both outputs as a tuple
+pub fn join<A, A2, I, O, O2>(
+    left: impl Transform<A, I, O>,
+    right: impl Transform<A2, I, O2>,
+) -> Join<A, A2, I, O, O2>
+where
+    A: Send + Sync + 'static,
+    A2: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    O2: Send + Sync + 'static,
+{
+    Join {
+        left: Arc::new(left),
+        right: Arc::new(right),
+    }
+}
+
+/// Implements the transform trait on the join (for downstream)
+impl<A, A2, I, O, O2> Transform<(I, (O, O2)), I, (O, O2)> for Join<A, A2, I, O, O2>
+where
+    A: Send + Sync + 'static,
+    A2: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    O2: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> (O, O2) {
+        futures::join!(
+            self.left.transform(input.clone()),
+            self.right.transform(input)
+        )
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::branching::<I, (O, O2)>(
+            "join",
+            vec![
+                ("left".into(), self.left.stages()),
+                ("right".into(), self.right.stages()),
+            ],
+        )]
+    }
+}
+
+/// Implements the middleware trait on the join
+impl<A, A2, I, O, O2> Middleware<I, (O, O2)> for Join<A, A2, I, O, O2>
+where
+    A: Send + Sync + 'static,
+    A2: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    O2: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> (O, O2) {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    async fn merge(pair: (i32, String)) -> String {
+        format!("{} {}", pair.0, pair.1)
+    }
+
+    #[async_std::test]
+    async fn test_join() {
+        let m = (join(multipler, (multipler, stringer).pipe()), merge).pipe();
+        assert_eq!(String::from("64 64"), m.call(2).await);
+    }
+}
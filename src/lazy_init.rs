@@ -0,0 +1,137 @@
+//! Stage state constructed asynchronously on first use.
+
+use async_lock::OnceCell;
+use std::{future::Future, sync::Arc};
+
+use crate::{DynStateTransform, Middleware, StageInfo, State, StateTransform, Transform};
+
+/// Encapsulates a stateful stage whose state is built by an async factory on the first call
+pub struct LazyInit<S, F, Args, T, O> {
+    state: OnceCell<Arc<S>>,
+    init: F,
+    stage: Arc<dyn DynStateTransform<S, Args, T, O>>,
+}
+
+/// Creates a new stage resolving its state with the async factory on the first call (e.g. a
+/// connection pool or a loaded model); concurrent first calls wait for a single initialization
+pub fn lazy_init<S, F, Fut, Args, T, O>(
+    init: F,
+    stage: impl StateTransform<S, Args, T, O>,
+) -> LazyInit<S, F, Args, T, O>
+where
+    S: Send + Sync + 'static,
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = S> + Send + 'static,
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    LazyInit {
+        state: OnceCell::new(),
+        init,
+        stage: Arc::new(stage),
+    }
+}
+
+impl<S, F, Fut, Args, T, O> LazyInit<S, F, Args, T, O>
+where
+    S: Send + Sync + 'static,
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = S> + Send + 'static,
+{
+    /// Builds the state ahead of the first call if it isn't built yet
+    pub async fn warm_up(&self) {
+        self.state().await;
+    }
+
+    /// Returns whether the state has been built
+    pub fn is_initialized(&self) -> bool {
+        self.state.is_initialized()
+    }
+
+    async fn state(&self) -> State<S> {
+        let state = self
+            .state
+            .get_or_init(|| async { Arc::new((self.init)().await) })
+            .await;
+        State(state.clone())
+    }
+}
+
+/// Implements the transform trait on the lazily initialized stage (for downstream)
+impl<S, F, Fut, Args, T, O> Transform<(T, O), T, O> for LazyInit<S, F, Args, T, O>
+where
+    S: Send + Sync + 'static,
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = S> + Send + 'static,
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> O {
+        let state = self.state().await;
+        self.stage.transform_with(state, input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the lazily initialized stage
+impl<S, F, Fut, Args, T, O> Middleware<T, O> for LazyInit<S, F, Args, T, O>
+where
+    S: Send + Sync + 'static,
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = S> + Send + 'static,
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use futures::future::join_all;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CONNECTS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Pool {
+        prefix: String,
+    }
+
+    async fn connect() -> Pool {
+        CONNECTS.fetch_add(1, Ordering::SeqCst);
+        async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+        Pool {
+            prefix: String::from("row "),
+        }
+    }
+
+    async fn query(pool: State<Pool>, id: i32) -> String {
+        format!("{}{}", pool.prefix, id)
+    }
+
+    #[async_std::test]
+    async fn test_lazy_init() {
+        let stage = lazy_init(connect, query);
+        assert!(!stage.is_initialized());
+        stage.warm_up().await;
+        assert!(stage.is_initialized());
+
+        let m = (|i: i32| async move { i + 1 }, stage).pipe();
+        let outputs = join_all((0..3).map(|i| m.call(i))).await;
+        assert_eq!(vec!["row 1", "row 2", "row 3"], outputs);
+        assert_eq!(1, CONNECTS.load(Ordering::SeqCst));
+    }
+}
@@ -1,56 +1,447 @@
 //! Middleware types.
-
-use async_trait::async_trait;
-use std::{future::Future, marker::PhantomData, sync::Arc};
+//!
+//! Without the default `std` feature only the core transforms and their composition are built,
+//! which need nothing beyond `alloc`, so pipelines also run on `no_std` executors.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::{boxed::Box, sync::Arc, vec, vec::Vec};
+use core::{future::Future, marker::PhantomData};
+use futures::future::BoxFuture;
+
+#[cfg(feature = "std")]
+mod ab_split;
+#[cfg(feature = "std")]
+mod any_middleware;
+#[cfg(feature = "audit")]
+mod audit;
+#[cfg(feature = "std")]
+mod balance;
+#[cfg(feature = "std")]
+mod batch;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+mod blocking;
+#[cfg(feature = "std")]
+mod borrowed;
+mod branch;
+#[cfg(feature = "std")]
+mod breaker;
+#[cfg(feature = "std")]
+mod broadcast;
+#[cfg(feature = "std")]
+mod broker;
+#[cfg(feature = "std")]
+mod bulkhead;
+#[cfg(feature = "std")]
+mod cache;
+mod chain;
+#[cfg(feature = "std")]
+mod channel;
+#[cfg(feature = "std")]
+mod concurrency;
+#[cfg(feature = "std")]
+mod context;
+#[cfg(feature = "std")]
+mod deadline;
+#[cfg(feature = "std")]
+mod dedup;
+#[cfg(feature = "std")]
+mod delay;
+#[cfg(feature = "std")]
+mod dyn_chain;
+mod either;
+#[cfg(feature = "metrics")]
+mod exporter;
+mod fallible;
+#[cfg(feature = "std")]
+mod feedback;
+mod filter;
+mod from_fn;
+#[cfg(feature = "tonic")]
+mod grpc;
+mod guard;
+#[cfg(feature = "axum")]
+mod handler;
+#[cfg(feature = "std")]
+mod hedge;
+#[cfg(feature = "hyper")]
+mod hyper_service;
+mod identity;
+#[cfg(feature = "std")]
+mod inject;
+mod inspect;
+mod introspect;
+mod join;
+#[cfg(feature = "std")]
+mod lazy_init;
+#[cfg(feature = "std")]
+mod load_shed;
+#[cfg(feature = "std")]
+mod local;
+#[cfg(feature = "log")]
+mod logging;
+#[cfg(feature = "std")]
+mod metrics;
+mod optional;
+#[cfg(feature = "otel")]
+mod otel;
+#[cfg(feature = "std")]
+mod overflow;
+#[cfg(feature = "std")]
+mod panic;
+#[cfg(feature = "std")]
+mod priority;
+#[cfg(feature = "std")]
+mod profile;
+#[cfg(feature = "std")]
+mod race;
+#[cfg(feature = "std")]
+mod rate_limit;
+#[cfg(feature = "serde")]
+mod registry;
+mod repeat;
+#[cfg(feature = "std")]
+mod retry;
+#[cfg(feature = "std")]
+mod router;
+#[cfg(feature = "std")]
+mod rt;
+#[cfg(feature = "std")]
+mod saga;
+#[cfg(feature = "std")]
+mod sample;
+#[cfg(feature = "std")]
+mod scan;
+#[cfg(feature = "std")]
+mod schedule;
+#[cfg(feature = "tower")]
+mod service;
+#[cfg(feature = "std")]
+mod shadow;
+#[cfg(feature = "std")]
+mod source;
+#[cfg(feature = "std")]
+mod spawned;
+#[cfg(feature = "std")]
+mod state;
+#[cfg(feature = "std")]
+mod stateful;
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "std")]
+mod swap;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(feature = "std")]
+mod throttle;
+#[cfg(feature = "std")]
+mod timeout;
+#[cfg(feature = "tracing")]
+mod trace;
+#[cfg(feature = "std")]
+mod util;
+#[cfg(feature = "std")]
+mod window;
+#[cfg(feature = "std")]
+mod with_ctx;
+#[cfg(feature = "std")]
+mod workers;
+#[cfg(feature = "std")]
+mod wrap;
+
+#[cfg(feature = "std")]
+pub use ab_split::*;
+#[cfg(feature = "std")]
+pub use any_middleware::*;
+#[cfg(feature = "audit")]
+pub use audit::*;
+#[cfg(feature = "std")]
+pub use balance::*;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub use blocking::*;
+#[cfg(feature = "std")]
+pub use borrowed::*;
+pub use branch::*;
+#[cfg(feature = "std")]
+pub use breaker::*;
+#[cfg(feature = "std")]
+pub use broadcast::*;
+#[cfg(feature = "std")]
+pub use broker::*;
+#[cfg(feature = "std")]
+pub use bulkhead::*;
+#[cfg(feature = "std")]
+pub use cache::*;
+pub use chain::*;
+#[cfg(feature = "std")]
+pub use channel::*;
+#[cfg(feature = "std")]
+pub use concurrency::*;
+#[cfg(feature = "std")]
+pub use context::*;
+#[cfg(feature = "std")]
+pub use deadline::*;
+#[cfg(feature = "std")]
+pub use dedup::*;
+#[cfg(feature = "std")]
+pub use delay::*;
+#[cfg(feature = "std")]
+pub use dyn_chain::*;
+pub use either::*;
+#[cfg(feature = "metrics")]
+pub use exporter::*;
+pub use fallible::*;
+#[cfg(feature = "std")]
+pub use feedback::*;
+pub use filter::*;
+pub use from_fn::*;
+#[cfg(feature = "tonic")]
+pub use grpc::*;
+pub use guard::*;
+#[cfg(feature = "axum")]
+pub use handler::*;
+#[cfg(feature = "std")]
+pub use hedge::*;
+#[cfg(feature = "hyper")]
+pub use hyper_service::*;
+pub use identity::*;
+#[cfg(feature = "std")]
+pub use inject::*;
+pub use inspect::*;
+pub use introspect::*;
+pub use join::*;
+#[cfg(feature = "std")]
+pub use lazy_init::*;
+#[cfg(feature = "std")]
+pub use load_shed::*;
+#[cfg(feature = "std")]
+pub use local::*;
+#[cfg(feature = "log")]
+pub use logging::*;
+#[cfg(feature = "std")]
+pub use metrics::*;
+pub use optional::*;
+#[cfg(feature = "otel")]
+pub use otel::*;
+#[cfg(feature = "std")]
+pub use overflow::*;
+#[cfg(feature = "std")]
+pub use panic::*;
+#[cfg(feature = "std")]
+pub use profile::*;
+#[cfg(feature = "std")]
+pub use race::*;
+#[cfg(feature = "std")]
+pub use rate_limit::*;
+#[cfg(feature = "serde")]
+pub use registry::*;
+pub use repeat::*;
+#[cfg(feature = "std")]
+pub use retry::*;
+#[cfg(feature = "std")]
+pub use router::*;
+#[cfg(feature = "std")]
+pub use saga::*;
+#[cfg(feature = "std")]
+pub use sample::*;
+#[cfg(feature = "std")]
+pub use scan::*;
+#[cfg(feature = "std")]
+pub use schedule::*;
+#[cfg(feature = "tower")]
+pub use service::*;
+#[cfg(feature = "std")]
+pub use shadow::*;
+#[cfg(feature = "std")]
+pub use source::*;
+#[cfg(feature = "std")]
+pub use spawned::*;
+#[cfg(feature = "std")]
+pub use state::*;
+#[cfg(feature = "std")]
+pub use stateful::*;
+#[cfg(feature = "std")]
+pub use stream::*;
+#[cfg(feature = "std")]
+pub use swap::*;
+#[cfg(feature = "std")]
+pub use throttle::*;
+#[cfg(feature = "std")]
+pub use timeout::*;
+#[cfg(feature = "tracing")]
+pub use trace::*;
+#[cfg(feature = "std")]
+pub use window::*;
+#[cfg(feature = "std")]
+pub use with_ctx::*;
+#[cfg(feature = "std")]
+pub use wrap::*;
 
 /// Middleware that transforms around an input to output type.
-#[async_trait]
 pub trait Transform<Args, T, O>: Send + Sync + 'static {
     /// Asynchronously execute this handler to modify state
-    async fn transform(&self, input: T) -> O;
+    fn transform(&self, input: T) -> impl Future<Output = O> + Send;
+
+    /// Describes the stages making up this transform, in order
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::of::<Self, T, O>()]
+    }
+}
+
+/// Object-safe form of a transform that boxes its future, used where stages are erased
+pub trait DynTransform<Args, T, O>: Send + Sync + 'static {
+    /// Execute this handler, boxing the returned future
+    fn transform_dyn(&self, input: T) -> BoxFuture<'_, O>;
+
+    /// Describes the stages making up this transform, in order
+    fn stages_dyn(&self) -> Vec<StageInfo>;
+}
+
+/// Every transform can be erased by boxing its future
+impl<X, Args, T, O> DynTransform<Args, T, O> for X
+where
+    X: Transform<Args, T, O>,
+    Args: 'static,
+    T: 'static,
+    O: 'static,
+{
+    fn transform_dyn(&self, input: T) -> BoxFuture<'_, O> {
+        Box::pin(self.transform(input))
+    }
+
+    fn stages_dyn(&self) -> Vec<StageInfo> {
+        self.stages()
+    }
+}
+
+/// Erased transforms are transforms themselves, so stored stages are called the same way
+impl<Args, T, O> Transform<Args, T, O> for dyn DynTransform<Args, T, O>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    fn transform(&self, input: T) -> impl Future<Output = O> + Send {
+        self.transform_dyn(input)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stages_dyn()
+    }
 }
 
 /// Middleware implementation for an async function that produces an output
-#[async_trait]
 impl<Func, Fut, O> Transform<(), (), O> for Func
 where
     Func: Send + Sync + 'static + Fn() -> Fut,
-    Fut: Future<Output = O> + Send + Sync + 'static,
+    Fut: Future<Output = O> + Send + 'static,
     O: Send + Sync + 'static,
 {
-    async fn transform(&self, _input: ()) -> O {
-        (self)().await
+    fn transform(&self, _input: ()) -> impl Future<Output = O> + Send {
+        (self)()
     }
 }
 
 /// Middleware implementation for an async function that returns nothing
-#[async_trait]
 impl<Func, Fut, T, O> Transform<(T, O), T, O> for Func
 where
     Func: Send + Sync + 'static + Fn(T) -> Fut,
-    Fut: Future<Output = O> + Send + Sync + 'static,
+    Fut: Future<Output = O> + Send + 'static,
     T: Send + Sync + 'static,
     O: Send + Sync + 'static,
 {
-    async fn transform(&self, input: T) -> O {
-        (self)(input).await
+    fn transform(&self, input: T) -> impl Future<Output = O> + Send {
+        (self)(input)
     }
 }
 
+/// Generates the transform implementation for an async function of several arguments, taking
+/// them as a tuple input; the arity of the `Args` marker keeps it apart from the other impls
+macro_rules! impl_transform_args {
+    ($($A:ident $a:ident),+) => {
+        /// Middleware implementation for an async function accepting several arguments
+        impl<Func, Fut, $($A,)+ O> Transform<($($A,)+ O), ($($A,)+), O> for Func
+        where
+            Func: Send + Sync + 'static + Fn($($A),+) -> Fut,
+            Fut: Future<Output = O> + Send + 'static,
+            $($A: Send + Sync + 'static,)+
+            O: Send + Sync + 'static,
+        {
+            fn transform(&self, input: ($($A,)+)) -> impl Future<Output = O> + Send {
+                let ($($a,)+) = input;
+                (self)($($a),+)
+            }
+        }
+    };
+}
+
+impl_transform_args!(A a, B b);
+impl_transform_args!(A a, B b, C c);
+impl_transform_args!(A a, B b, C c, D d);
+impl_transform_args!(A a, B b, C c, D d, E e);
+impl_transform_args!(A a, B b, C c, D d, E e, F f);
+impl_transform_args!(A a, B b, C c, D d, E e, F f, G g);
+impl_transform_args!(A a, B b, C c, D d, E e, F f, G g, H h);
+
 /// Middleware that performs an operation.
-#[async_trait]
 pub trait Middleware<I, O>: Send + Sync + 'static {
-    async fn call(&self, input: I) -> O;
+    fn call(&self, input: I) -> impl Future<Output = O> + Send;
+
+    /// Describes the stages making up this middleware, in order
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::of::<Self, I, O>()]
+    }
+}
+
+/// Object-safe form of a middleware that boxes its future, used where pipelines are erased
+pub trait DynMiddleware<I, O>: Send + Sync + 'static {
+    /// Execute this middleware, boxing the returned future
+    fn call_dyn(&self, input: I) -> BoxFuture<'_, O>;
+
+    /// Describes the stages making up this middleware, in order
+    fn stages_dyn(&self) -> Vec<StageInfo>;
+}
+
+/// Every middleware can be erased by boxing its future
+impl<M, I, O> DynMiddleware<I, O> for M
+where
+    M: Middleware<I, O>,
+    I: 'static,
+    O: 'static,
+{
+    fn call_dyn(&self, input: I) -> BoxFuture<'_, O> {
+        Box::pin(self.call(input))
+    }
+
+    fn stages_dyn(&self) -> Vec<StageInfo> {
+        self.stages()
+    }
+}
+
+/// Erased middleware are middleware themselves, so stored pipelines are called the same way
+impl<I, O> Middleware<I, O> for dyn DynMiddleware<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    fn call(&self, input: I) -> impl Future<Output = O> + Send {
+        self.call_dyn(input)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stages_dyn()
+    }
 }
 
 /// Encapsulates the conversion between two different transform types
 pub struct ConvertMiddleware<T, T2, A, B, C> {
-    t: Arc<dyn Transform<T, A, B>>,
-    t2: Arc<dyn Transform<T2, B, C>>,
+    t: Arc<dyn DynTransform<T, A, B>>,
+    t2: Arc<dyn DynTransform<T2, B, C>>,
 }
 
 /// Implements the transform trait on the conversion middleware (for downstream)
-#[async_trait]
 impl<T, T2, A, B, C> Transform<(A, C), A, C> for ConvertMiddleware<T, T2, A, B, C>
 where
     T: Send + Sync + 'static,
@@ -63,10 +454,13 @@ where
         let input = self.t.transform(input).await;
         self.t2.transform(input).await
     }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        sequence(self.t.stages(), self.t2.stages())
+    }
 }
 
 /// Implements the middleware trait on the conversion middleware to make it A -> C
-#[async_trait]
 impl<T, T2, A, B, C> Middleware<A, C> for ConvertMiddleware<T, T2, A, B, C>
 where
     T: Send + Sync + 'static,
@@ -78,9 +472,14 @@ where
     async fn call(&self, input: A) -> C {
         self.transform(input).await
     }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
 }
 
-/// Creates a new conversion middleware from two existing transforms
+/// Creates a new conversion middleware from two existing transforms; nesting conversions costs
+/// an erased call per level, so longer pipelines are better built with `pipe!` or `Pied::then`
 pub fn convert<T, T2, A, B, C>(
     t: impl Transform<T, A, B>,
     t2: impl Transform<T2, B, C>,
@@ -98,237 +497,309 @@ where
     }
 }
 
-/// Pied constructs the way we pipe between lots of functions via middleware
-pub struct Pied<T, Args, I, O> {
-    middleware: Arc<dyn Middleware<I, O>>,
-    _phantom: PhantomData<T>,
-    _phantom2: PhantomData<Args>,
+/// Adapts a transform into a middleware so it can be erased behind `dyn Middleware`
+#[cfg(feature = "std")]
+pub(crate) struct TransformMiddleware<Args, I, O> {
+    t: Arc<dyn DynTransform<Args, I, O>>,
 }
 
-/// Implements the middleware trait for the main Pied structure
-#[async_trait]
-impl<T, Args, I, O> Middleware<I, O> for Pied<T, Args, I, O>
+#[cfg(feature = "std")]
+impl<Args, I, O> Middleware<I, O> for TransformMiddleware<Args, I, O>
 where
-    T: Send + Sync + 'static,
     Args: Send + Sync + 'static,
     I: Send + Sync + 'static,
     O: Send + Sync + 'static,
 {
     async fn call(&self, input: I) -> O {
-        self.middleware.call(input).await
+        self.t.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.t.stages()
     }
 }
 
-#[async_trait]
-impl<T, Args, I, O> Transform<(I, O), I, O> for Pied<T, Args, I, O>
+/// Erases the transform behind a middleware trait object
+#[cfg(feature = "std")]
+pub(crate) fn into_middleware<Args, I, O>(
+    t: impl Transform<Args, I, O>,
+) -> Arc<dyn DynMiddleware<I, O>>
 where
-    T: Send + Sync + 'static,
     Args: Send + Sync + 'static,
     I: Send + Sync + 'static,
     O: Send + Sync + 'static,
 {
-    async fn transform(&self, input: I) -> O {
-        self.middleware.call(input).await
-    }
+    Arc::new(TransformMiddleware { t: Arc::new(t) })
 }
 
-/// Common pipe trait used to create implementations for each tuple
-pub trait Piper<T, Args, I, O> {
-    fn pipe(self) -> Pied<T, Args, I, O>;
-}
+/// Erased pipeline from `I` to `O`, convenient to store in registries and app state
+pub type BoxMiddleware<I, O> = Arc<dyn DynMiddleware<I, O>>;
 
-/// Helper utility to execute the .pipe on a Pipe implementation and returns a middleware
-pub fn pipe<T, Args, I, O>(f: impl Piper<T, Args, I, O>) -> Pied<T, Args, I, O>
+/// Value of any type flowing through a type-erased pipeline
+pub type AnyValue = Box<dyn core::any::Any + Send + Sync>;
+
+/// Implements the transform trait on an erased pipeline so it can be piped again
+impl<I, O> Transform<(I, O), I, O> for BoxMiddleware<I, O>
 where
-    T: Send + Sync + 'static,
-    Args: Send + Sync + 'static,
     I: Send + Sync + 'static,
     O: Send + Sync + 'static,
 {
-    f.pipe()
-}
+    fn transform(&self, input: I) -> impl Future<Output = O> + Send {
+        self.call_dyn(input)
+    }
 
-// Pipe middleware for source -> transform from (A, B)
-impl<T, O, A, B> Piper<(T, O), (A, B), (), O> for (A, B)
-where
-    A: Transform<(), (), T>,
-    B: Transform<(T, O), T, O>,
-    T: Send + Sync + 'static,
-    O: Send + Sync + 'static,
-{
-    fn pipe(self) -> Pied<(T, O), (A, B), (), O> {
-        let args = self;
-        Pied {
-            middleware: Arc::new(convert(args.0, args.1)),
-            _phantom: PhantomData::default(),
-            _phantom2: PhantomData::default(),
-        }
+    fn stages(&self) -> Vec<StageInfo> {
+        // deref explicitly, as the Arc itself is a transform and would recurse into this method
+        (**self).stages_dyn()
     }
 }
 
-// Pipe middleware for transform -> transform from (A, B)
-impl<T, T2, O, A, B> Piper<(T, T2, O), (A, B), T, O> for (A, B)
+/// Pied constructs the way we pipe between lots of functions via middleware
+pub struct Pied<T, Args, I, O> {
+    middleware: Arc<dyn DynMiddleware<I, O>>,
+    chain: Option<Arc<FlatChain<I, O>>>,
+    #[cfg(feature = "std")]
+    profiler: Option<Arc<Profiler>>,
+    _phantom: PhantomData<T>,
+    _phantom2: PhantomData<Args>,
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
 where
-    A: Transform<(T, T2), T, T2>,
-    B: Transform<(T2, O), T2, O>,
-    T: Send + Sync + 'static,
-    T2: Send + Sync + 'static,
+    I: Send + Sync + 'static,
     O: Send + Sync + 'static,
 {
-    fn pipe(self) -> Pied<(T, T2, O), (A, B), T, O> {
-        let args = self;
+    /// Wraps an existing middleware as a pipeline
+    pub(crate) fn from_middleware(middleware: impl Middleware<I, O>) -> Self {
         Pied {
-            middleware: Arc::new(convert(args.0, args.1)),
-            _phantom: PhantomData::default(),
-            _phantom2: PhantomData::default(),
+            middleware: Arc::new(middleware),
+            chain: None,
+            #[cfg(feature = "std")]
+            profiler: None,
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
         }
     }
-}
 
-// Pipe middleware for source -> transform -> transform for (A, B, C)
-impl<T, T2, O, A, B, C> Piper<(T, T2, O), (A, B, C), (), O> for (A, B, C)
-where
-    A: Transform<(), (), T>,
-    B: Transform<(T, T2), T, T2>,
-    C: Transform<(T2, O), T2, O>,
-    T: Send + Sync + 'static,
-    T2: Send + Sync + 'static,
-    O: Send + Sync + 'static,
-{
-    fn pipe(self) -> Pied<(T, T2, O), (A, B, C), (), O> {
-        let args = self;
+    /// Wraps the pipeline in a middleware built from it (e.g. a retry or a timeout), keeping the
+    /// profiler so the stages inside are still reported by `timings`
+    pub(crate) fn rebuild<T2, Args2, I2, O2, M>(
+        self,
+        wrap: impl FnOnce(Self) -> M,
+    ) -> Pied<T2, Args2, I2, O2>
+    where
+        M: Middleware<I2, O2>,
+        I2: Send + Sync + 'static,
+        O2: Send + Sync + 'static,
+    {
+        #[cfg(feature = "std")]
+        let profiler = self.profiler.clone();
         Pied {
-            middleware: Arc::new(convert(convert(args.0, args.1), args.2)),
-            _phantom: PhantomData::default(),
-            _phantom2: PhantomData::default(),
+            #[cfg(feature = "std")]
+            profiler,
+            ..Pied::from_middleware(wrap(self))
         }
     }
+
+    /// Describes the stages making up the pipeline, in order
+    pub fn stages(&self) -> Vec<StageInfo> {
+        self.middleware.stages()
+    }
+
+    /// Erases the stage types of the pipeline, keeping only its input and output
+    pub fn boxed(self) -> BoxMiddleware<I, O> {
+        self.middleware
+    }
 }
 
-// Pipe middleware for transform -> transform -> transform for (A, B, C)
-impl<T, T2, T3, O, A, B, C> Piper<(T, T2, T3, O), (A, B, C), T, O> for (A, B, C)
-where
-    A: Transform<(T, T2), T, T2>,
-    B: Transform<(T2, T3), T2, T3>,
-    C: Transform<(T3, O), T3, O>,
-    T: Send + Sync + 'static,
-    T2: Send + Sync + 'static,
-    T3: Send + Sync + 'static,
-    O: Send + Sync + 'static,
-{
-    fn pipe(self) -> Pied<(T, T2, T3, O), (A, B, C), T, O> {
-        let args = self;
+impl<T, Args, I, O> Clone for Pied<T, Args, I, O> {
+    fn clone(&self) -> Self {
         Pied {
-            middleware: Arc::new(convert(convert(args.0, args.1), args.2)),
-            _phantom: PhantomData::default(),
-            _phantom2: PhantomData::default(),
+            middleware: self.middleware.clone(),
+            chain: self.chain.clone(),
+            #[cfg(feature = "std")]
+            profiler: self.profiler.clone(),
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
         }
     }
 }
 
-// Pipe middleware for source -> transform -> transform -> transform for (A, B, C, D)
-impl<T, T2, T3, O, A, B, C, D> Piper<(T, T2, T3, O), (A, B, C, D), (), O> for (A, B, C, D)
+/// Implements the middleware trait for the main Pied structure
+impl<T, Args, I, O> Middleware<I, O> for Pied<T, Args, I, O>
 where
-    A: Transform<(), (), T>,
-    B: Transform<(T, T2), T, T2>,
-    C: Transform<(T2, T3), T2, T3>,
-    D: Transform<(T3, O), T3, O>,
     T: Send + Sync + 'static,
-    T2: Send + Sync + 'static,
-    T3: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
     O: Send + Sync + 'static,
 {
-    fn pipe(self) -> Pied<(T, T2, T3, O), (A, B, C, D), (), O> {
-        let args = self;
-        Pied {
-            middleware: Arc::new(convert(convert(convert(args.0, args.1), args.2), args.3)),
-            _phantom: PhantomData::default(),
-            _phantom2: PhantomData::default(),
-        }
+    async fn call(&self, input: I) -> O {
+        self.middleware.call(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.middleware.stages()
     }
 }
 
-// Pipe middleware for transform -> transform -> transform -> transform for (A, B, C, D)
-impl<T, T2, T3, T4, O, A, B, C, D> Piper<(T, T2, T3, T4, O), (A, B, C, D), T, O> for (A, B, C, D)
+impl<T, Args, I, O> Transform<(I, O), I, O> for Pied<T, Args, I, O>
 where
-    A: Transform<(T, T2), T, T2>,
-    B: Transform<(T2, T3), T2, T3>,
-    C: Transform<(T3, T4), T3, T4>,
-    D: Transform<(T4, O), T4, O>,
     T: Send + Sync + 'static,
-    T2: Send + Sync + 'static,
-    T3: Send + Sync + 'static,
-    T4: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
     O: Send + Sync + 'static,
 {
-    fn pipe(self) -> Pied<(T, T2, T3, T4, O), (A, B, C, D), T, O> {
-        let args = self;
-        Pied {
-            middleware: Arc::new(convert(convert(convert(args.0, args.1), args.2), args.3)),
-            _phantom: PhantomData::default(),
-            _phantom2: PhantomData::default(),
-        }
+    async fn transform(&self, input: I) -> O {
+        self.middleware.call(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.middleware.stages()
     }
 }
 
-// Pipe middleware for source -> transform -> transform -> transform -> transform for (A, B, C, D, E)
-impl<T, T2, T3, T4, O, A, B, C, D, E> Piper<(T, T2, T3, T4, O), (A, B, C, D, E), (), O>
-    for (A, B, C, D, E)
+/// Common pipe trait used to create implementations for each tuple
+pub trait Piper<T, Args, I, O> {
+    fn pipe(self) -> Pied<T, Args, I, O>;
+
+    /// Pipes the stages while recording the duration of every call of each stage, see `timings`
+    #[cfg(feature = "std")]
+    fn profile(self) -> Pied<T, Args, I, O>;
+}
+
+/// Helper utility to execute the .pipe on a Pipe implementation and returns a middleware
+pub fn pipe<T, Args, I, O>(f: impl Piper<T, Args, I, O>) -> Pied<T, Args, I, O>
 where
-    A: Transform<(), (), T>,
-    B: Transform<(T, T2), T, T2>,
-    C: Transform<(T2, T3), T2, T3>,
-    D: Transform<(T3, T4), T3, T4>,
-    E: Transform<(T4, O), T4, O>,
     T: Send + Sync + 'static,
-    T2: Send + Sync + 'static,
-    T3: Send + Sync + 'static,
-    T4: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
     O: Send + Sync + 'static,
 {
-    fn pipe(self) -> Pied<(T, T2, T3, T4, O), (A, B, C, D, E), (), O> {
-        let args = self;
-        Pied {
-            middleware: Arc::new(convert(
-                convert(convert(convert(args.0, args.1), args.2), args.3),
-                args.4,
-            )),
-            _phantom: PhantomData::default(),
-            _phantom2: PhantomData::default(),
-        }
-    }
+    f.pipe()
 }
 
-// Pipe middleware for transform -> transform -> transform -> transform -> transform for (A, B, C, D, E)
-impl<T, T2, T3, T4, T5, O, A, B, C, D, E> Piper<(T, T2, T3, T4, T5, O), (A, B, C, D, E), T, O>
-    for (A, B, C, D, E)
+/// Lifts a single stage into a pipeline, so further stages can be appended with `then`
+pub fn pipe_fn<S, Args, I, O>(stage: S) -> Pied<(Args, I, O), (S,), I, O>
 where
-    A: Transform<(T, T2), T, T2>,
-    B: Transform<(T2, T3), T2, T3>,
-    C: Transform<(T3, T4), T3, T4>,
-    D: Transform<(T4, T5), T4, T5>,
-    E: Transform<(T5, O), T5, O>,
-    T: Send + Sync + 'static,
-    T2: Send + Sync + 'static,
-    T3: Send + Sync + 'static,
-    T4: Send + Sync + 'static,
-    T5: Send + Sync + 'static,
+    S: Transform<Args, I, O>,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
     O: Send + Sync + 'static,
 {
-    fn pipe(self) -> Pied<(T, T2, T3, T4, T5, O), (A, B, C, D, E), T, O> {
-        let args = self;
-        Pied {
-            middleware: Arc::new(convert(
-                convert(convert(convert(args.0, args.1), args.2), args.3),
-                args.4,
-            )),
-            _phantom: PhantomData::default(),
-            _phantom2: PhantomData::default(),
+    Pied::from_chain(FlatChain::new(stage))
+}
+
+/// Pipes any number of stages together into a flat pipeline, by appending each stage with `then`
+#[macro_export]
+macro_rules! pipe {
+    ($first:expr, $second:expr $(, $rest:expr)* $(,)?) => {
+        $crate::pipe_fn($first).then($second)$(.then($rest))*
+    };
+}
+
+/// Generates the `Piper` implementation for a tuple of stages; the first stage may be a source
+/// (a function with no input), a transform, or a function of several arguments taking a tuple.
+/// The stages are composed by value with `chain!`, so a call costs one dynamic dispatch for the
+/// whole pipeline rather than one per nested `convert`.
+macro_rules! impl_piper {
+    ($O:ident; $i0:tt $S0:ident: $T0:ident => $O0:ident $(, $i:tt $S:ident: $In:ident => $Out:ident)+) => {
+        impl<Args0, $T0, $O0, $($Out,)+ $S0, $($S),+>
+            Piper<(Args0, $T0, $O0, $($Out),+), ($S0, $($S),+), $T0, $O> for ($S0, $($S),+)
+        where
+            $S0: Transform<Args0, $T0, $O0>,
+            $($S: Transform<($In, $Out), $In, $Out>,)+
+            Args0: Send + Sync + 'static,
+            $T0: Send + Sync + 'static,
+            $O0: Send + Sync + 'static,
+            $($Out: Send + Sync + 'static,)+
+        {
+            fn pipe(self) -> Pied<(Args0, $T0, $O0, $($Out),+), ($S0, $($S),+), $T0, $O> {
+                let args = self;
+                Pied::from_middleware(chain!(args.$i0, $(args.$i),+))
+            }
+
+            #[cfg(feature = "std")]
+            fn profile(self) -> Pied<(Args0, $T0, $O0, $($Out),+), ($S0, $($S),+), $T0, $O> {
+                let args = self;
+                let profiler = Arc::new(Profiler::default());
+                let middleware = chain!(
+                    Timed::new(args.$i0, &profiler),
+                    $(Timed::new(args.$i, &profiler)),+
+                );
+                Pied {
+                    profiler: Some(profiler),
+                    ..Pied::from_middleware(middleware)
+                }
+            }
         }
-    }
+    };
 }
 
+impl_piper!(O; 0 A: T => T2, 1 B: T2 => O);
+impl_piper!(O; 0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => O);
+impl_piper!(O; 0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => T4, 3 D: T4 => O);
+impl_piper!(O; 0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => T4, 3 D: T4 => T5, 4 E: T5 => O);
+impl_piper!(
+    O;
+    0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => T4, 3 D: T4 => T5, 4 E: T5 => T6, 5 F: T6 => O
+);
+impl_piper!(
+    O;
+    0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => T4, 3 D: T4 => T5, 4 E: T5 => T6, 5 F: T6 => T7,
+    6 G: T7 => O
+);
+impl_piper!(
+    O;
+    0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => T4, 3 D: T4 => T5, 4 E: T5 => T6, 5 F: T6 => T7,
+    6 G: T7 => T8, 7 H: T8 => O
+);
+impl_piper!(
+    O;
+    0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => T4, 3 D: T4 => T5, 4 E: T5 => T6, 5 F: T6 => T7,
+    6 G: T7 => T8, 7 H: T8 => T9, 8 J: T9 => O
+);
+impl_piper!(
+    O;
+    0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => T4, 3 D: T4 => T5, 4 E: T5 => T6, 5 F: T6 => T7,
+    6 G: T7 => T8, 7 H: T8 => T9, 8 J: T9 => T10, 9 K: T10 => O
+);
+impl_piper!(
+    O;
+    0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => T4, 3 D: T4 => T5, 4 E: T5 => T6, 5 F: T6 => T7,
+    6 G: T7 => T8, 7 H: T8 => T9, 8 J: T9 => T10, 9 K: T10 => T11, 10 L: T11 => O
+);
+impl_piper!(
+    O;
+    0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => T4, 3 D: T4 => T5, 4 E: T5 => T6, 5 F: T6 => T7,
+    6 G: T7 => T8, 7 H: T8 => T9, 8 J: T9 => T10, 9 K: T10 => T11, 10 L: T11 => T12, 11 M: T12 => O
+);
+impl_piper!(
+    O;
+    0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => T4, 3 D: T4 => T5, 4 E: T5 => T6, 5 F: T6 => T7,
+    6 G: T7 => T8, 7 H: T8 => T9, 8 J: T9 => T10, 9 K: T10 => T11, 10 L: T11 => T12,
+    11 M: T12 => T13, 12 N: T13 => O
+);
+impl_piper!(
+    O;
+    0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => T4, 3 D: T4 => T5, 4 E: T5 => T6, 5 F: T6 => T7,
+    6 G: T7 => T8, 7 H: T8 => T9, 8 J: T9 => T10, 9 K: T10 => T11, 10 L: T11 => T12,
+    11 M: T12 => T13, 12 N: T13 => T14, 13 P: T14 => O
+);
+impl_piper!(
+    O;
+    0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => T4, 3 D: T4 => T5, 4 E: T5 => T6, 5 F: T6 => T7,
+    6 G: T7 => T8, 7 H: T8 => T9, 8 J: T9 => T10, 9 K: T10 => T11, 10 L: T11 => T12,
+    11 M: T12 => T13, 12 N: T13 => T14, 13 P: T14 => T15, 14 Q: T15 => O
+);
+impl_piper!(
+    O;
+    0 A: T => T2, 1 B: T2 => T3, 2 C: T3 => T4, 3 D: T4 => T5, 4 E: T5 => T6, 5 F: T6 => T7,
+    6 G: T7 => T8, 7 H: T8 => T9, 8 J: T9 => T10, 9 K: T10 => T11, 10 L: T11 => T12,
+    11 M: T12 => T13, 12 N: T13 => T14, 13 P: T14 => T15, 14 Q: T15 => T16, 15 R: T16 => O
+);
+
 #[cfg(test)]
+#[cfg(feature = "std")]
 mod tests {
     use super::*;
 
@@ -391,21 +862,116 @@ mod tests {
         assert_eq!(String::from("3072"), m.call(3).await);
     }
 
-    // lack of support for variadics at the moment for the initial source
+    async fn incr(i: i32) -> i32 {
+        i + 1
+    }
+
+    #[async_std::test]
+    async fn test_piper_long_tuples() {
+        let m = (producer, incr, incr, incr, incr, incr).pipe();
+        assert_eq!(8, m.call(()).await);
+
+        let m = (
+            incr, incr, incr, incr, incr, incr, incr, incr, incr, incr, incr, incr, incr, incr,
+            incr, stringer,
+        )
+            .pipe();
+        assert_eq!(String::from("15"), m.call(0).await);
+
+        let m = pipe((
+            producer, incr, incr, incr, incr, incr, incr, incr, incr, incr, incr, incr, incr,
+            multipler, incr, stringer,
+        ));
+        assert_eq!(String::from("481"), m.call(()).await);
+    }
+
+    #[async_std::test]
+    async fn test_pipe_macro() {
+        let m = pipe!(producer, multipler, stringer);
+        assert_eq!(String::from("96"), m.call(()).await);
+
+        let m = pipe!(multipler, multipler, multipler, gen,);
+        assert_eq!(String::from("foo 32768"), m.call(1).await);
+
+        let m = pipe!(
+            producer, incr, incr, incr, incr, incr, incr, incr, incr, incr, incr, incr, incr, incr,
+            incr, incr, incr, incr, incr, incr, incr, stringer
+        );
+        assert_eq!(String::from("23"), m.call(()).await);
+
+        // mix with tuple pipes
+        let m = pipe!((producer, multipler).pipe(), multipler, stringer, logger);
+        m.call(()).await;
+    }
+
     // downstream functions will only be able to accept a single value
-    // as a future's output can only be a single return value
-    // input should however be flexible to be variadic here though
+    // as a future's output can only be a single return value, but the
+    // first stage may accept several arguments passed in as a tuple
     async fn multi(a: i32, b: i32) -> i32 {
         a + b
     }
 
-    #[cfg(todo)]
+    async fn weighted(a: i32, b: i32, weight: u8) -> i32 {
+        (a + b) * i32::from(weight)
+    }
+
     #[async_std::test]
     async fn test_piper_multiple_tuple_inputs() {
         let m = (multi, multipler, stringer).pipe();
-        assert_eq!(String::from("1024"), m.call(1).await);
-        assert_eq!(String::from("2048"), m.call(2).await);
-        assert_eq!(String::from("3072"), m.call(3).await);
+        assert_eq!(String::from("64"), m.call((1, 1)).await);
+        assert_eq!(String::from("96"), m.call((2, 1)).await);
+        assert_eq!(String::from("224"), m.call((3, 4)).await);
+
+        let m = pipe!(weighted, stringer);
+        assert_eq!(String::from("20"), m.call((3, 2, 4)).await);
+    }
+
+    async fn not_sync(i: i32) -> i32 {
+        // a Cell held across an await point makes the future Send but not Sync
+        let cell = std::cell::Cell::new(i);
+        producer().await;
+        cell.get() * 2
+    }
+
+    #[async_std::test]
+    async fn test_non_sync_futures() {
+        let m = (producer, not_sync, multipler, stringer).pipe();
+        assert_eq!(String::from("192"), m.call(()).await);
+    }
+
+    #[async_std::test]
+    async fn test_pipe_fn_identity() {
+        let m = pipe_fn(multipler);
+        assert_eq!(64, m.call(2).await);
+
+        for double in [false, true] {
+            let mut m = pipe_fn(identity::<i32>());
+            if double {
+                m = m.then(multipler);
+            }
+            let m = m.then(stringer);
+            assert_eq!(double, m.call(1).await == "32");
+        }
+    }
+
+    #[async_std::test]
+    async fn test_clone_and_boxed() {
+        let m = (multipler, stringer).pipe();
+        let cloned = m.clone();
+        assert_eq!(m.call(2).await, cloned.call(2).await);
+
+        let mut registry: std::collections::HashMap<&str, BoxMiddleware<i32, String>> =
+            std::collections::HashMap::new();
+        registry.insert("stringify", m.boxed());
+        registry.insert("gen", (multipler, gen).pipe().boxed());
+        assert_eq!(String::from("foo 32"), registry["gen"].call(1).await);
+
+        let m = (
+            registry["stringify"].clone(),
+            |s: String| async move { s.len() },
+        )
+            .pipe();
+        assert_eq!(3, m.call(10).await);
     }
 
     #[test]
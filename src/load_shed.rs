@@ -0,0 +1,139 @@
+//! Rejecting calls up front while a stage is overloaded.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::{util::InFlight, DynTransform, Middleware, Overloaded, Pied, StageInfo, Transform};
+
+type DepthProbe = Box<dyn Fn() -> usize + Send + Sync>;
+
+/// Rejects calls immediately with `Overloaded` while too many are in flight or a watched queue is
+/// too deep, instead of letting latency grow without bound
+pub struct LoadShed<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    in_flight: AtomicUsize,
+    max_in_flight: usize,
+    queue: Option<(DepthProbe, usize)>,
+}
+
+impl<Args, I, O> LoadShed<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Creates a new load shedder letting at most `max_in_flight` calls run the stage at once
+    pub fn new(stage: impl Transform<Args, I, O>, max_in_flight: usize) -> Self {
+        LoadShed {
+            stage: Arc::new(stage),
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: max_in_flight.max(1),
+            queue: None,
+        }
+    }
+
+    /// Also rejects calls while the depth reported by the probe (e.g. the length of a channel
+    /// feeding this stage) exceeds `max_depth`
+    pub fn max_queue_depth(
+        mut self,
+        max_depth: usize,
+        depth: impl Fn() -> usize + Send + Sync + 'static,
+    ) -> Self {
+        self.queue = Some((Box::new(depth), max_depth));
+        self
+    }
+
+    /// Returns the number of calls currently running the stage
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+}
+
+/// Implements the transform trait on the load shedder (for downstream)
+impl<Args, I, O> Transform<(I, Result<O, Overloaded>), I, Result<O, Overloaded>>
+    for LoadShed<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> Result<O, Overloaded> {
+        if let Some((depth, max_depth)) = &self.queue {
+            if depth() > *max_depth {
+                return Err(Overloaded);
+            }
+        }
+        if self.in_flight.fetch_add(1, Ordering::SeqCst) >= self.max_in_flight {
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            return Err(Overloaded);
+        }
+        let _in_flight = InFlight(&self.in_flight);
+        Ok(self.stage.transform(input).await)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the load shedder
+impl<Args, I, O> Middleware<I, Result<O, Overloaded>> for LoadShed<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> Result<O, Overloaded> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Rejects calls with `Overloaded` while `max_in_flight` calls are already running the
+    /// pipeline
+    pub fn load_shed(self, max_in_flight: usize) -> Pied<T, Args, I, Result<O, Overloaded>> {
+        self.rebuild(|pied| LoadShed::new(pied, max_in_flight))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use async_std::task::sleep;
+    use futures::future::join_all;
+    use std::time::Duration;
+
+    async fn slow(i: i32) -> i32 {
+        sleep(Duration::from_millis(20)).await;
+        i
+    }
+
+    #[async_std::test]
+    async fn test_load_shed() {
+        let m = (slow, |i: i32| async move { i * 2 }).pipe().load_shed(2);
+        let outputs = join_all((0..3).map(|i| m.call(i))).await;
+        assert_eq!(vec![Ok(0), Ok(2), Err(Overloaded)], outputs);
+        assert_eq!(Ok(6), m.call(3).await);
+
+        let depth = Arc::new(AtomicUsize::new(0));
+        let probe = depth.clone();
+        let m = LoadShed::new(slow, 8).max_queue_depth(4, move || probe.load(Ordering::SeqCst));
+        assert_eq!(Ok(1), m.call(1).await);
+        depth.store(5, Ordering::SeqCst);
+        assert_eq!(Err(Overloaded), m.call(2).await);
+        assert_eq!(0, m.in_flight());
+    }
+}
@@ -0,0 +1,385 @@
+//! Single-threaded pipelines for `!Send` stages, such as those holding JS values in browser
+//! workers with the `wasm` feature, where the timeout and rate limit below run on browser timers.
+//! Streams of local items are paced with `ThrottleExt` as is.
+
+use futures::future::LocalBoxFuture;
+use std::{future::Future, marker::PhantomData, rc::Rc, time::Duration};
+
+use crate::{rt, Elapsed, SharedLimiter, TokenBucket};
+
+/// Transform whose future does not need to be sent across threads (e.g. wasm or `Rc` state)
+pub trait LocalTransform<Args, T, O>: 'static {
+    /// Asynchronously execute this handler on the current thread
+    fn transform_local(&self, input: T) -> LocalBoxFuture<'_, O>;
+}
+
+/// Local transform implementation for an async function that produces an output
+impl<Func, Fut, O> LocalTransform<(), (), O> for Func
+where
+    Func: Fn() -> Fut + 'static,
+    Fut: Future<Output = O> + 'static,
+    O: 'static,
+{
+    fn transform_local(&self, _input: ()) -> LocalBoxFuture<'_, O> {
+        Box::pin((self)())
+    }
+}
+
+/// Local transform implementation for an async function that accepts an input
+impl<Func, Fut, T, O> LocalTransform<(T, O), T, O> for Func
+where
+    Func: Fn(T) -> Fut + 'static,
+    Fut: Future<Output = O> + 'static,
+    T: 'static,
+    O: 'static,
+{
+    fn transform_local(&self, input: T) -> LocalBoxFuture<'_, O> {
+        Box::pin((self)(input))
+    }
+}
+
+/// Middleware whose future does not need to be sent across threads
+pub trait LocalMiddleware<I, O>: 'static {
+    fn call_local(&self, input: I) -> LocalBoxFuture<'_, O>;
+}
+
+/// Encapsulates the conversion between two local transforms
+struct LocalConvert<T, T2, A, B, C> {
+    t: Rc<dyn LocalTransform<T, A, B>>,
+    t2: Rc<dyn LocalTransform<T2, B, C>>,
+}
+
+/// Creates a new local conversion from two existing local transforms
+fn local_convert<T, T2, A, B, C>(
+    t: impl LocalTransform<T, A, B>,
+    t2: impl LocalTransform<T2, B, C>,
+) -> LocalConvert<T, T2, A, B, C> {
+    LocalConvert {
+        t: Rc::new(t),
+        t2: Rc::new(t2),
+    }
+}
+
+/// Implements the local middleware trait on the local conversion
+impl<T, T2, A, B, C> LocalMiddleware<A, C> for LocalConvert<T, T2, A, B, C>
+where
+    T: 'static,
+    T2: 'static,
+    A: 'static,
+    B: 'static,
+    C: 'static,
+{
+    fn call_local(&self, input: A) -> LocalBoxFuture<'_, C> {
+        Box::pin(async move {
+            let input = self.t.transform_local(input).await;
+            self.t2.transform_local(input).await
+        })
+    }
+}
+
+/// Implements the local transform trait on the local conversion (for downstream)
+impl<T, T2, A, B, C> LocalTransform<(A, C), A, C> for LocalConvert<T, T2, A, B, C>
+where
+    T: 'static,
+    T2: 'static,
+    A: 'static,
+    B: 'static,
+    C: 'static,
+{
+    fn transform_local(&self, input: A) -> LocalBoxFuture<'_, C> {
+        self.call_local(input)
+    }
+}
+
+/// Single-threaded pipeline holding its stages behind `Rc`
+pub struct LocalPied<T, Args, I, O> {
+    middleware: Rc<dyn LocalMiddleware<I, O>>,
+    _phantom: PhantomData<T>,
+    _phantom2: PhantomData<Args>,
+}
+
+impl<T, Args, I, O> LocalPied<T, Args, I, O> {
+    /// Wraps an existing local middleware as a pipeline
+    fn from_middleware(middleware: impl LocalMiddleware<I, O>) -> Self {
+        LocalPied {
+            middleware: Rc::new(middleware),
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
+        }
+    }
+}
+
+impl<T, Args, I, O> Clone for LocalPied<T, Args, I, O> {
+    fn clone(&self) -> Self {
+        LocalPied {
+            middleware: self.middleware.clone(),
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
+        }
+    }
+}
+
+/// Implements the local middleware trait for the local pipeline
+impl<T, Args, I, O> LocalMiddleware<I, O> for LocalPied<T, Args, I, O>
+where
+    T: 'static,
+    Args: 'static,
+    I: 'static,
+    O: 'static,
+{
+    fn call_local(&self, input: I) -> LocalBoxFuture<'_, O> {
+        self.middleware.call_local(input)
+    }
+}
+
+/// Implements the local transform trait for the local pipeline (for downstream)
+impl<T, Args, I, O> LocalTransform<(I, O), I, O> for LocalPied<T, Args, I, O>
+where
+    T: 'static,
+    Args: 'static,
+    I: 'static,
+    O: 'static,
+{
+    fn transform_local(&self, input: I) -> LocalBoxFuture<'_, O> {
+        self.middleware.call_local(input)
+    }
+}
+
+/// Bounds how long a local stage may run before failing with `Elapsed`
+pub struct LocalTimeout<Args, I, O> {
+    stage: Rc<dyn LocalTransform<Args, I, O>>,
+    duration: Duration,
+}
+
+/// Creates a new timeout around the local stage
+pub fn local_timeout<Args, I, O>(
+    stage: impl LocalTransform<Args, I, O>,
+    duration: Duration,
+) -> LocalTimeout<Args, I, O> {
+    LocalTimeout {
+        stage: Rc::new(stage),
+        duration,
+    }
+}
+
+/// Implements the local middleware trait on the local timeout
+impl<Args, I, O> LocalMiddleware<I, Result<O, Elapsed>> for LocalTimeout<Args, I, O>
+where
+    Args: 'static,
+    I: 'static,
+    O: 'static,
+{
+    fn call_local(&self, input: I) -> LocalBoxFuture<'_, Result<O, Elapsed>> {
+        Box::pin(rt::timeout(
+            self.duration,
+            self.stage.transform_local(input),
+        ))
+    }
+}
+
+/// Implements the local transform trait on the local timeout (for downstream)
+impl<Args, I, O> LocalTransform<(I, Result<O, Elapsed>), I, Result<O, Elapsed>>
+    for LocalTimeout<Args, I, O>
+where
+    Args: 'static,
+    I: 'static,
+    O: 'static,
+{
+    fn transform_local(&self, input: I) -> LocalBoxFuture<'_, Result<O, Elapsed>> {
+        self.call_local(input)
+    }
+}
+
+/// Bounds how many calls per period pass through a local stage, waiting when over budget
+pub struct LocalRateLimit<Args, I, O> {
+    stage: Rc<dyn LocalTransform<Args, I, O>>,
+    limiter: SharedLimiter,
+}
+
+/// Creates a new rate limit around the local stage drawing from the quota of a shared limiter
+pub fn local_rate_limit<Args, I, O>(
+    stage: impl LocalTransform<Args, I, O>,
+    limiter: &SharedLimiter,
+) -> LocalRateLimit<Args, I, O> {
+    LocalRateLimit {
+        stage: Rc::new(stage),
+        limiter: limiter.clone(),
+    }
+}
+
+/// Implements the local middleware trait on the local rate limit
+impl<Args, I, O> LocalMiddleware<I, O> for LocalRateLimit<Args, I, O>
+where
+    Args: 'static,
+    I: 'static,
+    O: 'static,
+{
+    fn call_local(&self, input: I) -> LocalBoxFuture<'_, O> {
+        Box::pin(async move {
+            self.limiter.acquire().await;
+            self.stage.transform_local(input).await
+        })
+    }
+}
+
+/// Implements the local transform trait on the local rate limit (for downstream)
+impl<Args, I, O> LocalTransform<(I, O), I, O> for LocalRateLimit<Args, I, O>
+where
+    Args: 'static,
+    I: 'static,
+    O: 'static,
+{
+    fn transform_local(&self, input: I) -> LocalBoxFuture<'_, O> {
+        self.call_local(input)
+    }
+}
+
+impl<T, Args, I, O> LocalPied<T, Args, I, O>
+where
+    T: 'static,
+    Args: 'static,
+    I: 'static,
+    O: 'static,
+{
+    /// Bounds how long the whole pipeline may run before failing with `Elapsed`
+    pub fn timeout(self, duration: Duration) -> LocalPied<T, Args, I, Result<O, Elapsed>> {
+        LocalPied::from_middleware(local_timeout(self, duration))
+    }
+
+    /// Bounds how many calls per period pass through the whole pipeline
+    pub fn rate_limit(self, num: u32, per: Duration) -> LocalPied<T, Args, I, O> {
+        self.rate_limit_shared(&SharedLimiter::new(TokenBucket::new(num, per)))
+    }
+
+    /// Bounds the calls through the whole pipeline by the quota of a shared limiter
+    pub fn rate_limit_shared(self, limiter: &SharedLimiter) -> LocalPied<T, Args, I, O> {
+        LocalPied::from_middleware(local_rate_limit(self, limiter))
+    }
+}
+
+/// Common local pipe trait used to create implementations for each tuple
+pub trait LocalPiper<T, Args, I, O> {
+    fn pipe_local(self) -> LocalPied<T, Args, I, O>;
+}
+
+/// Helper utility to execute the .pipe_local on a local pipe implementation
+pub fn pipe_local<T, Args, I, O>(f: impl LocalPiper<T, Args, I, O>) -> LocalPied<T, Args, I, O> {
+    f.pipe_local()
+}
+
+/// Folds the stages into nested local conversions
+macro_rules! local_pipe {
+    ($first:expr, $second:expr $(, $rest:expr)*) => {
+        local_pipe!(@acc local_convert($first, $second) $(, $rest)*)
+    };
+    (@acc $acc:expr, $next:expr $(, $rest:expr)*) => {
+        local_pipe!(@acc local_convert($acc, $next) $(, $rest)*)
+    };
+    (@acc $acc:expr) => {
+        $acc
+    };
+}
+
+/// Generates the `LocalPiper` implementation for a tuple of stages with the given
+/// intermediate types; the first stage may be a source (input `()`) or a transform
+macro_rules! impl_local_piper {
+    ($($T:ident),*; $($i:tt $S:ident $A:ident: $In:ident => $Out:ident),+) => {
+        impl<I, O, $($T,)* $($S, $A),+> LocalPiper<($($T,)*), ($($A,)+), I, O> for ($($S,)+)
+        where
+            $($S: LocalTransform<$A, $In, $Out>,)+
+            I: 'static,
+            O: 'static,
+            $($T: 'static,)*
+            $($A: 'static,)+
+        {
+            fn pipe_local(self) -> LocalPied<($($T,)*), ($($A,)+), I, O> {
+                let stages = self;
+                LocalPied::from_middleware(local_pipe!($(stages.$i),+))
+            }
+        }
+    };
+}
+
+impl_local_piper!(T; 0 S0 A0: I => T, 1 S1 A1: T => O);
+impl_local_piper!(T, T2; 0 S0 A0: I => T, 1 S1 A1: T => T2, 2 S2 A2: T2 => O);
+impl_local_piper!(
+    T, T2, T3;
+    0 S0 A0: I => T, 1 S1 A1: T => T2, 2 S2 A2: T2 => T3, 3 S3 A3: T3 => O
+);
+impl_local_piper!(
+    T, T2, T3, T4;
+    0 S0 A0: I => T, 1 S1 A1: T => T2, 2 S2 A2: T2 => T3, 3 S3 A3: T3 => T4, 4 S4 A4: T4 => O
+);
+impl_local_piper!(
+    T, T2, T3, T4, T5;
+    0 S0 A0: I => T, 1 S1 A1: T => T2, 2 S2 A2: T2 => T3, 3 S3 A3: T3 => T4, 4 S4 A4: T4 => T5,
+    5 S5 A5: T5 => O
+);
+impl_local_piper!(
+    T, T2, T3, T4, T5, T6;
+    0 S0 A0: I => T, 1 S1 A1: T => T2, 2 S2 A2: T2 => T3, 3 S3 A3: T3 => T4, 4 S4 A4: T4 => T5,
+    5 S5 A5: T5 => T6, 6 S6 A6: T6 => O
+);
+impl_local_piper!(
+    T, T2, T3, T4, T5, T6, T7;
+    0 S0 A0: I => T, 1 S1 A1: T => T2, 2 S2 A2: T2 => T3, 3 S3 A3: T3 => T4, 4 S4 A4: T4 => T5,
+    5 S5 A5: T5 => T6, 6 S6 A6: T6 => T7, 7 S7 A7: T7 => O
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task::sleep;
+    use std::cell::RefCell;
+
+    async fn producer() -> i32 {
+        3
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_pipe_local() {
+        let total = Rc::new(RefCell::new(0));
+        let sum = total.clone();
+        let accumulate = move |i: i32| {
+            let sum = sum.clone();
+            async move {
+                *sum.borrow_mut() += i;
+                *sum.borrow()
+            }
+        };
+        let m = (producer, accumulate, stringer).pipe_local();
+        assert_eq!(String::from("3"), m.call_local(()).await);
+        assert_eq!(String::from("6"), m.call_local(()).await);
+
+        let m = pipe_local((m, |s: String| async move { s.len() }));
+        assert_eq!(1, m.call_local(()).await);
+        assert_eq!(9, *total.borrow());
+    }
+
+    #[async_std::test]
+    async fn test_local_timeout_and_rate_limit() {
+        let calls = Rc::new(RefCell::new(0));
+        let counted = calls.clone();
+        let slow = move |ms: u64| {
+            *counted.borrow_mut() += 1;
+            async move {
+                sleep(Duration::from_millis(ms)).await;
+                ms
+            }
+        };
+        let m = (slow, |ms: u64| async move { ms.to_string() })
+            .pipe_local()
+            .rate_limit(1, Duration::from_millis(30))
+            .timeout(Duration::from_millis(20));
+        assert_eq!(Ok(String::from("1")), m.call_local(1).await);
+        assert_eq!(1, *calls.borrow());
+        // waits for the quota within the timeout, so the second call never reaches the stage
+        assert_eq!(Err(Elapsed(())), m.call_local(1).await);
+        assert_eq!(1, *calls.borrow());
+        assert_eq!(Err(Elapsed(())), m.call_local(100).await);
+    }
+}
@@ -0,0 +1,226 @@
+//! Structured logging around stages.
+
+use log::{kv::Value, Level, Record};
+use std::{any::type_name, borrow::Cow, fmt, sync::Arc};
+
+use crate::{rt::Instant, DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// Key-value fields describing the input of a logged call
+pub type LogFields = Vec<(&'static str, String)>;
+
+type FieldsFn<I> = Box<dyn Fn(&I) -> LogFields + Send + Sync>;
+
+/// Logs every call of a stage as structured key-value records before and after it runs,
+/// including its duration and outcome
+pub struct Logged<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    name: Cow<'static, str>,
+    level: Level,
+    fields: FieldsFn<I>,
+    is_error: Option<fn(&O) -> bool>,
+}
+
+/// Creates a new logged stage, named after the type of the stage, adding the fields computed
+/// from each input to its records
+pub fn log_stage<Args, I, O, S>(
+    stage: S,
+    level: Level,
+    fields: impl Fn(&I) -> LogFields + Send + Sync + 'static,
+) -> Logged<Args, I, O>
+where
+    S: Transform<Args, I, O>,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    Logged {
+        stage: Arc::new(stage),
+        name: Cow::Borrowed(type_name::<S>()),
+        level,
+        fields: Box::new(fields),
+        is_error: None,
+    }
+}
+
+/// Creates a new logged stage for a fallible stage, recording whether each call failed
+pub fn try_log_stage<Args, I, O, E, S>(
+    stage: S,
+    level: Level,
+    fields: impl Fn(&I) -> LogFields + Send + Sync + 'static,
+) -> Logged<Args, I, Result<O, E>>
+where
+    S: Transform<Args, I, Result<O, E>>,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    Logged {
+        is_error: Some(Result::is_err),
+        ..log_stage(stage, level, fields)
+    }
+}
+
+impl<Args, I, O> Logged<Args, I, O> {
+    /// Sets the stage name recorded in the logs
+    pub fn named(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Sends a record with the stage name, the fields and any extra values to the installed logger
+    fn emit(&self, message: fmt::Arguments<'_>, fields: &LogFields, extra: &[(&str, Value<'_>)]) {
+        let mut values = vec![("stage", Value::from(&*self.name))];
+        values.extend(fields.iter().map(|(k, v)| (*k, Value::from(v.as_str()))));
+        values.extend_from_slice(extra);
+        log::logger().log(
+            &Record::builder()
+                .level(self.level)
+                .target(module_path!())
+                .args(message)
+                .key_values(&values)
+                .build(),
+        );
+    }
+}
+
+/// Implements the transform trait on the logged stage (for downstream)
+impl<Args, I, O> Transform<(I, O), I, O> for Logged<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        if self.level > log::max_level() {
+            return self.stage.transform(input).await;
+        }
+        let fields = (self.fields)(&input);
+        self.emit(format_args!("stage started"), &fields, &[]);
+
+        let start = Instant::now();
+        let output = self.stage.transform(input).await;
+        let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+        let mut extra = vec![("elapsed_ms", Value::from(elapsed_ms))];
+        if let Some(is_error) = self.is_error {
+            let outcome = if is_error(&output) { "error" } else { "ok" };
+            extra.push(("outcome", Value::from(outcome)));
+        }
+        self.emit(format_args!("stage finished"), &fields, &extra);
+        output
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the logged stage
+impl<Args, I, O> Middleware<I, O> for Logged<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Logs every call of the whole pipeline under the given name with the fields of its input
+    pub fn log(
+        self,
+        name: impl Into<Cow<'static, str>>,
+        level: Level,
+        fields: impl Fn(&I) -> LogFields + Send + Sync + 'static,
+    ) -> Pied<T, Args, I, O> {
+        self.rebuild(|pied| log_stage(pied, level, fields).named(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipe_fn;
+    use log::{
+        kv::{self, VisitSource},
+        Log, Metadata,
+    };
+    use std::sync::Mutex;
+
+    static LOGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct Recorder;
+
+    struct Pairs(String);
+
+    impl<'kvs> VisitSource<'kvs> for Pairs {
+        fn visit_pair(&mut self, key: kv::Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+            // the duration varies between runs, so only its presence is recorded
+            match key.as_str() {
+                "elapsed_ms" => self.0.push_str(" elapsed_ms"),
+                key => self.0.push_str(&format!(" {}={}", key, value)),
+            }
+            Ok(())
+        }
+    }
+
+    impl Log for Recorder {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            let mut line = Pairs(format!("{} {}:", record.level(), record.args()));
+            record.key_values().visit(&mut line).unwrap();
+            LOGS.lock().unwrap().push(line.0);
+        }
+
+        fn flush(&self) {}
+    }
+
+    async fn checked(i: i32) -> Result<i32, String> {
+        if i < 0 {
+            Err(String::from("negative"))
+        } else {
+            Ok(i * 2)
+        }
+    }
+
+    #[async_std::test]
+    async fn test_log_stage() {
+        log::set_logger(&Recorder).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+
+        let m = try_log_stage(checked, Level::Info, |i: &i32| {
+            vec![("input", i.to_string())]
+        })
+        .named("checked");
+        assert_eq!(Ok(4), m.call(2).await);
+        assert!(m.call(-1).await.is_err());
+
+        let m = pipe_fn(checked).log("quiet", Level::Debug, |_| Vec::new());
+        assert_eq!(Ok(2), m.call(1).await);
+
+        assert_eq!(
+            vec![
+                "INFO stage started: stage=checked input=2",
+                "INFO stage finished: stage=checked input=2 elapsed_ms outcome=ok",
+                "INFO stage started: stage=checked input=-1",
+                "INFO stage finished: stage=checked input=-1 elapsed_ms outcome=error",
+            ],
+            *LOGS.lock().unwrap()
+        );
+    }
+}
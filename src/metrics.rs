@@ -0,0 +1,241 @@
+//! Metrics hooks per stage.
+
+use std::{borrow::Cow, sync::Arc, time::Duration};
+
+use crate::{rt::Instant, DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// Callbacks invoked around every call of an observed stage.
+pub trait MetricsObserver: Send + Sync + 'static {
+    /// Called before the stage runs
+    fn on_stage_start(&self, _stage: &str) {}
+
+    /// Called after the stage completes with the time it took
+    fn on_stage_end(&self, _stage: &str, _elapsed: Duration) {}
+
+    /// Called after a fallible stage completes with an error
+    fn on_error(&self, _stage: &str) {}
+}
+
+/// Reports every call of a stage to a metrics observer
+pub struct Observed<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    name: Cow<'static, str>,
+    observer: Arc<dyn MetricsObserver>,
+    is_error: Option<fn(&O) -> bool>,
+}
+
+/// Creates a new observed stage, named after the stages it wraps
+pub fn observed<Args, I, O, S>(stage: S, observer: Arc<dyn MetricsObserver>) -> Observed<Args, I, O>
+where
+    S: Transform<Args, I, O>,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    let names: Vec<_> = stage.stages().into_iter().map(|s| s.name).collect();
+    Observed {
+        name: names.join(", ").into(),
+        stage: Arc::new(stage),
+        observer,
+        is_error: None,
+    }
+}
+
+/// Creates a new observed stage for a fallible stage, also reporting its errors
+pub fn try_observed<Args, I, O, E, S>(
+    stage: S,
+    observer: Arc<dyn MetricsObserver>,
+) -> Observed<Args, I, Result<O, E>>
+where
+    S: Transform<Args, I, Result<O, E>>,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    Observed {
+        is_error: Some(Result::is_err),
+        ..observed(stage, observer)
+    }
+}
+
+impl<Args, I, O> Observed<Args, I, O> {
+    /// Sets the name reported to the observer
+    pub fn named(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.name = name.into();
+        self
+    }
+}
+
+/// Implements the transform trait on the observed stage (for downstream)
+impl<Args, I, O> Transform<(I, O), I, O> for Observed<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        self.observer.on_stage_start(&self.name);
+        let start = Instant::now();
+        let output = self.stage.transform(input).await;
+        self.observer.on_stage_end(&self.name, start.elapsed());
+        if self.is_error.is_some_and(|is_error| is_error(&output)) {
+            self.observer.on_error(&self.name);
+        }
+        output
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the observed stage
+impl<Args, I, O> Middleware<I, O> for Observed<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Reports every call of the whole pipeline to the observer under the given name
+    pub fn observe(
+        self,
+        name: impl Into<Cow<'static, str>>,
+        observer: Arc<dyn MetricsObserver>,
+    ) -> Pied<T, Args, I, O> {
+        self.rebuild(|pied| observed(pied, observer).named(name))
+    }
+}
+
+impl<T, Args, I, O, E> Pied<T, Args, I, Result<O, E>>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    /// Reports every call of the whole fallible pipeline, including its errors, to the observer
+    pub fn try_observe(
+        self,
+        name: impl Into<Cow<'static, str>>,
+        observer: Arc<dyn MetricsObserver>,
+    ) -> Pied<T, Args, I, Result<O, E>> {
+        self.rebuild(|pied| try_observed(pied, observer).named(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Piper, TransformExt};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl MetricsObserver for Recorder {
+        fn on_stage_start(&self, stage: &str) {
+            self.events.lock().unwrap().push(format!("start {}", stage));
+        }
+
+        fn on_stage_end(&self, stage: &str, _elapsed: Duration) {
+            self.events.lock().unwrap().push(format!("end {}", stage));
+        }
+
+        fn on_error(&self, stage: &str) {
+            self.events.lock().unwrap().push(format!("error {}", stage));
+        }
+    }
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn checked(i: i32) -> Result<i32, String> {
+        if i < 0 {
+            Err(String::from("negative"))
+        } else {
+            Ok(i)
+        }
+    }
+
+    #[async_std::test]
+    async fn test_observed_stages() {
+        let recorder = Arc::new(Recorder::default());
+        let m = (
+            observed(multipler, recorder.clone()).named("multipler"),
+            try_observed(checked, recorder.clone()).named("checked"),
+        )
+            .pipe();
+        assert_eq!(Ok(64), m.call(2).await);
+        assert_eq!(Err(String::from("negative")), m.call(-1).await);
+        assert_eq!(
+            vec![
+                "start multipler",
+                "end multipler",
+                "start checked",
+                "end checked",
+                "start multipler",
+                "end multipler",
+                "start checked",
+                "end checked",
+                "error checked",
+            ],
+            *recorder.events.lock().unwrap()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_observe_pipeline() {
+        let recorder = Arc::new(Recorder::default());
+        let m = (multipler, checked)
+            .pipe()
+            .try_observe("pipeline", recorder.clone());
+        assert!(m.call(-1).await.is_err());
+
+        let m = (multipler, multipler)
+            .pipe()
+            .observe("plain", recorder.clone());
+        assert_eq!(2048, m.call(2).await);
+        assert_eq!(
+            vec![
+                "start pipeline",
+                "end pipeline",
+                "error pipeline",
+                "start plain",
+                "end plain",
+            ],
+            *recorder.events.lock().unwrap()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_observed_default_name() {
+        let recorder = Arc::new(Recorder::default());
+        let m = observed(multipler.named("decode"), recorder.clone());
+        assert_eq!(64, m.call(2).await);
+        assert_eq!(
+            vec!["start decode", "end decode"],
+            *recorder.events.lock().unwrap()
+        );
+    }
+}
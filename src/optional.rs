@@ -0,0 +1,152 @@
+//! Optional middleware types.
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{sequence, DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// Encapsulates the conversion between two optional transforms, short-circuiting on `None`
+pub struct OptConvertMiddleware<T, T2, A, B, C> {
+    t: Arc<dyn DynTransform<T, A, Option<B>>>,
+    t2: Arc<dyn DynTransform<T2, B, Option<C>>>,
+}
+
+/// Implements the transform trait on the optional conversion middleware (for downstream)
+impl<T, T2, A, B, C> Transform<(A, Option<C>), A, Option<C>>
+    for OptConvertMiddleware<T, T2, A, B, C>
+where
+    T: Send + Sync + 'static,
+    T2: Send + Sync + 'static,
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    C: Send + Sync + 'static,
+{
+    async fn transform(&self, input: A) -> Option<C> {
+        let input = self.t.transform(input).await?;
+        self.t2.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        sequence(self.t.stages(), self.t2.stages())
+    }
+}
+
+/// Implements the middleware trait on the optional conversion middleware to make it A -> C
+impl<T, T2, A, B, C> Middleware<A, Option<C>> for OptConvertMiddleware<T, T2, A, B, C>
+where
+    T: Send + Sync + 'static,
+    T2: Send + Sync + 'static,
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    C: Send + Sync + 'static,
+{
+    async fn call(&self, input: A) -> Option<C> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Creates a new optional conversion middleware from two stages returning `Option`, skipping
+/// the second when the first yields `None`
+pub fn opt_convert<T, T2, A, B, C>(
+    t: impl Transform<T, A, Option<B>>,
+    t2: impl Transform<T2, B, Option<C>>,
+) -> OptConvertMiddleware<T, T2, A, B, C>
+where
+    T: Send + Sync + 'static,
+    T2: Send + Sync + 'static,
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    C: Send + Sync + 'static,
+{
+    OptConvertMiddleware {
+        t: Arc::new(t),
+        t2: Arc::new(t2),
+    }
+}
+
+/// Pipes any number of optional stages together by expanding into nested `opt_convert` calls
+#[macro_export]
+macro_rules! opt_pipe {
+    ($first:expr, $second:expr $(, $rest:expr)* $(,)?) => {
+        $crate::opt_pipe!(@acc $crate::opt_convert($first, $second) $(, $rest)*)
+    };
+    (@acc $acc:expr, $next:expr $(, $rest:expr)*) => {
+        $crate::opt_pipe!(@acc $crate::opt_convert($acc, $next) $(, $rest)*)
+    };
+    (@acc $acc:expr) => {
+        $acc
+    };
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, Option<O>>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Appends an optional stage which only runs when the pipeline yields `Some`, similar to
+    /// `Option::and_then`
+    pub fn and_then<ArgsN, O2>(
+        self,
+        next: impl Transform<ArgsN, O, Option<O2>>,
+    ) -> Pied<T, Args, I, Option<O2>>
+    where
+        ArgsN: Send + Sync + 'static,
+        O2: Send + Sync + 'static,
+    {
+        self.rebuild(|pied| opt_convert(pied.middleware, next))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use std::collections::HashMap;
+
+    async fn user_id(name: String) -> Option<u32> {
+        match name.as_str() {
+            "alice" => Some(1),
+            "bob" => Some(2),
+            _ => None,
+        }
+    }
+
+    async fn email(id: u32) -> Option<String> {
+        let emails = HashMap::from([(1, "alice@example.com")]);
+        emails.get(&id).map(|e| e.to_string())
+    }
+
+    async fn domain(email: String) -> Option<String> {
+        email.split('@').nth(1).map(String::from)
+    }
+
+    #[async_std::test]
+    async fn test_opt_pipe() {
+        let m = opt_pipe!(user_id, email, domain);
+        assert_eq!(
+            Some(String::from("example.com")),
+            m.call(String::from("alice")).await
+        );
+        assert_eq!(None, m.call(String::from("bob")).await);
+        assert_eq!(None, m.call(String::from("carol")).await);
+        assert_eq!(3, Transform::stages(&m).len());
+    }
+
+    #[async_std::test]
+    async fn test_and_then() {
+        let m = (|s: String| async move { s }, user_id)
+            .pipe()
+            .and_then(email)
+            .and_then(domain);
+        assert_eq!(
+            Some(String::from("example.com")),
+            m.call(String::from("alice")).await
+        );
+        assert_eq!(None, m.call(String::from("carol")).await);
+    }
+}
@@ -0,0 +1,174 @@
+//! OpenTelemetry trace context propagation through per-call extensions.
+
+use futures::future::BoxFuture;
+use opentelemetry::{
+    context::FutureExt,
+    global,
+    propagation::{Extractor, Injector},
+    trace::{TraceContextExt, Tracer},
+};
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+use crate::{Context, DynTransform, Extensions, Middleware, Spawn, StageInfo, Transform};
+
+/// Propagation headers (e.g. W3C `traceparent`) carried in the extensions of a call
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TraceHeaders(pub HashMap<String, String>);
+
+impl Extractor for TraceHeaders {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(String::as_str).collect()
+    }
+}
+
+impl Injector for TraceHeaders {
+    fn set(&mut self, key: &str, value: String) {
+        self.0.insert(key.to_owned(), value);
+    }
+}
+
+/// Returns the trace context of the call: the one stored in the extensions by an enclosing span,
+/// else the one extracted from its `TraceHeaders` with the global propagator, else the current one
+pub fn trace_context(extensions: &Extensions) -> opentelemetry::Context {
+    if let Some(cx) = extensions.get::<opentelemetry::Context>() {
+        return cx.clone();
+    }
+    match extensions.get::<TraceHeaders>() {
+        Some(headers) => global::get_text_map_propagator(|propagator| propagator.extract(headers)),
+        None => opentelemetry::Context::current(),
+    }
+}
+
+/// Writes the trace context of the call into its `TraceHeaders` with the global propagator, so it
+/// crosses to the next service or pipeline
+pub fn inject_trace_context(extensions: &mut Extensions) {
+    let cx = trace_context(extensions);
+    let mut headers = extensions.remove::<TraceHeaders>().unwrap_or_default();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&cx, &mut headers));
+    extensions.insert(headers);
+}
+
+/// Runs a context-aware stage in a child span of the trace context of the call, which the stage
+/// and the stages it calls see as their parent
+pub struct OtelSpan<Args, T, O> {
+    stage: Arc<dyn DynTransform<Args, Context<T>, Context<O>>>,
+    name: Cow<'static, str>,
+}
+
+/// Creates a new stage tracing every call of the context-aware stage as a span with the name
+pub fn otel_span<Args, T, O>(
+    name: impl Into<Cow<'static, str>>,
+    stage: impl Transform<Args, Context<T>, Context<O>>,
+) -> OtelSpan<Args, T, O>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    OtelSpan {
+        stage: Arc::new(stage),
+        name: name.into(),
+    }
+}
+
+/// Implements the transform trait on the traced stage (for downstream)
+impl<Args, T, O> Transform<(Context<T>, Context<O>), Context<T>, Context<O>>
+    for OtelSpan<Args, T, O>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, mut input: Context<T>) -> Context<O> {
+        let parent = trace_context(&input.extensions);
+        let span =
+            global::tracer("async-middleware").start_with_context(self.name.clone(), &parent);
+        let cx = parent.with_span(span);
+        input.extensions.insert(cx.clone());
+        let mut output = self.stage.transform(input).with_context(cx.clone()).await;
+        cx.span().end();
+        // later stages are siblings of this span rather than its children
+        output.extensions.insert(parent);
+        output
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the traced stage
+impl<Args, T, O> Middleware<Context<T>, Context<O>> for OtelSpan<Args, T, O>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: Context<T>) -> Context<O> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Wraps a spawner so every task it spawns (e.g. the stage tasks of a spawned pipeline) runs
+/// within the trace context current when it was spawned
+pub fn otel_spawner(spawner: impl Spawn) -> impl Spawn {
+    move |future: BoxFuture<'static, ()>| {
+        let cx = opentelemetry::Context::current();
+        spawner.spawn(Box::pin(future.with_context(cx)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{in_context, Piper};
+    use opentelemetry::trace::{SpanContext, TraceFlags, TraceId, TraceState};
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+
+    async fn trace_id(cx: Context<i32>) -> Context<String> {
+        let id = trace_context(&cx.extensions)
+            .span()
+            .span_context()
+            .trace_id();
+        cx.map(|_| id.to_string())
+    }
+
+    #[async_std::test]
+    async fn test_otel_span_propagation() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let remote = SpanContext::new(
+            TraceId::from(0x4bf92f3577b34da6a3ce929d0e0e4736),
+            0x00f067aa0ba902b7.into(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let mut headers = TraceHeaders::default();
+        global::get_text_map_propagator(|propagator| {
+            let cx = opentelemetry::Context::new().with_remote_span_context(remote.clone());
+            propagator.inject_context(&cx, &mut headers)
+        });
+
+        let m = (
+            in_context(|i: i32| async move { i * 2 }),
+            otel_span("render", trace_id),
+        )
+            .pipe();
+        let mut input = Context::new(21);
+        input.extensions.insert(headers.clone());
+        let mut output = m.call(input).await;
+        assert_eq!("4bf92f3577b34da6a3ce929d0e0e4736", output.value);
+
+        output.extensions.remove::<TraceHeaders>();
+        inject_trace_context(&mut output.extensions);
+        assert_eq!(Some(&headers), output.extensions.get::<TraceHeaders>());
+    }
+}
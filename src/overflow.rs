@@ -0,0 +1,163 @@
+//! Overflow policies for the channels between spawned stages.
+
+use futures::{
+    channel::mpsc::{Receiver, Sender},
+    future::{poll_fn, BoxFuture},
+    Sink, StreamExt,
+};
+use std::{collections::VecDeque, pin::Pin, task::Poll};
+
+use crate::Spawned;
+
+/// What a buffer between spawned stages does with an item arriving while it is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Stops receiving until there is room, slowing down the previous stages (lossless)
+    Block,
+    /// Discards the oldest buffered item to make room for the new one
+    DropOldest,
+    /// Discards the new item, keeping the buffered ones
+    DropNewest,
+    /// Closes the buffer's input so the previous stages fail to send, then drains what is
+    /// buffered
+    Error,
+}
+
+enum Event<T> {
+    Ready(bool),
+    Received(Option<T>),
+}
+
+/// Creates the task loop that buffers up to `capacity` items between two stages, applying the
+/// overflow policy when the next stage falls behind
+fn overflow_task<T>(
+    mut rx: Receiver<T>,
+    mut tx: Sender<T>,
+    capacity: usize,
+    overflow: Overflow,
+) -> BoxFuture<'static, ()>
+where
+    T: Send + Sync + 'static,
+{
+    Box::pin(async move {
+        let mut queue = VecDeque::with_capacity(capacity);
+        let mut open = true;
+        while open || !queue.is_empty() {
+            let event = poll_fn(|cx| {
+                if !queue.is_empty() {
+                    if let Poll::Ready(ready) = Pin::new(&mut tx).poll_ready(cx) {
+                        return Poll::Ready(Event::Ready(ready.is_ok()));
+                    }
+                }
+                if open && (overflow != Overflow::Block || queue.len() < capacity) {
+                    if let Poll::Ready(item) = rx.poll_next_unpin(cx) {
+                        return Poll::Ready(Event::Received(item));
+                    }
+                }
+                Poll::Pending
+            })
+            .await;
+            match event {
+                Event::Ready(true) => {
+                    if let Some(item) = queue.pop_front() {
+                        if Pin::new(&mut tx).start_send(item).is_err() {
+                            return;
+                        }
+                    }
+                }
+                Event::Ready(false) => return,
+                Event::Received(None) => open = false,
+                Event::Received(Some(item)) if queue.len() < capacity => queue.push_back(item),
+                Event::Received(Some(item)) => match overflow {
+                    Overflow::Block | Overflow::DropNewest => {}
+                    Overflow::DropOldest => {
+                        queue.pop_front();
+                        queue.push_back(item);
+                    }
+                    Overflow::Error => {
+                        rx.close();
+                        open = false;
+                    }
+                },
+            }
+        }
+    })
+}
+
+impl<I, O> Spawned<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Buffers up to `capacity` outputs of the previous stage for the next one, applying the
+    /// overflow policy once the buffer is full instead of the default blocking backpressure
+    pub fn buffer(self, capacity: usize, overflow: Overflow) -> Spawned<I, O> {
+        let capacity = capacity.max(1);
+        self.task(move |rx, tx, _| overflow_task(rx, tx, capacity, overflow))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawned;
+    use async_std::task::{self, sleep};
+    use std::time::Duration;
+
+    fn spawner(future: BoxFuture<'static, ()>) {
+        task::spawn(future);
+    }
+
+    async fn pass(i: u32) -> u32 {
+        i
+    }
+
+    /// Sends the items without receiving any output, then drains the pipeline
+    async fn overflowed(overflow: Overflow) -> (usize, Vec<u32>) {
+        let mut handle = spawned(pass).buffer(2, overflow).spawn(1, spawner);
+        let mut sent = 0;
+        for i in 0..8 {
+            let send = handle.send(i);
+            match async_std::future::timeout(Duration::from_millis(20), send).await {
+                Ok(Ok(())) => sent += 1,
+                _ => break,
+            }
+            sleep(Duration::from_millis(5)).await;
+        }
+        (
+            sent,
+            handle
+                .shutdown(Duration::from_secs(1))
+                .await
+                .unwrap_or_default(),
+        )
+    }
+
+    #[async_std::test]
+    async fn test_buffer_overflow() {
+        let (sent, outputs) = overflowed(Overflow::DropNewest).await;
+        assert_eq!(8, sent);
+        assert_eq!(vec![0, 1, 2, 3], outputs);
+
+        let (sent, outputs) = overflowed(Overflow::DropOldest).await;
+        assert_eq!(8, sent);
+        assert_eq!(vec![0, 1, 6, 7], outputs);
+    }
+
+    #[async_std::test]
+    async fn test_buffer_block_and_error() {
+        let (sent, _) = overflowed(Overflow::Block).await;
+        assert!(sent < 8);
+
+        let mut handle = spawned(pass).buffer(1, Overflow::Error).spawn(1, spawner);
+        let mut failed = false;
+        for i in 0..8 {
+            if handle.send(i).await.is_err() {
+                failed = true;
+                break;
+            }
+            sleep(Duration::from_millis(5)).await;
+        }
+        assert!(failed);
+    }
+}
@@ -0,0 +1,161 @@
+//! Catching panics raised by stages.
+
+use futures::FutureExt;
+use std::{
+    any::{type_name, Any},
+    borrow::Cow,
+    error::Error,
+    fmt,
+    panic::AssertUnwindSafe,
+    sync::Arc,
+};
+
+use crate::{DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// Error returned when a stage panicked instead of producing an output
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StagePanic {
+    stage: Cow<'static, str>,
+    message: String,
+}
+
+impl StagePanic {
+    /// Returns the name of the stage that panicked
+    pub fn stage(&self) -> &str {
+        &self.stage
+    }
+
+    /// Returns the message of the panic payload, if it was a string
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for StagePanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stage {} panicked: {}", self.stage, self.message)
+    }
+}
+
+impl Error for StagePanic {}
+
+/// Extracts the message of a panic payload raised by `panic!`
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        String::from(*message)
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("Box<dyn Any>")
+    }
+}
+
+/// Converts a panicking stage into a `StagePanic` error instead of unwinding the caller's task
+pub struct CatchUnwind<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    name: Cow<'static, str>,
+}
+
+/// Creates a new panic-catching stage, named after the type of the stage
+pub fn catch_unwind<Args, I, O, S>(stage: S) -> CatchUnwind<Args, I, O>
+where
+    S: Transform<Args, I, O>,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    CatchUnwind {
+        stage: Arc::new(stage),
+        name: Cow::Borrowed(type_name::<S>()),
+    }
+}
+
+impl<Args, I, O> CatchUnwind<Args, I, O> {
+    /// Sets the stage name recorded on a caught panic
+    pub fn named(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.name = name.into();
+        self
+    }
+}
+
+/// Implements the transform trait on the panic-catching stage (for downstream)
+impl<Args, I, O> Transform<(I, Result<O, StagePanic>), I, Result<O, StagePanic>>
+    for CatchUnwind<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> Result<O, StagePanic> {
+        AssertUnwindSafe(self.stage.transform(input))
+            .catch_unwind()
+            .await
+            .map_err(|payload| StagePanic {
+                stage: self.name.clone(),
+                message: panic_message(payload.as_ref()),
+            })
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the panic-catching stage
+impl<Args, I, O> Middleware<I, Result<O, StagePanic>> for CatchUnwind<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> Result<O, StagePanic> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Converts a panic anywhere in the pipeline into a `StagePanic` error
+    pub fn catch_unwind(self) -> Pied<T, Args, I, Result<O, StagePanic>> {
+        self.rebuild(|pied| catch_unwind(pied).named("pipeline"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn checked(i: u32) -> u32 {
+        if i == 0 {
+            panic!("zero input");
+        }
+        i * 2
+    }
+
+    async fn stringer(i: u32) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_catch_unwind() {
+        let m = catch_unwind(checked).named("checked");
+        assert_eq!(Ok(4), m.call(2).await);
+        let err = m.call(0).await.unwrap_err();
+        assert_eq!("checked", err.stage());
+        assert_eq!("stage checked panicked: zero input", err.to_string());
+
+        let m = (checked, stringer).pipe().catch_unwind();
+        assert_eq!(Ok(String::from("6")), m.call(3).await);
+        assert_eq!("pipeline", m.call(0).await.unwrap_err().stage());
+    }
+}
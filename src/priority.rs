@@ -0,0 +1,173 @@
+//! Priority ordering of items between spawned stages.
+
+use futures::{
+    channel::mpsc::{Receiver, Sender},
+    future::BoxFuture,
+    SinkExt, StreamExt,
+};
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use crate::Spawned;
+
+/// Buffer of waiting items ordered both by priority and by arrival
+struct PriorityBuffer<T, P> {
+    by_priority: BTreeMap<(P, Reverse<u64>), T>,
+    by_arrival: BTreeMap<u64, P>,
+    next: u64,
+}
+
+impl<T, P> PriorityBuffer<T, P>
+where
+    P: Ord + Clone,
+{
+    fn push(&mut self, item: T, priority: P) {
+        self.by_arrival.insert(self.next, priority.clone());
+        self.by_priority
+            .insert((priority, Reverse(self.next)), item);
+        self.next += 1;
+    }
+
+    fn len(&self) -> usize {
+        self.by_arrival.len()
+    }
+
+    fn oldest(&self) -> Option<u64> {
+        self.by_arrival.keys().next().copied()
+    }
+
+    /// Removes the item with the highest priority, the oldest first among equal priorities
+    fn pop_highest(&mut self) -> Option<(u64, T)> {
+        let ((_, Reverse(seq)), item) = self.by_priority.pop_last()?;
+        self.by_arrival.remove(&seq);
+        Some((seq, item))
+    }
+
+    fn pop_oldest(&mut self) -> Option<T> {
+        let (seq, priority) = self.by_arrival.pop_first()?;
+        self.by_priority.remove(&(priority, Reverse(seq)))
+    }
+}
+
+/// Creates the task loop that buffers up to `capacity` received items and forwards the one
+/// with the highest priority whenever the next stage has room, sending the oldest item instead
+/// once `max_skips` items in a row have overtaken it
+fn priority_task<T, P>(
+    mut rx: Receiver<T>,
+    mut tx: Sender<T>,
+    priority: impl Fn(&T) -> P + Send + 'static,
+    capacity: usize,
+    max_skips: usize,
+) -> BoxFuture<'static, ()>
+where
+    T: Send + Sync + 'static,
+    P: Ord + Clone + Send + 'static,
+{
+    Box::pin(async move {
+        let mut buffer = PriorityBuffer {
+            by_priority: BTreeMap::new(),
+            by_arrival: BTreeMap::new(),
+            next: 0,
+        };
+        let mut open = true;
+        let mut skipped = 0;
+        loop {
+            if open && buffer.len() == 0 {
+                match rx.next().await {
+                    Some(item) => {
+                        let p = priority(&item);
+                        buffer.push(item, p)
+                    }
+                    None => open = false,
+                }
+            }
+            while open && buffer.len() < capacity {
+                match rx.try_recv() {
+                    Ok(item) => {
+                        let p = priority(&item);
+                        buffer.push(item, p)
+                    }
+                    Err(e) if e.is_closed() => open = false,
+                    Err(_) => break,
+                }
+            }
+            let Some(oldest) = buffer.oldest() else {
+                return;
+            };
+            let item = if skipped >= max_skips {
+                skipped = 0;
+                buffer.pop_oldest()
+            } else {
+                buffer.pop_highest().map(|(seq, item)| {
+                    skipped = if seq == oldest { 0 } else { skipped + 1 };
+                    item
+                })
+            };
+            if let Some(item) = item {
+                if tx.send(item).await.is_err() {
+                    return;
+                }
+            }
+        }
+    })
+}
+
+impl<I, O> Spawned<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Reorders the outputs of the previous stage so items with a higher priority are handed to
+    /// the next stage first while it is backed up. At most `max_skips` items may overtake the
+    /// oldest waiting item before it is sent, so low-priority items are never starved.
+    pub fn prioritize<P>(
+        self,
+        priority: impl Fn(&O) -> P + Send + 'static,
+        max_skips: usize,
+    ) -> Spawned<I, O>
+    where
+        P: Ord + Clone + Send + 'static,
+    {
+        self.task(move |rx, tx, capacity| {
+            priority_task(rx, tx, priority, capacity.max(1), max_skips)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spawned;
+    use async_std::task::{self, sleep};
+    use futures::future::BoxFuture;
+    use std::time::Duration;
+
+    fn spawner(future: BoxFuture<'static, ()>) {
+        task::spawn(future);
+    }
+
+    async fn pass(i: u32) -> u32 {
+        i
+    }
+
+    async fn prioritized(max_skips: usize) -> Vec<u32> {
+        let mut handle = spawned(pass)
+            .prioritize(|i: &u32| *i >= 100, max_skips)
+            .spawn(4, spawner);
+        // fill the output channel so later items wait in the priority buffer
+        for i in 0..5 {
+            handle.send(i).await.unwrap();
+        }
+        sleep(Duration::from_millis(20)).await;
+        for i in [5, 6, 100, 101] {
+            handle.send(i).await.unwrap();
+        }
+        sleep(Duration::from_millis(20)).await;
+        handle.shutdown(Duration::from_secs(1)).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_prioritize() {
+        assert_eq!(vec![0, 1, 2, 3, 4, 100, 101, 5, 6], prioritized(2).await);
+        // the oldest item is sent once another item overtook it
+        assert_eq!(vec![0, 1, 2, 3, 4, 100, 5, 101, 6], prioritized(1).await);
+    }
+}
@@ -0,0 +1,277 @@
+//! Per-stage timing of profiled pipelines.
+
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{rt::Instant, DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// Number of recent durations kept per stage to compute percentiles from
+const MAX_SAMPLES: usize = 1024;
+
+/// Summary of the wall-clock durations recorded for a stage of a profiled pipeline
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StageTiming {
+    pub name: Cow<'static, str>,
+    pub position: usize,
+    pub calls: u64,
+    pub min: Duration,
+    pub avg: Duration,
+    /// 99th percentile over the most recent calls
+    pub p99: Duration,
+}
+
+struct Samples {
+    name: Cow<'static, str>,
+    calls: u64,
+    total: Duration,
+    min: Duration,
+    recent: VecDeque<Duration>,
+}
+
+impl Samples {
+    /// Records the duration of a single call of the stage
+    fn record(&mut self, elapsed: Duration) {
+        self.calls += 1;
+        self.total += elapsed;
+        self.min = self.min.min(elapsed);
+        if self.recent.len() == MAX_SAMPLES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(elapsed);
+    }
+}
+
+/// Durations recorded by the stages of a profiled pipeline, in order; the samples of a stage
+/// are shared by every profiler the stage was carried over to
+#[derive(Default)]
+pub(crate) struct Profiler {
+    stages: Mutex<Vec<Arc<Mutex<Samples>>>>,
+}
+
+impl Profiler {
+    /// Adds a stage to the end of the profile, returning where to record its durations
+    fn register(&self, name: Cow<'static, str>) -> Arc<Mutex<Samples>> {
+        let samples = Arc::new(Mutex::new(Samples {
+            name,
+            calls: 0,
+            total: Duration::ZERO,
+            min: Duration::MAX,
+            recent: VecDeque::new(),
+        }));
+        self.stages.lock().unwrap().push(samples.clone());
+        samples
+    }
+
+    /// Creates a new profiler starting with the stages of this one, so stages appended to a
+    /// pipeline don't show up in the timings of its clones
+    pub(crate) fn fork(&self) -> Arc<Profiler> {
+        Arc::new(Profiler {
+            stages: Mutex::new(self.stages.lock().unwrap().clone()),
+        })
+    }
+
+    /// Appends the stages of the other profiler after those of this one
+    pub(crate) fn extend(&self, other: &Profiler) {
+        let other = other.stages.lock().unwrap().clone();
+        self.stages.lock().unwrap().extend(other);
+    }
+
+    /// Summarizes the durations recorded so far for every stage
+    fn timings(&self) -> Vec<StageTiming> {
+        let stages = self.stages.lock().unwrap();
+        stages
+            .iter()
+            .enumerate()
+            .map(|(position, samples)| {
+                let samples = samples.lock().unwrap();
+                let mut recent: Vec<_> = samples.recent.iter().copied().collect();
+                recent.sort();
+                let p99 = match recent.len() {
+                    0 => Duration::ZERO,
+                    n => recent[(n * 99).div_ceil(100) - 1],
+                };
+                StageTiming {
+                    name: samples.name.clone(),
+                    position,
+                    calls: samples.calls,
+                    min: if samples.calls == 0 {
+                        Duration::ZERO
+                    } else {
+                        samples.min
+                    },
+                    avg: match samples.calls {
+                        0 => Duration::ZERO,
+                        calls => {
+                            Duration::from_nanos((samples.total.as_nanos() / calls as u128) as u64)
+                        }
+                    },
+                    p99,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Records the wall-clock duration of every call of a stage into the profiler
+pub(crate) struct Timed<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    samples: Arc<Mutex<Samples>>,
+}
+
+impl<Args, I, O> Timed<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Registers the stage with the profiler, taking the next position
+    pub(crate) fn new(stage: impl Transform<Args, I, O>, profiler: &Arc<Profiler>) -> Self {
+        let names: Vec<_> = stage.stages().into_iter().map(|s| s.name).collect();
+        Timed {
+            samples: profiler.register(names.join(", ").into()),
+            stage: Arc::new(stage),
+        }
+    }
+}
+
+/// Implements the transform trait on the timed stage (for downstream)
+impl<Args, I, O> Transform<(I, O), I, O> for Timed<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let start = Instant::now();
+        let output = self.stage.transform(input).await;
+        self.samples.lock().unwrap().record(start.elapsed());
+        output
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the timed stage
+impl<Args, I, O> Middleware<I, O> for Timed<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O> {
+    /// Returns the timing summary of every stage, in order, for a pipeline built with
+    /// `profile()`; stages appended later (e.g. with `then`) are timed too, and combinators
+    /// wrapping the whole pipeline (e.g. `timeout`) keep the timings of the stages inside.
+    /// Empty for pipelines built with `pipe()`
+    pub fn timings(&self) -> Vec<StageTiming> {
+        self.profiler
+            .as_ref()
+            .map(|profiler| profiler.timings())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{pipe_fn, Middleware, Piper, TransformExt};
+    use async_std::task::sleep;
+    use std::time::Duration;
+
+    async fn fast(i: u64) -> u64 {
+        i + 1
+    }
+
+    async fn slow(i: u64) -> u64 {
+        sleep(Duration::from_millis(i)).await;
+        i
+    }
+
+    #[async_std::test]
+    async fn test_profile_timings() {
+        let m = (fast, slow.named("slow"), fast).profile();
+        for i in [5, 10, 15] {
+            m.call(i).await;
+        }
+        let timings = m.timings();
+        assert_eq!(3, timings.len());
+        assert_eq!("slow", timings[1].name);
+        assert_eq!((1, 3), (timings[1].position, timings[1].calls));
+        assert!(timings[1].min >= Duration::from_millis(6));
+        assert!(timings[1].p99 >= Duration::from_millis(16));
+        assert!(timings[1].avg > timings[0].avg);
+
+        assert!((fast, slow).pipe().timings().is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_profile_then() {
+        let profiled = (fast, fast).profile();
+        let m = profiled.clone().then(slow.named("slow"));
+        m.call(5).await;
+        let timings = m.timings();
+        assert_eq!(3, timings.len());
+        assert_eq!(
+            ("slow", 2, 1),
+            (&*timings[2].name, timings[2].position, timings[2].calls)
+        );
+        assert!(timings[2].min >= Duration::from_millis(7));
+        assert_eq!(1, timings[0].calls);
+
+        // the appended stage is only part of the extended pipeline
+        assert_eq!(2, profiled.timings().len());
+        assert_eq!(1, profiled.timings()[0].calls);
+    }
+
+    #[async_std::test]
+    async fn test_profile_builders() {
+        let m = (fast, slow.named("slow"))
+            .profile()
+            .timeout(Duration::from_millis(100))
+            .rate_limit(10, Duration::from_millis(10))
+            .inspect(|_| {})
+            .map_err(|_| "timed out");
+        assert_eq!(Ok(6), m.call(5).await);
+        let timings = m.timings();
+        assert_eq!(4, timings.len());
+        assert_eq!(("slow", 1), (&*timings[1].name, timings[1].calls));
+        assert_eq!(1, timings[3].calls);
+    }
+
+    #[async_std::test]
+    async fn test_profile_shr() {
+        let m = (fast, fast).profile() >> (slow.named("slow"), fast).profile();
+        m.call(5).await;
+        let names: Vec<_> = m.timings().into_iter().map(|t| (t.name, t.calls)).collect();
+        assert_eq!(4, names.len());
+        assert_eq!(("slow".into(), 1), names[2]);
+
+        // an unprofiled side is timed as a single stage
+        let m = (fast, fast).pipe() >> (fast, slow.named("slow")).profile();
+        m.call(5).await;
+        let timings = m.timings();
+        assert_eq!(3, timings.len());
+        assert!(timings[0].name.contains("fast"));
+        assert_eq!(
+            ("slow", 2, 1),
+            (&*timings[2].name, timings[2].position, timings[2].calls)
+        );
+        let m = (fast, fast).profile() >> pipe_fn(slow.named("slow"));
+        m.call(5).await;
+        assert_eq!(("slow", 1), (&*m.timings()[2].name, m.timings()[2].calls));
+    }
+}
@@ -0,0 +1,105 @@
+//! Select-first execution of two equivalent pipelines.
+
+use futures::future::{select, Either};
+use std::sync::Arc;
+
+use crate::{DynTransform, Middleware, StageInfo, Transform};
+
+/// Runs two stages concurrently on clones of the input, keeping whichever finishes first
+pub struct Race<A, A2, I, O> {
+    left: Arc<dyn DynTransform<A, I, O>>,
+    right: Arc<dyn DynTransform<A2, I, O>>,
+}
+
+/// Creates a new race between two stages; the slower one is dropped when the first completes
+pub fn race<A, A2, I, O>(
+    left: impl Transform<A, I, O>,
+    right: impl Transform<A2, I, O>,
+) -> Race<A, A2, I, O>
+where
+    A: Send + Sync + 'static,
+    A2: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    Race {
+        left: Arc::new(left),
+        right: Arc::new(right),
+    }
+}
+
+/// Implements the transform trait on the race (for downstream)
+impl<A, A2, I, O> Transform<(I, O), I, O> for Race<A, A2, I, O>
+where
+    A: Send + Sync + 'static,
+    A2: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let left = self.left.transform(input.clone());
+        let right = self.right.transform(input);
+        match select(left, right).await {
+            Either::Left((output, _)) | Either::Right((output, _)) => output,
+        }
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::branching::<I, O>(
+            "race",
+            vec![
+                ("left".into(), self.left.stages()),
+                ("right".into(), self.right.stages()),
+            ],
+        )]
+    }
+}
+
+/// Implements the middleware trait on the race
+impl<A, A2, I, O> Middleware<I, O> for Race<A, A2, I, O>
+where
+    A: Send + Sync + 'static,
+    A2: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task::sleep;
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    };
+
+    async fn fast(i: i32) -> String {
+        format!("fast {}", i)
+    }
+
+    #[async_std::test]
+    async fn test_race_cancels_loser() {
+        let finished = Arc::new(AtomicBool::new(false));
+        let flag = finished.clone();
+        let slow = move |i: i32| {
+            let flag = flag.clone();
+            async move {
+                sleep(Duration::from_millis(20)).await;
+                flag.store(true, Ordering::SeqCst);
+                format!("slow {}", i)
+            }
+        };
+        let m = race(slow, fast);
+        assert_eq!(String::from("fast 1"), m.call(1).await);
+        sleep(Duration::from_millis(30)).await;
+        assert!(!finished.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,308 @@
+//! Rate limiting middleware.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    rt::{self, Instant},
+    DynTransform, Middleware, Pied, StageInfo, Transform,
+};
+
+/// Strategy deciding when the calls of a rate limit may proceed
+pub trait RateLimitStrategy: Send + 'static {
+    /// Admits a call now, or returns how long to wait before trying again
+    fn try_acquire(&mut self) -> Result<(), Duration>;
+}
+
+/// Token bucket refilled continuously at a fixed rate, up to its capacity, allowing bursts
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a new full bucket letting `num` calls through every `per`, in bursts of up to `num`
+    pub fn new(num: u32, per: Duration) -> Self {
+        let capacity = f64::from(num.max(1));
+        TokenBucket {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: capacity / per.as_secs_f64(),
+            last: Instant::now(),
+        }
+    }
+}
+
+impl RateLimitStrategy for TokenBucket {
+    fn try_acquire(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / self.refill_per_sec,
+            ))
+        }
+    }
+}
+
+/// Leaky bucket draining at a constant rate, spacing calls evenly without bursts
+pub struct LeakyBucket {
+    interval: Duration,
+    next: Instant,
+}
+
+impl LeakyBucket {
+    /// Creates a new bucket letting `num` calls through every `per`, one every `per / num`
+    pub fn new(num: u32, per: Duration) -> Self {
+        LeakyBucket {
+            interval: per / num.max(1),
+            next: Instant::now(),
+        }
+    }
+}
+
+impl RateLimitStrategy for LeakyBucket {
+    fn try_acquire(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        if now < self.next {
+            return Err(self.next - now);
+        }
+        self.next = now + self.interval;
+        Ok(())
+    }
+}
+
+/// Counter reset at the start of every fixed window, letting the full quota through at once
+pub struct FixedWindow {
+    num: u32,
+    per: Duration,
+    start: Instant,
+    count: u32,
+}
+
+impl FixedWindow {
+    /// Creates a new window letting `num` calls through every `per`
+    pub fn new(num: u32, per: Duration) -> Self {
+        FixedWindow {
+            num: num.max(1),
+            per,
+            start: Instant::now(),
+            count: 0,
+        }
+    }
+}
+
+impl RateLimitStrategy for FixedWindow {
+    fn try_acquire(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.start);
+        if elapsed >= self.per {
+            // align to the window boundaries so idle periods don't shift them
+            let into = elapsed.as_nanos() % self.per.as_nanos().max(1);
+            self.start =
+                now - Duration::new((into / 1_000_000_000) as u64, (into % 1_000_000_000) as u32);
+            self.count = 0;
+        }
+        if self.count < self.num {
+            self.count += 1;
+            Ok(())
+        } else {
+            Err((self.start + self.per).saturating_duration_since(now))
+        }
+    }
+}
+
+/// Handle to a rate limit strategy that several stages or pipelines draw from, so they share
+/// one quota
+#[derive(Clone)]
+pub struct SharedLimiter {
+    strategy: Arc<Mutex<dyn RateLimitStrategy>>,
+}
+
+impl SharedLimiter {
+    /// Creates a new shared limiter around the strategy
+    pub fn new(strategy: impl RateLimitStrategy) -> Self {
+        SharedLimiter {
+            strategy: Arc::new(Mutex::new(strategy)),
+        }
+    }
+
+    /// Waits until the strategy admits the next call
+    pub async fn acquire(&self) {
+        loop {
+            let wait = self.strategy.lock().unwrap().try_acquire();
+            match wait {
+                Ok(()) => return,
+                Err(delay) => rt::sleep(delay).await,
+            }
+        }
+    }
+}
+
+/// Bounds how many calls per period pass through a stage, waiting when over budget
+pub struct RateLimit<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    limiter: SharedLimiter,
+}
+
+impl<Args, I, O> RateLimit<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Creates a new rate limit letting `num` calls through every `per`, allowing bursts of `num`
+    pub fn new(stage: impl Transform<Args, I, O>, num: u32, per: Duration) -> Self {
+        RateLimit::with_strategy(stage, TokenBucket::new(num, per))
+    }
+
+    /// Creates a new rate limit admitting calls according to the strategy
+    pub fn with_strategy(
+        stage: impl Transform<Args, I, O>,
+        strategy: impl RateLimitStrategy,
+    ) -> Self {
+        RateLimit::shared(stage, &SharedLimiter::new(strategy))
+    }
+
+    /// Creates a new rate limit drawing from the quota of a shared limiter
+    pub fn shared(stage: impl Transform<Args, I, O>, limiter: &SharedLimiter) -> Self {
+        RateLimit {
+            stage: Arc::new(stage),
+            limiter: limiter.clone(),
+        }
+    }
+}
+
+/// Implements the transform trait on the rate limit (for downstream)
+impl<Args, I, O> Transform<(I, O), I, O> for RateLimit<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        self.limiter.acquire().await;
+        self.stage.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the rate limit
+impl<Args, I, O> Middleware<I, O> for RateLimit<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Bounds how many calls per period pass through the whole pipeline
+    pub fn rate_limit(self, num: u32, per: Duration) -> Pied<T, Args, I, O> {
+        self.rebuild(|pied| RateLimit::new(pied, num, per))
+    }
+
+    /// Bounds the calls through the whole pipeline by the quota of a shared limiter
+    pub fn rate_limit_shared(self, limiter: &SharedLimiter) -> Pied<T, Args, I, O> {
+        self.rebuild(|pied| RateLimit::shared(pied, limiter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pipe_fn, Piper};
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_rate_limit_waits() {
+        let m = (multipler, stringer)
+            .pipe()
+            .rate_limit(2, Duration::from_millis(50));
+
+        let start = Instant::now();
+        assert_eq!(String::from("32"), m.call(1).await);
+        assert_eq!(String::from("64"), m.call(2).await);
+        assert!(start.elapsed() < Duration::from_millis(25));
+
+        // the burst is spent, so the third call waits for a refill
+        assert_eq!(String::from("96"), m.call(3).await);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[async_std::test]
+    async fn test_rate_limit_strategies() {
+        let m = RateLimit::with_strategy(multipler, LeakyBucket::new(2, Duration::from_millis(40)));
+        let start = Instant::now();
+        assert_eq!(32, m.call(1).await);
+        assert_eq!(64, m.call(2).await);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let m = RateLimit::with_strategy(multipler, FixedWindow::new(2, Duration::from_millis(40)));
+        let start = Instant::now();
+        m.call(1).await;
+        m.call(2).await;
+        assert!(start.elapsed() < Duration::from_millis(20));
+        m.call(3).await;
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_fixed_window_realigns() {
+        // more windows have passed than fit in a u32, the window still lands on the current one
+        let mut window = FixedWindow {
+            start: Instant::now() - Duration::from_secs(5),
+            ..FixedWindow::new(1, Duration::from_nanos(1))
+        };
+        assert!(window.try_acquire().is_ok());
+        assert!(window.start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[async_std::test]
+    async fn test_rate_limit_shared() {
+        let limiter = SharedLimiter::new(TokenBucket::new(2, Duration::from_millis(50)));
+        let a = (multipler, stringer).pipe().rate_limit_shared(&limiter);
+        let b = pipe_fn(stringer).rate_limit_shared(&limiter);
+
+        let start = Instant::now();
+        assert_eq!(String::from("32"), a.call(1).await);
+        assert_eq!(String::from("2"), b.call(2).await);
+        assert!(start.elapsed() < Duration::from_millis(20));
+
+        // both pipelines spent the one quota, so the next call of either waits
+        assert_eq!(String::from("3"), b.call(3).await);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,162 @@
+//! Config-driven pipeline assembly.
+
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error, fmt};
+
+use crate::{into_middleware, BoxMiddleware, DynChain, Transform};
+
+/// Serializable description of a pipeline as an ordered list of registered stages
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "S: Deserialize<'de> + Default"))]
+pub struct PipelineSpec<S> {
+    /// Stages to run, in order
+    pub stages: Vec<StageSpec<S>>,
+}
+
+/// Serializable description of a single stage and its settings
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "S: Deserialize<'de> + Default"))]
+pub struct StageSpec<S> {
+    /// Name the stage factory was registered under
+    pub stage: String,
+    /// Settings handed to the stage factory
+    #[serde(default)]
+    pub settings: S,
+}
+
+/// Error returned when a spec cannot be turned into a pipeline
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegistryError {
+    /// No factory was registered under the stage name
+    UnknownStage(String),
+    /// The factory rejected the settings of the stage
+    InvalidSettings { stage: String, message: String },
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::UnknownStage(stage) => write!(f, "unknown stage {}", stage),
+            RegistryError::InvalidSettings { stage, message } => {
+                write!(f, "invalid settings for stage {}: {}", stage, message)
+            }
+        }
+    }
+}
+
+impl Error for RegistryError {}
+
+type Factory<T, S> = Box<dyn Fn(&S) -> Result<BoxMiddleware<T, T>, String> + Send + Sync>;
+
+/// Named `T -> T` stage factories that pipelines are assembled from at runtime
+pub struct PipelineRegistry<T, S> {
+    factories: HashMap<String, Factory<T, S>>,
+}
+
+impl<T, S> PipelineRegistry<T, S>
+where
+    T: Send + Sync + 'static,
+{
+    /// Creates a new empty registry
+    pub fn new() -> Self {
+        PipelineRegistry {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Registers a factory building a stage from its settings under the name
+    pub fn register<F, R, Args, E>(&mut self, name: impl Into<String>, factory: F)
+    where
+        F: Fn(&S) -> Result<R, E> + Send + Sync + 'static,
+        R: Transform<Args, T, T>,
+        Args: Send + Sync + 'static,
+        E: fmt::Display,
+    {
+        self.factories.insert(
+            name.into(),
+            Box::new(move |settings| {
+                factory(settings)
+                    .map(into_middleware)
+                    .map_err(|e| e.to_string())
+            }),
+        );
+    }
+
+    /// Builds the pipeline described by the spec
+    pub fn build(&self, spec: &PipelineSpec<S>) -> Result<BoxMiddleware<T, T>, RegistryError> {
+        let mut chain = DynChain::new();
+        for stage in &spec.stages {
+            let factory = self
+                .factories
+                .get(&stage.stage)
+                .ok_or_else(|| RegistryError::UnknownStage(stage.stage.clone()))?;
+            let built =
+                factory(&stage.settings).map_err(|message| RegistryError::InvalidSettings {
+                    stage: stage.stage.clone(),
+                    message,
+                })?;
+            chain.push(built);
+        }
+        Ok(into_middleware(chain))
+    }
+}
+
+impl<T, S> Default for PipelineRegistry<T, S>
+where
+    T: Send + Sync + 'static,
+{
+    fn default() -> Self {
+        PipelineRegistry::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Middleware;
+    use serde_json::{json, Value};
+
+    async fn double(i: i64) -> i64 {
+        i * 2
+    }
+
+    fn registry() -> PipelineRegistry<i64, Value> {
+        let mut registry = PipelineRegistry::new();
+        registry.register("double", |_: &Value| Ok::<_, String>(double));
+        registry.register("add", |settings: &Value| {
+            let amount = settings["amount"].as_i64().ok_or("missing amount")?;
+            Ok::<_, &str>(move |i: i64| async move { i + amount })
+        });
+        registry
+    }
+
+    #[async_std::test]
+    async fn test_registry_build() {
+        let spec: PipelineSpec<Value> = serde_json::from_value(json!({
+            "stages": [
+                { "stage": "add", "settings": { "amount": 2 } },
+                { "stage": "double" },
+            ]
+        }))
+        .unwrap();
+        let m = registry().build(&spec).unwrap();
+        assert_eq!(10, m.call(3).await);
+    }
+
+    #[test]
+    fn test_registry_errors() {
+        let spec: PipelineSpec<Value> =
+            serde_json::from_value(json!({ "stages": [{ "stage": "triple" }] })).unwrap();
+        assert_eq!(
+            Some(RegistryError::UnknownStage(String::from("triple"))),
+            registry().build(&spec).err()
+        );
+
+        let spec: PipelineSpec<Value> =
+            serde_json::from_value(json!({ "stages": [{ "stage": "add" }] })).unwrap();
+        assert_eq!(
+            "invalid settings for stage add: missing amount",
+            registry().build(&spec).err().unwrap().to_string()
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Feeding a stage's output back as its input.
+
+use alloc::{boxed::Box, sync::Arc, vec, vec::Vec};
+
+use crate::{DynTransform, Middleware, StageInfo, Transform};
+
+/// Reruns a stage on its own output until a condition holds or the iteration cap is reached
+pub struct RepeatUntil<Args, T> {
+    stage: Arc<dyn DynTransform<Args, T, T>>,
+    until: Box<dyn Fn(&T) -> bool + Send + Sync>,
+    max_iters: usize,
+}
+
+/// Creates a new loop running the stage at least once and at most `max_iters` times,
+/// stopping early once `until` holds for its output
+pub fn repeat_until<Args, T>(
+    stage: impl Transform<Args, T, T>,
+    until: impl Fn(&T) -> bool + Send + Sync + 'static,
+    max_iters: usize,
+) -> RepeatUntil<Args, T>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+{
+    RepeatUntil {
+        stage: Arc::new(stage),
+        until: Box::new(until),
+        max_iters: max_iters.max(1),
+    }
+}
+
+/// Implements the transform trait on the loop (for downstream)
+impl<Args, T> Transform<(T, T), T, T> for RepeatUntil<Args, T>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> T {
+        let mut value = input;
+        for _ in 0..self.max_iters {
+            value = self.stage.transform(value).await;
+            if (self.until)(&value) {
+                break;
+            }
+        }
+        value
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::branching::<T, T>(
+            "repeat_until",
+            vec![("repeat".into(), self.stage.stages())],
+        )]
+    }
+}
+
+/// Implements the middleware trait on the loop
+impl<Args, T> Middleware<T, T> for RepeatUntil<Args, T>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> T {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    #[derive(Debug, PartialEq)]
+    struct Page {
+        cursor: u32,
+        items: Vec<u32>,
+    }
+
+    async fn fetch(mut page: Page) -> Page {
+        page.items.push(page.cursor);
+        page.cursor += 1;
+        page
+    }
+
+    async fn halve(i: f64) -> f64 {
+        i / 2.0
+    }
+
+    async fn count(page: Page) -> usize {
+        page.items.len()
+    }
+
+    #[async_std::test]
+    async fn test_repeat_until() {
+        let m = (repeat_until(fetch, |p: &Page| p.cursor == 3, 10), count).pipe();
+        let start = Page {
+            cursor: 0,
+            items: Vec::new(),
+        };
+        assert_eq!(3, m.call(start).await);
+
+        let m = repeat_until(halve, |i: &f64| *i < 0.001, 4);
+        assert_eq!(1.0, m.call(16.0).await);
+    }
+}
@@ -0,0 +1,371 @@
+//! Retry middleware with pluggable backoff.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    rt::{self, Instant},
+    util::random,
+    DynTransform, Middleware, Pied, StageInfo, Transform,
+};
+
+/// Policy deciding whether and how long to wait before retrying a failed call.
+pub trait Backoff: Send + Sync + 'static {
+    /// Returns the delay before the given retry (starting at 1), or `None` to stop retrying
+    fn next_delay(&self, retry: u32) -> Option<Duration>;
+}
+
+/// Exponential backoff with an optional full jitter and a cap on the number of attempts
+#[derive(Clone, Debug)]
+pub struct ExponentialBackoff {
+    initial: Duration,
+    factor: u32,
+    max_delay: Duration,
+    max_attempts: u32,
+    jitter: bool,
+}
+
+impl ExponentialBackoff {
+    /// Creates a new backoff starting at the initial delay, doubling up to three attempts
+    pub fn new(initial: Duration) -> Self {
+        ExponentialBackoff {
+            initial,
+            factor: 2,
+            max_delay: Duration::from_secs(30),
+            max_attempts: 3,
+            jitter: false,
+        }
+    }
+
+    /// Sets the multiplier applied to the delay after every retry
+    pub fn factor(mut self, factor: u32) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Sets the upper bound of any single delay
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Sets the total number of attempts, including the first call
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Randomizes every delay between zero and the computed delay
+    pub fn jitter(mut self) -> Self {
+        self.jitter = true;
+        self
+    }
+}
+
+impl Backoff for ExponentialBackoff {
+    fn next_delay(&self, retry: u32) -> Option<Duration> {
+        if retry >= self.max_attempts {
+            return None;
+        }
+        let delay = self
+            .factor
+            .checked_pow(retry.saturating_sub(1))
+            .and_then(|factor| self.initial.checked_mul(factor))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+        if self.jitter {
+            Some(delay.mul_f64(random()))
+        } else {
+            Some(delay)
+        }
+    }
+}
+
+/// Tokens earned by calls and spent by retries
+struct BudgetState {
+    tokens: f64,
+    last: Instant,
+}
+
+/// Token budget shared by several retrying stages, so correlated failures can't turn into a
+/// retry storm: every call deposits a fraction of a token, every retry withdraws a whole one
+#[derive(Clone)]
+pub struct RetryBudget {
+    state: Arc<Mutex<BudgetState>>,
+    ratio: f64,
+    min_per_sec: f64,
+    max_tokens: f64,
+}
+
+impl RetryBudget {
+    /// Creates a new empty budget allowing roughly `ratio` retries per call (e.g. 0.2 for 20%)
+    pub fn new(ratio: f64) -> Self {
+        RetryBudget {
+            state: Arc::new(Mutex::new(BudgetState {
+                tokens: 0.0,
+                last: Instant::now(),
+            })),
+            ratio: ratio.max(0.0),
+            min_per_sec: 0.0,
+            max_tokens: 100.0,
+        }
+    }
+
+    /// Refills the budget with `n` tokens per second on top of the deposits, so stages with
+    /// little traffic can still retry
+    pub fn min_per_second(mut self, n: u32) -> Self {
+        self.min_per_sec = f64::from(n);
+        self
+    }
+
+    /// Caps the tokens the budget can accumulate, bounding the retries of a burst of failures
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = f64::from(max_tokens);
+        self
+    }
+
+    /// Returns the number of whole retries currently permitted
+    pub fn available(&self) -> u32 {
+        self.update(0.0) as u32
+    }
+
+    /// Records a call, depositing its share of a retry
+    fn deposit(&self) {
+        self.update(self.ratio);
+    }
+
+    /// Takes a token for a retry, or returns false when the budget is exhausted
+    fn try_withdraw(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Adds the amount and the reserve refilled since the last update, returning the balance
+    fn update(&self, amount: f64) -> f64 {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        state.tokens = (state.tokens + amount).min(self.max_tokens);
+        state.tokens
+    }
+
+    /// Adds the reserve accumulated since the last update
+    fn refill(&self, state: &mut BudgetState) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.min_per_sec).min(self.max_tokens);
+        state.last = now;
+    }
+}
+
+/// Re-invokes a fallible stage according to the backoff policy until it succeeds
+pub struct RetryMiddleware<Args, I, O, E> {
+    stage: Arc<dyn DynTransform<Args, I, Result<O, E>>>,
+    policy: Arc<dyn Backoff>,
+    budget: Option<RetryBudget>,
+}
+
+impl<Args, I, O, E> RetryMiddleware<Args, I, O, E> {
+    /// Only retries while the shared budget has tokens, depositing into it on every call
+    pub fn budget(mut self, budget: &RetryBudget) -> Self {
+        self.budget = Some(budget.clone());
+        self
+    }
+}
+
+/// Implements the transform trait on the retry middleware (for downstream)
+impl<Args, I, O, E> Transform<(I, Result<O, E>), I, Result<O, E>> for RetryMiddleware<Args, I, O, E>
+where
+    Args: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> Result<O, E> {
+        if let Some(budget) = &self.budget {
+            budget.deposit();
+        }
+        let mut retry = 0;
+        loop {
+            match self.stage.transform(input.clone()).await {
+                Ok(output) => return Ok(output),
+                Err(e) => {
+                    retry += 1;
+                    let delay = self.policy.next_delay(retry).filter(|_| {
+                        self.budget
+                            .as_ref()
+                            .is_none_or(|budget| budget.try_withdraw())
+                    });
+                    match delay {
+                        Some(delay) => rt::sleep(delay).await,
+                        None => return Err(e),
+                    }
+                }
+            }
+        }
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the retry middleware
+impl<Args, I, O, E> Middleware<I, Result<O, E>> for RetryMiddleware<Args, I, O, E>
+where
+    Args: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> Result<O, E> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Creates a new retry middleware around a fallible stage
+pub fn retry<Args, I, O, E>(
+    stage: impl Transform<Args, I, Result<O, E>>,
+    policy: impl Backoff,
+) -> RetryMiddleware<Args, I, O, E>
+where
+    Args: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    RetryMiddleware {
+        stage: Arc::new(stage),
+        policy: Arc::new(policy),
+        budget: None,
+    }
+}
+
+impl<T, Args, I, O, E> Pied<T, Args, I, Result<O, E>>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    /// Retries the whole pipeline according to the backoff policy when it fails
+    pub fn retry(self, policy: impl Backoff) -> Pied<T, Args, I, Result<O, E>> {
+        self.rebuild(|pied| retry(pied, policy))
+    }
+
+    /// Retries the whole pipeline according to the backoff policy when it fails, as long as the
+    /// shared budget permits
+    pub fn retry_with_budget(
+        self,
+        policy: impl Backoff,
+        budget: &RetryBudget,
+    ) -> Pied<T, Args, I, Result<O, E>> {
+        self.rebuild(|pied| retry(pied, policy).budget(budget))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_exponential_backoff() {
+        let policy = ExponentialBackoff::new(Duration::from_millis(10))
+            .max_attempts(5)
+            .max_delay(Duration::from_millis(50));
+        assert_eq!(Some(Duration::from_millis(10)), policy.next_delay(0));
+        assert_eq!(Some(Duration::from_millis(10)), policy.next_delay(1));
+        assert_eq!(Some(Duration::from_millis(20)), policy.next_delay(2));
+        assert_eq!(Some(Duration::from_millis(40)), policy.next_delay(3));
+        assert_eq!(Some(Duration::from_millis(50)), policy.next_delay(4));
+        assert_eq!(None, policy.next_delay(5));
+
+        let policy = policy.jitter();
+        for retry in 1..5 {
+            assert!(policy.next_delay(retry).unwrap() <= Duration::from_millis(50));
+        }
+    }
+
+    async fn render(r: Result<i32, &'static str>) -> Result<String, &'static str> {
+        r.map(|i| i.to_string())
+    }
+
+    #[async_std::test]
+    async fn test_retry_until_success() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let flaky = move |i: i32| {
+            let counter = counter.clone();
+            async move {
+                match counter.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => Err("unavailable"),
+                    _ => Ok(i * 2),
+                }
+            }
+        };
+        let policy = ExponentialBackoff::new(Duration::from_millis(1)).max_attempts(3);
+        let m = (retry(flaky, policy), render).pipe();
+        assert_eq!(Ok(String::from("42")), m.call(21).await);
+        assert_eq!(3, calls.load(Ordering::SeqCst));
+    }
+
+    #[async_std::test]
+    async fn test_pied_retry_gives_up() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let failing = move |_: i32| {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Err::<i32, _>("down")
+            }
+        };
+        let m = (|i: i32| async move { i }, failing)
+            .pipe()
+            .retry(ExponentialBackoff::new(Duration::from_millis(1)).max_attempts(4));
+        assert_eq!(Err("down"), m.call(1).await);
+        assert_eq!(4, calls.load(Ordering::SeqCst));
+    }
+
+    #[async_std::test]
+    async fn test_retry_budget() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let failing = move |_: i32| {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Err::<i32, _>("down")
+            }
+        };
+        let policy = ExponentialBackoff::new(Duration::from_millis(1)).max_attempts(4);
+        let budget = RetryBudget::new(0.5);
+        let a = retry(failing.clone(), policy.clone()).budget(&budget);
+        let b = (|i: i32| async move { i }, failing)
+            .pipe()
+            .retry_with_budget(policy, &budget);
+
+        // two calls deposit one token in total, so only one retry is spent across both stages
+        assert_eq!(Err("down"), a.call(1).await);
+        assert_eq!(Err("down"), b.call(1).await);
+        assert_eq!(3, calls.load(Ordering::SeqCst));
+        assert_eq!(0, budget.available());
+
+        let budget = RetryBudget::new(0.0).min_per_second(1000).max_tokens(2);
+        async_std::task::sleep(Duration::from_millis(10)).await;
+        assert_eq!(2, budget.available());
+    }
+}
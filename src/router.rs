@@ -0,0 +1,160 @@
+//! Content-based routing between sub-pipelines.
+
+use std::{borrow::Cow, collections::HashMap, fmt::Debug, hash::Hash, sync::Arc};
+
+use crate::{into_middleware, BoxMiddleware, DynMiddleware, Middleware, StageInfo, Transform};
+
+/// Dispatches the input to the stage registered under the key computed from it
+pub struct Router<K, I, O> {
+    key: Box<dyn Fn(&I) -> K + Send + Sync>,
+    routes: HashMap<K, usize>,
+    /// Registered stages labelled with their keys, in registration order
+    stages: Vec<(Cow<'static, str>, BoxMiddleware<I, O>)>,
+    default: Arc<dyn DynMiddleware<I, O>>,
+}
+
+impl<K, I, O> Router<K, I, O>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Creates a new router computing keys with the function, using the default for unknown keys
+    pub fn new<Args>(
+        key: impl Fn(&I) -> K + Send + Sync + 'static,
+        default: impl Transform<Args, I, O>,
+    ) -> Self
+    where
+        Args: Send + Sync + 'static,
+    {
+        Router {
+            key: Box::new(key),
+            routes: HashMap::new(),
+            stages: Vec::new(),
+            default: into_middleware(default),
+        }
+    }
+
+    /// Registers the stage for the key, replacing any stage previously registered for it; the
+    /// key labels the stage in introspection
+    pub fn route<Args>(mut self, key: K, stage: impl Transform<Args, I, O>) -> Self
+    where
+        K: Debug,
+        Args: Send + Sync + 'static,
+    {
+        let route = (format!("{:?}", key).into(), into_middleware(stage));
+        match self.routes.get(&key) {
+            Some(&index) => self.stages[index] = route,
+            None => {
+                self.routes.insert(key, self.stages.len());
+                self.stages.push(route);
+            }
+        }
+        self
+    }
+}
+
+/// Implements the transform trait on the router (for downstream)
+impl<K, I, O> Transform<(I, O), I, O> for Router<K, I, O>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let key = (self.key)(&input);
+        let stage = match self.routes.get(&key) {
+            Some(&index) => &self.stages[index].1,
+            None => &self.default,
+        };
+        stage.call(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        let routes = self
+            .stages
+            .iter()
+            .map(|(key, stage)| (key.clone(), stage.stages()));
+        let default = ("default".into(), self.default.stages());
+        vec![StageInfo::branching::<I, O>(
+            "router",
+            routes.chain([default]).collect(),
+        )]
+    }
+}
+
+/// Implements the middleware trait on the router
+impl<K, I, O> Middleware<I, O> for Router<K, I, O>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    #[derive(Debug, Hash, PartialEq, Eq)]
+    enum Kind {
+        Create,
+        Delete,
+        Other,
+    }
+
+    fn kind(message: &str) -> Kind {
+        match message.split(':').next() {
+            Some("create") => Kind::Create,
+            Some("delete") => Kind::Delete,
+            _ => Kind::Other,
+        }
+    }
+
+    async fn create(message: String) -> String {
+        format!("created {}", &message[7..])
+    }
+
+    async fn unknown(message: String) -> String {
+        format!("unknown {}", message)
+    }
+
+    async fn trim(message: String) -> String {
+        message.trim().to_string()
+    }
+
+    #[async_std::test]
+    async fn test_router() {
+        let router = Router::new(|m: &String| kind(m), unknown)
+            .route(Kind::Create, create)
+            .route(
+                Kind::Delete,
+                (trim, |m: String| async move { m.len().to_string() }).pipe(),
+            );
+        let m = (trim, router).pipe();
+        assert_eq!(
+            String::from("created a"),
+            m.call(String::from(" create:a ")).await
+        );
+        assert_eq!(String::from("8"), m.call(String::from("delete:b")).await);
+        assert_eq!(
+            String::from("unknown ping"),
+            m.call(String::from("ping")).await
+        );
+
+        let labels: Vec<_> = m.stages()[1]
+            .branches
+            .iter()
+            .map(|(label, _)| label.clone())
+            .collect();
+        assert_eq!(vec!["Create", "Delete", "default"], labels);
+    }
+}
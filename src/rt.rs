@@ -0,0 +1,248 @@
+//! Runtime utilities used by the time-based, spawning and blocking middleware.
+//!
+//! Every primitive goes through the `Runtime` selected by the `async-std`, `tokio` or `smol`
+//! feature (in that order of preference), falling back to a runtime-agnostic timer thread and
+//! shared thread pools when none is enabled. The `wasm` feature swaps the fallbacks for browser timers
+//! and local tasks; browser workers can't block or spawn threads, so there is no blocking pool
+//! on wasm32.
+
+use futures::{
+    future::{select, BoxFuture, Either},
+    stream, Stream,
+};
+use std::{future::Future, pin::pin, time::Duration};
+#[cfg(all(
+    not(any(feature = "async-std", feature = "tokio", feature = "smol")),
+    not(all(feature = "wasm", target_arch = "wasm32"))
+))]
+use {futures::executor::ThreadPool, std::sync::OnceLock};
+
+use crate::Elapsed;
+
+#[cfg(not(feature = "wasm"))]
+pub(crate) use std::time::Instant;
+#[cfg(feature = "wasm")]
+pub(crate) use web_time::Instant;
+
+/// Executor primitives the middleware is written against, so no combinator hard-codes a runtime
+pub(crate) trait Runtime {
+    /// Runs the future to completion in the background
+    fn spawn(future: BoxFuture<'static, ()>);
+
+    /// Waits for the given duration without blocking the executor
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send;
+
+    /// Ticks once every period, the first tick one period from now, without drifting
+    fn interval(period: Duration) -> impl Stream<Item = ()> + Send {
+        stream::unfold(Instant::now(), move |last| async move {
+            let next = last + period;
+            Self::sleep(next.saturating_duration_since(Instant::now())).await;
+            Some(((), next))
+        })
+    }
+
+    /// Runs the blocking function off the executor, resuming any panic it raised
+    #[cfg(not(target_arch = "wasm32"))]
+    fn spawn_blocking<F, R>(f: F) -> impl Future<Output = R> + Send
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static;
+}
+
+/// Runtime backed by async-std
+#[cfg(feature = "async-std")]
+pub(crate) struct AsyncStd;
+
+#[cfg(feature = "async-std")]
+impl Runtime for AsyncStd {
+    fn spawn(future: BoxFuture<'static, ()>) {
+        async_std::task::spawn(future);
+    }
+
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+        async_std::task::sleep(duration)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn spawn_blocking<F, R>(f: F) -> impl Future<Output = R> + Send
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        async_std::task::spawn_blocking(f)
+    }
+}
+
+/// Runtime backed by tokio, which must be running on the calling thread
+#[cfg(all(feature = "tokio", not(feature = "async-std")))]
+pub(crate) struct Tokio;
+
+#[cfg(all(feature = "tokio", not(feature = "async-std")))]
+impl Runtime for Tokio {
+    fn spawn(future: BoxFuture<'static, ()>) {
+        tokio::spawn(future);
+    }
+
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+        tokio::time::sleep(duration)
+    }
+
+    fn interval(period: Duration) -> impl Stream<Item = ()> + Send {
+        let ticks = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        stream::unfold(ticks, |mut ticks| async move {
+            ticks.tick().await;
+            Some(((), ticks))
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn spawn_blocking<F, R>(f: F) -> R
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        match tokio::task::spawn_blocking(f).await {
+            Ok(output) => output,
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+}
+
+/// Runtime backed by smol's global executor
+#[cfg(all(feature = "smol", not(any(feature = "async-std", feature = "tokio"))))]
+pub(crate) struct Smol;
+
+#[cfg(all(feature = "smol", not(any(feature = "async-std", feature = "tokio"))))]
+impl Runtime for Smol {
+    fn spawn(future: BoxFuture<'static, ()>) {
+        smol::spawn(future).detach();
+    }
+
+    async fn sleep(duration: Duration) {
+        smol::Timer::after(duration).await;
+    }
+
+    fn interval(period: Duration) -> impl Stream<Item = ()> + Send {
+        futures::StreamExt::map(smol::Timer::interval(period), |_| ())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn spawn_blocking<F, R>(f: F) -> impl Future<Output = R> + Send
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        smol::unblock(f)
+    }
+}
+
+/// Runtime-agnostic fallback using a shared timer thread, a thread pool sized to the number of
+/// CPUs for tasks and one twice that size for blocking calls, each created on first use, so
+/// neither spawns a thread per call
+#[cfg(not(any(feature = "async-std", feature = "tokio", feature = "smol")))]
+pub(crate) struct Threads;
+
+#[cfg(not(any(feature = "async-std", feature = "tokio", feature = "smol")))]
+impl Runtime for Threads {
+    #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+    fn spawn(future: BoxFuture<'static, ()>) {
+        static POOL: OnceLock<ThreadPool> = OnceLock::new();
+        pool(&POOL, "async-middleware-", 1).spawn_ok(future);
+    }
+
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    fn spawn(future: BoxFuture<'static, ()>) {
+        wasm_bindgen_futures::spawn_local(future);
+    }
+
+    async fn sleep(duration: Duration) {
+        futures_timer::Delay::new(duration).await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn spawn_blocking<F, R>(f: F) -> R
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        static POOL: OnceLock<ThreadPool> = OnceLock::new();
+        let (tx, rx) = futures::channel::oneshot::channel();
+        pool(&POOL, "async-middleware-blocking-", 2).spawn_ok(async move {
+            // caught so the panic is resumed by the caller rather than killing a pool thread
+            let _ = tx.send(std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)));
+        });
+        match rx.await.expect("blocking pool dropped the call") {
+            Ok(output) => output,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+/// Returns the pool in the cell, starting it with `per_cpu` threads per CPU on first use
+#[cfg(all(
+    not(any(feature = "async-std", feature = "tokio", feature = "smol")),
+    not(all(feature = "wasm", target_arch = "wasm32"))
+))]
+fn pool(
+    cell: &'static OnceLock<ThreadPool>,
+    name_prefix: &str,
+    per_cpu: usize,
+) -> &'static ThreadPool {
+    cell.get_or_init(|| {
+        let cpus = std::thread::available_parallelism().map_or(1, |cpus| cpus.get());
+        ThreadPool::builder()
+            .pool_size(cpus * per_cpu)
+            .name_prefix(name_prefix)
+            .create()
+            .expect("failed to start a thread pool")
+    })
+}
+
+/// Runtime selected by the enabled features
+#[cfg(feature = "async-std")]
+pub(crate) type Current = AsyncStd;
+/// Runtime selected by the enabled features
+#[cfg(all(feature = "tokio", not(feature = "async-std")))]
+pub(crate) type Current = Tokio;
+/// Runtime selected by the enabled features
+#[cfg(all(feature = "smol", not(any(feature = "async-std", feature = "tokio"))))]
+pub(crate) type Current = Smol;
+/// Runtime selected by the enabled features
+#[cfg(not(any(feature = "async-std", feature = "tokio", feature = "smol")))]
+pub(crate) type Current = Threads;
+
+/// Runs the future to completion in the background on the current runtime
+pub(crate) fn spawn(future: impl Future<Output = ()> + Send + 'static) {
+    Current::spawn(Box::pin(future))
+}
+
+/// Waits for the given duration without blocking the executor
+pub(crate) async fn sleep(duration: Duration) {
+    Current::sleep(duration).await
+}
+
+/// Ticks once every period, the first tick one period from now
+pub(crate) fn interval(period: Duration) -> impl Stream<Item = ()> + Send {
+    Current::interval(period)
+}
+
+/// Waits for the future to complete, giving up once the duration has elapsed
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    match select(pin!(future), pin!(sleep(duration))).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(Elapsed(())),
+    }
+}
+
+/// Runs the blocking function off the executor, resuming any panic it raised
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn spawn_blocking<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    Current::spawn_blocking(f).await
+}
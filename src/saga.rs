@@ -0,0 +1,161 @@
+//! Multi-step workflows with compensating actions.
+
+use crate::{into_middleware, BoxMiddleware, Middleware, StageInfo, Transform};
+
+/// Step of a saga paired with the action compensating it
+type SagaStep<T, E> = (BoxMiddleware<T, Result<T, E>>, BoxMiddleware<T, ()>);
+
+/// Fallible steps over `T` that each register a compensating action; when a step fails, the
+/// compensations of the completed steps run in reverse order before the error is returned
+pub struct Saga<T, E> {
+    steps: Vec<SagaStep<T, E>>,
+}
+
+impl<T, E> Saga<T, E>
+where
+    T: Clone + Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    /// Creates a new empty saga, which succeeds with its input unchanged
+    pub fn new() -> Self {
+        Saga { steps: Vec::new() }
+    }
+
+    /// Appends a step along with the action undoing it, which receives the step's output
+    pub fn step<Args, Args2>(
+        mut self,
+        action: impl Transform<Args, T, Result<T, E>>,
+        compensate: impl Transform<Args2, T, ()>,
+    ) -> Self
+    where
+        Args: Send + Sync + 'static,
+        Args2: Send + Sync + 'static,
+    {
+        self.steps
+            .push((into_middleware(action), into_middleware(compensate)));
+        self
+    }
+}
+
+impl<T, E> Default for Saga<T, E>
+where
+    T: Clone + Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Saga::new()
+    }
+}
+
+/// Implements the transform trait on the saga (for downstream)
+impl<T, E> Transform<(T, Result<T, E>), T, Result<T, E>> for Saga<T, E>
+where
+    T: Clone + Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> Result<T, E> {
+        let mut completed = Vec::with_capacity(self.steps.len());
+        let mut value = input;
+        for (action, compensate) in &self.steps {
+            match action.call(value).await {
+                Ok(output) => {
+                    completed.push((compensate, output.clone()));
+                    value = output;
+                }
+                Err(e) => {
+                    for (compensate, output) in completed.into_iter().rev() {
+                        compensate.call(output).await;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(value)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.steps
+            .iter()
+            .flat_map(|(action, _)| action.stages())
+            .collect()
+    }
+}
+
+/// Implements the middleware trait on the saga
+impl<T, E> Middleware<T, Result<T, E>> for Saga<T, E>
+where
+    T: Clone + Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> Result<T, E> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Order {
+        amount: u32,
+        charged: bool,
+        shipped: bool,
+    }
+
+    #[async_std::test]
+    async fn test_saga_compensates_in_reverse() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (l1, l2) = (log.clone(), log.clone());
+        let saga = Saga::new()
+            .step(
+                |order: Order| async move {
+                    Ok(Order {
+                        charged: true,
+                        ..order
+                    })
+                },
+                move |order: Order| {
+                    l1.lock().unwrap().push(format!("refund {}", order.amount));
+                    async {}
+                },
+            )
+            .step(
+                |order: Order| async move {
+                    if order.amount > 100 {
+                        Err(String::from("out of stock"))
+                    } else {
+                        Ok(Order {
+                            shipped: true,
+                            ..order
+                        })
+                    }
+                },
+                move |_: Order| {
+                    l2.lock().unwrap().push(String::from("recall"));
+                    async {}
+                },
+            );
+
+        let order = Order {
+            amount: 50,
+            charged: false,
+            shipped: false,
+        };
+        let done = saga.call(order.clone()).await.unwrap();
+        assert!(done.charged && done.shipped);
+        assert!(log.lock().unwrap().is_empty());
+
+        let large = Order {
+            amount: 500,
+            ..order
+        };
+        assert_eq!(Err(String::from("out of stock")), saga.call(large).await);
+        assert_eq!(vec![String::from("refund 500")], *log.lock().unwrap());
+    }
+}
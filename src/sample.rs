@@ -0,0 +1,83 @@
+//! Passing only a fraction of items downstream.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{filter, util::random, Filter, Pied};
+
+/// Creates a new stage keeping the first of every `n` inputs, dropping the others as `None`
+pub fn sample<T>(n: usize) -> Filter<T>
+where
+    T: Send + Sync + 'static,
+{
+    let (n, seen) = (n.max(1), AtomicUsize::new(0));
+    filter(move |_: &T| seen.fetch_add(1, Ordering::Relaxed).is_multiple_of(n))
+}
+
+/// Creates a new stage keeping each input with the given probability (0 to 1), dropping the
+/// others as `None`
+pub fn sample_rate<T>(rate: f64) -> Filter<T>
+where
+    T: Send + Sync + 'static,
+{
+    filter(move |_: &T| random() < rate)
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Keeps the first of every `n` outputs of the pipeline, dropping the others as `None`
+    pub fn sample(self, n: usize) -> Pied<T, Args, I, Option<O>> {
+        let (n, seen) = (n.max(1), AtomicUsize::new(0));
+        self.filter(move |_| seen.fetch_add(1, Ordering::Relaxed).is_multiple_of(n))
+    }
+
+    /// Keeps each output of the pipeline with the given probability (0 to 1), dropping the
+    /// others as `None`
+    pub fn sample_rate(self, rate: f64) -> Pied<T, Args, I, Option<O>> {
+        self.filter(move |_| random() < rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Middleware, Piper};
+
+    async fn double(i: i32) -> i32 {
+        i * 2
+    }
+
+    #[async_std::test]
+    async fn test_sample() {
+        let m = (double, sample(3)).pipe();
+        let mut kept = Vec::new();
+        for i in 0..7 {
+            kept.extend(m.call(i).await);
+        }
+        assert_eq!(vec![0, 6, 12], kept);
+
+        let m = (double, double).pipe().sample(2);
+        assert_eq!(Some(4), m.call(1).await);
+        assert_eq!(None, m.call(2).await);
+    }
+
+    #[async_std::test]
+    async fn test_sample_rate() {
+        let m = (double, sample_rate(0.0)).pipe();
+        assert_eq!(None, m.call(1).await);
+
+        let m = (double, double).pipe().sample_rate(1.0);
+        assert_eq!(Some(4), m.call(1).await);
+
+        let m = (double, sample_rate(0.5)).pipe();
+        let mut kept = 0;
+        for i in 0..1000 {
+            kept += m.call(i).await.is_some() as usize;
+        }
+        assert!((350..650).contains(&kept));
+    }
+}
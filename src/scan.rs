@@ -0,0 +1,108 @@
+//! Accumulating stages for stream processing.
+
+use async_lock::Mutex;
+use futures::{Stream, StreamExt};
+use std::future::Future;
+
+use crate::{Middleware, Pied, Transform};
+
+/// Stage carrying an accumulator across items, emitting the accumulator after every item
+pub struct Scan<A, F> {
+    acc: Mutex<A>,
+    f: F,
+}
+
+/// Creates a new scan stage folding each input into the accumulator and emitting the
+/// intermediate result; calls are serialized so items are folded one at a time
+pub fn scan<A, F, Fut, T>(initial: A, f: F) -> Scan<A, F>
+where
+    F: Fn(A, T) -> Fut,
+    Fut: Future<Output = A>,
+{
+    Scan {
+        acc: Mutex::new(initial),
+        f,
+    }
+}
+
+/// Implements the transform trait on the scan stage (for downstream)
+impl<A, F, Fut, T> Transform<(T, A), T, A> for Scan<A, F>
+where
+    A: Clone + Send + Sync + 'static,
+    F: Fn(A, T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = A> + Send + 'static,
+    T: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> A {
+        let mut acc = self.acc.lock().await;
+        *acc = (self.f)(acc.clone(), input).await;
+        acc.clone()
+    }
+}
+
+/// Implements the middleware trait on the scan stage
+impl<A, F, Fut, T> Middleware<T, A> for Scan<A, F>
+where
+    A: Clone + Send + Sync + 'static,
+    F: Fn(A, T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = A> + Send + 'static,
+    T: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> A {
+        self.transform(input).await
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Applies the pipeline to every item of the stream in order, folding the outputs into
+    /// the accumulator and returning it once the stream ends
+    pub async fn fold<S, A, F, Fut>(&self, stream: S, initial: A, mut f: F) -> A
+    where
+        S: Stream<Item = I>,
+        F: FnMut(A, O) -> Fut,
+        Fut: Future<Output = A>,
+    {
+        let mut stream = std::pin::pin!(stream);
+        let mut acc = initial;
+        while let Some(input) = stream.next().await {
+            acc = f(acc, self.middleware.call(input).await).await;
+        }
+        acc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use futures::stream;
+
+    async fn double(i: i32) -> i32 {
+        i * 2
+    }
+
+    #[async_std::test]
+    async fn test_scan() {
+        let m = (double, scan(0, |acc, i: i32| async move { acc + i })).pipe();
+        let totals: Vec<i32> = m.call_stream(stream::iter(1..5), 1).collect().await;
+        assert_eq!(vec![2, 6, 12, 20], totals);
+    }
+
+    #[async_std::test]
+    async fn test_fold() {
+        let m = (double, double).pipe();
+        let total = m
+            .fold(stream::iter(1..4), Vec::new(), |mut acc, i| async move {
+                acc.push(i);
+                acc
+            })
+            .await;
+        assert_eq!(vec![4, 8, 12], total);
+    }
+}
@@ -0,0 +1,114 @@
+//! Sources triggered on a schedule, feeding spawned pipelines.
+
+use futures::{
+    channel::mpsc::{Receiver, Sender},
+    future::ready,
+    SinkExt, Stream, StreamExt,
+};
+use std::{sync::Arc, time::Duration};
+
+use crate::{identity, rt, spawned, DynTransform, Spawned, Transform};
+
+/// Creates a spawned pipeline invoking the source stage once every period and sending its output
+/// downstream; inputs sent to the handle are ignored, and closing it stops the schedule
+pub fn interval_source<Args, O>(
+    period: Duration,
+    producer: impl Transform<Args, (), O>,
+) -> Spawned<(), O>
+where
+    Args: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    scheduled(rt::interval(period), producer)
+}
+
+/// Creates a spawned pipeline invoking the source stage at every time matching the cron
+/// expression (five fields, or six with leading seconds, in UTC); inputs sent to the handle are
+/// ignored, and closing it stops the schedule
+#[cfg(feature = "cron")]
+pub fn cron_source<Args, O>(
+    expression: &str,
+    producer: impl Transform<Args, (), O>,
+) -> Result<Spawned<(), O>, croner::errors::CronError>
+where
+    Args: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    let cron = croner::Cron::new(expression)
+        .with_seconds_optional()
+        .parse()?;
+    let ticks = futures::stream::unfold((cron, chrono::Utc::now()), |(cron, last)| async move {
+        // search from the last tick too, so a timer firing early can't repeat an occurrence
+        let now = chrono::Utc::now();
+        let next = cron.find_next_occurrence(&now.max(last), false).ok()?;
+        rt::sleep((next - now).to_std().unwrap_or_default()).await;
+        Some(((), (cron, next)))
+    });
+    Ok(scheduled(ticks, producer))
+}
+
+/// Feeds one output of the producer downstream per tick until the handle is closed
+fn scheduled<Args, O>(
+    ticks: impl Stream<Item = ()> + Send + 'static,
+    producer: impl Transform<Args, (), O>,
+) -> Spawned<(), O>
+where
+    Args: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    let producer: Arc<dyn DynTransform<Args, (), O>> = Arc::new(producer);
+    spawned(identity()).task(move |rx: Receiver<()>, mut tx: Sender<O>, _| {
+        Box::pin(async move {
+            let mut ticks = Box::pin(ticks.take_until(rx.for_each(|_| ready(()))));
+            while ticks.next().await.is_some() {
+                if tx.send(producer.transform(()).await).await.is_err() {
+                    break;
+                }
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task;
+    use futures::future::BoxFuture;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn spawner(future: BoxFuture<'static, ()>) {
+        task::spawn(future);
+    }
+
+    #[async_std::test]
+    async fn test_interval_source() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let producer = move || {
+            let counter = counter.clone();
+            async move { counter.fetch_add(1, Ordering::SeqCst) as u64 }
+        };
+        let mut handle = interval_source(Duration::from_millis(10), producer)
+            .stage(|i: u64| async move { i * 32 })
+            .spawn(1, spawner);
+
+        assert_eq!(Some(0), handle.recv().await);
+        assert_eq!(Some(32), handle.recv().await);
+        assert_eq!(Some(64), handle.recv().await);
+        handle.close();
+        while handle.recv().await.is_some() {}
+        assert!(count.load(Ordering::SeqCst) < 6);
+    }
+
+    #[cfg(feature = "cron")]
+    #[async_std::test]
+    async fn test_cron_source() {
+        assert!(cron_source("not a cron", || async { 1 }).is_err());
+
+        let mut handle = cron_source("* * * * * *", || async { 1 })
+            .unwrap()
+            .spawn(1, spawner);
+        assert_eq!(Some(1), handle.recv().await);
+        handle.close();
+    }
+}
@@ -0,0 +1,107 @@
+//! Tower service interop.
+
+use std::{
+    convert::Infallible,
+    future::{poll_fn, Future},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tower::Service;
+
+use crate::{Middleware, Pied, Transform};
+
+/// Implements the tower service trait for a pipeline so it can be used in tower stacks
+impl<T, Args, I, O> Service<I> for Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    type Response = O;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<O, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, input: I) -> Self::Future {
+        let middleware = self.middleware.clone();
+        Box::pin(async move { Ok(middleware.call(input).await) })
+    }
+}
+
+/// Adapts a tower service into a transform, cloning the service for every call
+pub struct ServiceTransform<S> {
+    service: S,
+}
+
+/// Implements the transform trait for a tower service, waiting on readiness before each call
+impl<S, I> Transform<(I, Result<S::Response, S::Error>), I, Result<S::Response, S::Error>>
+    for ServiceTransform<S>
+where
+    S: Service<I> + Clone + Send + Sync + 'static,
+    S::Future: Send,
+    S::Response: Send + Sync + 'static,
+    S::Error: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> Result<S::Response, S::Error> {
+        let mut service = self.service.clone();
+        poll_fn(|cx| service.poll_ready(cx)).await?;
+        service.call(input).await
+    }
+}
+
+/// Implements the middleware trait for a tower service
+impl<S, I> Middleware<I, Result<S::Response, S::Error>> for ServiceTransform<S>
+where
+    S: Service<I> + Clone + Send + Sync + 'static,
+    S::Future: Send,
+    S::Response: Send + Sync + 'static,
+    S::Error: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> Result<S::Response, S::Error> {
+        self.transform(input).await
+    }
+}
+
+/// Creates a new transform from an existing tower service
+pub fn from_service<S>(service: S) -> ServiceTransform<S> {
+    ServiceTransform { service }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{try_convert, Piper};
+    use tower::{service_fn, ServiceExt};
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    async fn parse(s: String) -> Result<i32, String> {
+        s.parse().map_err(|_| s)
+    }
+
+    #[async_std::test]
+    async fn test_pied_as_service() {
+        let m = (multipler, stringer).pipe();
+        assert_eq!(Ok(String::from("64")), m.oneshot(2).await);
+    }
+
+    #[async_std::test]
+    async fn test_service_as_transform() {
+        let double = from_service(service_fn(|i: i32| async move { Ok::<_, String>(i * 2) }));
+        let m = try_convert(parse, double);
+        assert_eq!(Ok(42), m.call(String::from("21")).await);
+        assert_eq!(Err(String::from("x")), m.call(String::from("x")).await);
+    }
+}
@@ -0,0 +1,134 @@
+//! Mirroring traffic to a shadow sub-pipeline.
+
+use std::sync::Arc;
+
+use crate::{DynTransform, Middleware, Spawn, StageInfo, Transform};
+
+type MirrorObserver<O2> = Arc<dyn Fn(&O2) + Send + Sync>;
+
+/// Returns the output of the primary stage while mirroring a copy of every input to a shadow
+/// stage on a spawned task
+pub struct Shadow<A, B, I, O, O2> {
+    primary: Arc<dyn DynTransform<A, I, O>>,
+    mirror: Arc<dyn DynTransform<B, I, O2>>,
+    spawner: Arc<dyn Spawn>,
+    on_mirror: Option<MirrorObserver<O2>>,
+}
+
+/// Creates a new shadow stage answering with `primary` and sending a clone of each input to
+/// `mirror` on a task handed to the spawner, whose output is discarded
+pub fn shadow<A, B, I, O, O2>(
+    primary: impl Transform<A, I, O>,
+    mirror: impl Transform<B, I, O2>,
+    spawner: impl Spawn,
+) -> Shadow<A, B, I, O, O2>
+where
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    O2: Send + Sync + 'static,
+{
+    Shadow {
+        primary: Arc::new(primary),
+        mirror: Arc::new(mirror),
+        spawner: Arc::new(spawner),
+        on_mirror: None,
+    }
+}
+
+impl<A, B, I, O, O2> Shadow<A, B, I, O, O2> {
+    /// Observes the output of every mirrored call, e.g. to log failures of the shadow stage
+    pub fn on_mirror(mut self, f: impl Fn(&O2) + Send + Sync + 'static) -> Self {
+        self.on_mirror = Some(Arc::new(f));
+        self
+    }
+}
+
+/// Implements the transform trait on the shadow stage (for downstream)
+impl<A, B, I, O, O2> Transform<(I, O), I, O> for Shadow<A, B, I, O, O2>
+where
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    O2: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let mirror = self.mirror.clone();
+        let on_mirror = self.on_mirror.clone();
+        let mirrored = input.clone();
+        self.spawner.spawn(Box::pin(async move {
+            let output = mirror.transform(mirrored).await;
+            if let Some(f) = on_mirror {
+                f(&output);
+            }
+        }));
+        self.primary.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::branching::<I, O>(
+            "shadow",
+            vec![
+                ("primary".into(), self.primary.stages()),
+                ("mirror".into(), self.mirror.stages()),
+            ],
+        )]
+    }
+}
+
+/// Implements the middleware trait on the shadow stage
+impl<A, B, I, O, O2> Middleware<I, O> for Shadow<A, B, I, O, O2>
+where
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    O2: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task;
+    use futures::{channel::mpsc, future::BoxFuture, StreamExt};
+
+    fn spawner(future: BoxFuture<'static, ()>) {
+        task::spawn(future);
+    }
+
+    async fn primary(i: i32) -> i32 {
+        i + 1
+    }
+
+    async fn candidate(i: i32) -> Result<i32, String> {
+        if i < 0 {
+            Err(String::from("negative"))
+        } else {
+            Ok(i * 2)
+        }
+    }
+
+    #[async_std::test]
+    async fn test_shadow() {
+        let (tx, mut rx) = mpsc::unbounded();
+        let m = shadow(primary, candidate, spawner).on_mirror(move |output| {
+            let _ = tx.unbounded_send(output.clone());
+        });
+        assert_eq!(4, m.call(3).await);
+        assert_eq!(0, m.call(-1).await);
+
+        let mut mirrored = vec![rx.next().await.unwrap(), rx.next().await.unwrap()];
+        mirrored.sort();
+        assert_eq!(vec![Ok(6), Err(String::from("negative"))], mirrored);
+    }
+}
@@ -0,0 +1,114 @@
+//! Stages seeding a pipeline with its initial value.
+
+use async_lock::OnceCell;
+use std::future::Future;
+
+use crate::{Middleware, Transform};
+
+/// Source producing a clone of a constant value on every call
+#[derive(Clone)]
+pub struct Source<T> {
+    value: T,
+}
+
+/// Creates a new source yielding a clone of the value
+pub fn source<T>(value: T) -> Source<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    Source { value }
+}
+
+/// Implements the transform trait on the constant source (for downstream)
+impl<T> Transform<((), T), (), T> for Source<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    async fn transform(&self, _input: ()) -> T {
+        self.value.clone()
+    }
+}
+
+/// Implements the middleware trait on the constant source
+impl<T> Middleware<(), T> for Source<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    async fn call(&self, input: ()) -> T {
+        self.transform(input).await
+    }
+}
+
+/// Source computing its value on the first call and yielding clones of it afterwards
+pub struct LazySource<T, F> {
+    value: OnceCell<T>,
+    init: F,
+}
+
+/// Creates a new source initialized by the async function on first use; concurrent first calls
+/// wait for a single initialization
+pub fn lazy_source<T, F, Fut>(init: F) -> LazySource<T, F>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = T>,
+{
+    LazySource {
+        value: OnceCell::new(),
+        init,
+    }
+}
+
+/// Implements the transform trait on the lazy source (for downstream)
+impl<T, F, Fut> Transform<((), T), (), T> for LazySource<T, F>
+where
+    T: Clone + Send + Sync + 'static,
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = T> + Send + 'static,
+{
+    async fn transform(&self, _input: ()) -> T {
+        self.value.get_or_init(|| (self.init)()).await.clone()
+    }
+}
+
+/// Implements the middleware trait on the lazy source
+impl<T, F, Fut> Middleware<(), T> for LazySource<T, F>
+where
+    T: Clone + Send + Sync + 'static,
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = T> + Send + 'static,
+{
+    async fn call(&self, input: ()) -> T {
+        self.transform(input).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static LOADS: AtomicUsize = AtomicUsize::new(0);
+
+    async fn len(s: String) -> usize {
+        s.len()
+    }
+
+    #[async_std::test]
+    async fn test_sources() {
+        let m = (source(String::from("seed")), len).pipe();
+        assert_eq!(4, m.call(()).await);
+
+        let m = (
+            lazy_source(|| async {
+                LOADS.fetch_add(1, Ordering::SeqCst);
+                String::from("config")
+            }),
+            len,
+        )
+            .pipe();
+        assert_eq!(6, m.call(()).await);
+        assert_eq!(6, m.call(()).await);
+        assert_eq!(1, LOADS.load(Ordering::SeqCst));
+    }
+}
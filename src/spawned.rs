@@ -0,0 +1,377 @@
+//! Channel-backed staged execution.
+
+use futures::{
+    channel::{
+        mpsc::{self, Receiver, SendError, Sender},
+        oneshot,
+    },
+    future::{abortable, join_all, AbortHandle, BoxFuture},
+    SinkExt, StreamExt,
+};
+use std::{future::Future, sync::Arc, time::Duration};
+
+use crate::{rt, DynTransform, Elapsed, Transform};
+
+/// Spawns the task of a stage onto an executor
+pub trait Spawn: Send + Sync + 'static {
+    fn spawn(&self, future: BoxFuture<'static, ()>);
+}
+
+/// Spawn implementation for any function that hands the future off to an executor
+impl<F> Spawn for F
+where
+    F: Fn(BoxFuture<'static, ()>) + Send + Sync + 'static,
+{
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        (self)(future)
+    }
+}
+
+/// Spawner handing the tasks to the runtime selected by the enabled features
+pub fn runtime_spawner() -> impl Spawn {
+    |future: BoxFuture<'static, ()>| rt::spawn(future)
+}
+
+type BuildStages<I, O> =
+    Box<dyn FnOnce(Receiver<I>, usize, &mut Vec<BoxFuture<'static, ()>>) -> Receiver<O> + Send>;
+
+type Hook = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send>;
+
+/// Builder for a pipeline where each stage runs on its own task, connected by bounded channels
+pub struct Spawned<I, O> {
+    build: BuildStages<I, O>,
+    on_start: Vec<Hook>,
+    on_stop: Vec<Hook>,
+}
+
+/// Creates the task loop that feeds each received item through the stage
+pub(crate) fn stage_task<Args, A, B>(
+    stage: Arc<dyn DynTransform<Args, A, B>>,
+    mut rx: Receiver<A>,
+    mut tx: Sender<B>,
+) -> BoxFuture<'static, ()>
+where
+    Args: Send + Sync + 'static,
+    A: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+{
+    Box::pin(async move {
+        while let Some(input) = rx.next().await {
+            let output = stage.transform(input).await;
+            if tx.send(output).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Creates a new spawned pipeline starting from the given stage
+pub fn spawned<Args, I, O>(stage: impl Transform<Args, I, O>) -> Spawned<I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    let stage: Arc<dyn DynTransform<Args, I, O>> = Arc::new(stage);
+    Spawned {
+        build: Box::new(move |rx, capacity, tasks| {
+            let (tx, next) = mpsc::channel(capacity);
+            tasks.push(stage_task(stage, rx, tx));
+            next
+        }),
+        on_start: Vec::new(),
+        on_stop: Vec::new(),
+    }
+}
+
+impl<I, O> Spawned<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Appends a stage that runs on its own task, receiving the output of the previous stage
+    pub fn stage<Args, O2>(self, stage: impl Transform<Args, O, O2>) -> Spawned<I, O2>
+    where
+        Args: Send + Sync + 'static,
+        O2: Send + Sync + 'static,
+    {
+        let stage: Arc<dyn DynTransform<Args, O, O2>> = Arc::new(stage);
+        self.task(move |rx, tx, _| stage_task(stage, rx, tx))
+    }
+
+    /// Appends a task reading the output of the previous stage and writing to the next one,
+    /// given the capacity of the channels between stages
+    pub(crate) fn task<O2, F>(self, task: F) -> Spawned<I, O2>
+    where
+        O2: Send + Sync + 'static,
+        F: FnOnce(Receiver<O>, Sender<O2>, usize) -> BoxFuture<'static, ()> + Send + 'static,
+    {
+        self.tasks(move |rx, tx, capacity| vec![task(rx, tx, capacity)])
+    }
+
+    /// Appends a group of tasks sharing the output of the previous stage and the input of the
+    /// next one, each spawned separately
+    pub(crate) fn tasks<O2, F>(self, tasks: F) -> Spawned<I, O2>
+    where
+        O2: Send + Sync + 'static,
+        F: FnOnce(Receiver<O>, Sender<O2>, usize) -> Vec<BoxFuture<'static, ()>> + Send + 'static,
+    {
+        let build = self.build;
+        Spawned {
+            build: Box::new(move |rx, capacity, spawned| {
+                let rx = build(rx, capacity, spawned);
+                let (tx, next) = mpsc::channel(capacity);
+                spawned.extend(tasks(rx, tx, capacity));
+                next
+            }),
+            on_start: self.on_start,
+            on_stop: self.on_stop,
+        }
+    }
+
+    /// Runs the async hook before any stage starts processing, e.g. to open a file or socket
+    pub fn on_start<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_start.push(Box::new(move || Box::pin(hook())));
+        self
+    }
+
+    /// Runs the async hook once every stage has finished, whether the pipeline was shut down,
+    /// closed or its handle dropped, e.g. to flush and close resources
+    pub fn on_stop<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_stop.push(Box::new(move || Box::pin(hook())));
+        self
+    }
+
+    /// Spawns every stage with the spawner, bounding each channel between stages by `capacity`.
+    /// The stages start once the start hooks have completed.
+    pub fn spawn(self, capacity: usize, spawner: impl Spawn) -> PipelineHandle<I, O> {
+        let (input, rx) = mpsc::channel(capacity);
+        let mut tasks = Vec::new();
+        let output = (self.build)(rx, capacity, &mut tasks);
+        let (on_start, on_stop) = (self.on_start, self.on_stop);
+        let spawner = Arc::new(spawner);
+        let (finished, done) = oneshot::channel();
+        let mut aborts = Vec::with_capacity(tasks.len() + 1);
+        let tasks: Vec<_> = tasks
+            .into_iter()
+            .map(|task| {
+                let (task, abort) = abortable(task);
+                aborts.push(abort);
+                task
+            })
+            .collect();
+        let (lifecycle, abort) = abortable({
+            let spawner = spawner.clone();
+            async move {
+                for hook in on_start {
+                    hook().await;
+                }
+                let mut stopped = Vec::with_capacity(tasks.len());
+                for task in tasks {
+                    let (finished, rx) = oneshot::channel();
+                    spawner.spawn(Box::pin(async move {
+                        let _ = task.await;
+                        let _ = finished.send(());
+                    }));
+                    stopped.push(rx);
+                }
+                join_all(stopped).await;
+                for hook in on_stop {
+                    hook().await;
+                }
+                let _ = finished.send(());
+            }
+        });
+        aborts.push(abort);
+        spawner.spawn(Box::pin(async move {
+            let _ = lifecycle.await;
+        }));
+        PipelineHandle {
+            input,
+            output,
+            done,
+            aborts,
+        }
+    }
+}
+
+/// Handle to a spawned pipeline, used to feed inputs and receive outputs
+pub struct PipelineHandle<I, O> {
+    input: Sender<I>,
+    output: Receiver<O>,
+    done: oneshot::Receiver<()>,
+    aborts: Vec<AbortHandle>,
+}
+
+impl<I, O> PipelineHandle<I, O> {
+    /// Sends an input into the first stage, waiting while the channel is full
+    pub async fn send(&mut self, input: I) -> Result<(), SendError> {
+        self.input.send(input).await
+    }
+
+    /// Receives the next output of the last stage, or `None` once every stage has finished
+    pub async fn recv(&mut self) -> Option<O> {
+        self.output.next().await
+    }
+
+    /// Stops accepting new inputs, letting the stages finish once they are drained
+    pub fn close(&mut self) {
+        self.input.close_channel();
+    }
+
+    /// Stops accepting new inputs and drains in-flight items through every stage, returning
+    /// the remaining outputs once all stage tasks and stop hooks have finished within the deadline;
+    /// past the deadline the stage tasks and any pending hooks are aborted
+    pub async fn shutdown(mut self, deadline: Duration) -> Result<Vec<O>, Elapsed> {
+        self.input.close_channel();
+        let PipelineHandle {
+            output,
+            done,
+            aborts,
+            ..
+        } = self;
+        let result = rt::timeout(deadline, async move {
+            let drained = output.collect().await;
+            let _ = done.await;
+            drained
+        })
+        .await;
+        if result.is_err() {
+            // the lifecycle task goes first, so aborted stages can't let it run the stop hooks
+            aborts.iter().rev().for_each(AbortHandle::abort);
+        }
+        result
+    }
+
+    /// Splits the handle into the input sender and output receiver
+    pub fn split(self) -> (Sender<I>, Receiver<O>) {
+        (self.input, self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task::{self, sleep};
+    use std::time::Duration;
+
+    async fn multipler(i: u64) -> u64 {
+        sleep(Duration::from_millis(5)).await;
+        i * 32
+    }
+
+    async fn stringer(i: u64) -> String {
+        i.to_string()
+    }
+
+    fn spawner(future: BoxFuture<'static, ()>) {
+        task::spawn(future);
+    }
+
+    #[async_std::test]
+    async fn test_spawned_pipeline() {
+        let mut handle = spawned(multipler)
+            .stage(multipler)
+            .stage(stringer)
+            .spawn(2, spawner);
+
+        handle.send(1).await.unwrap();
+        assert_eq!(Some(String::from("1024")), handle.recv().await);
+
+        let (mut input, output) = handle.split();
+
+        task::spawn(async move {
+            for i in 0..5 {
+                input.send(i).await.unwrap();
+            }
+        });
+        let outputs: Vec<String> = output.collect().await;
+        assert_eq!(vec!["0", "1024", "2048", "3072", "4096"], outputs);
+    }
+
+    #[async_std::test]
+    async fn test_spawned_close() {
+        let mut handle = spawned(stringer).spawn(4, spawner);
+        handle.send(3).await.unwrap();
+        handle.close();
+        assert!(handle.send(4).await.is_err());
+        assert_eq!(Some(String::from("3")), handle.recv().await);
+        assert_eq!(None, handle.recv().await);
+    }
+
+    #[async_std::test]
+    async fn test_spawned_runtime_spawner() {
+        let mut handle = spawned(multipler)
+            .stage(stringer)
+            .spawn(4, runtime_spawner());
+        handle.send(2).await.unwrap();
+        assert_eq!(Some(String::from("64")), handle.recv().await);
+    }
+
+    #[async_std::test]
+    async fn test_spawned_shutdown() {
+        let mut handle = spawned(multipler).stage(stringer).spawn(4, spawner);
+        for i in 1..4 {
+            handle.send(i).await.unwrap();
+        }
+        let drained = handle.shutdown(Duration::from_secs(1)).await;
+        assert_eq!(Ok(vec!["32".into(), "64".into(), "96".into()]), drained);
+
+        let (stopped, rx) = oneshot::channel();
+        let mut handle = spawned(multipler)
+            .stage(|i: u64| async move {
+                sleep(Duration::from_millis(50)).await;
+                i
+            })
+            .on_stop(move || async move {
+                let _ = stopped.send(());
+            })
+            .spawn(4, spawner);
+        handle.send(1).await.unwrap();
+        assert!(handle.shutdown(Duration::from_millis(10)).await.is_err());
+        // the stages were aborted, so the stop hook is dropped without running
+        assert!(rx.await.is_err());
+    }
+
+    #[async_std::test]
+    async fn test_spawned_lifecycle_hooks() {
+        let (events, mut log) = mpsc::unbounded();
+        let (start, stop) = (events.clone(), events.clone());
+        let mut handle = spawned(multipler)
+            .on_start(move || async move {
+                sleep(Duration::from_millis(10)).await;
+                let _ = start.unbounded_send("open");
+            })
+            .stage(move |i: u64| {
+                let _ = events.unbounded_send("call");
+                async move { i }
+            })
+            .on_stop(move || async move {
+                let _ = stop.unbounded_send("flush");
+            })
+            .spawn(4, spawner);
+        handle.send(1).await.unwrap();
+        assert_eq!(Ok(vec![32]), handle.shutdown(Duration::from_secs(1)).await);
+        assert_eq!(Some("open"), log.next().await);
+        assert_eq!(Some("call"), log.next().await);
+        assert_eq!(Some("flush"), log.try_recv().ok());
+
+        // stop hooks also run when the handle is dropped
+        let (stopped, rx) = oneshot::channel();
+        let handle = spawned(stringer)
+            .on_stop(move || async move {
+                let _ = stopped.send(());
+            })
+            .spawn(4, spawner);
+        drop(handle);
+        assert_eq!(Ok(()), rx.await);
+    }
+}
@@ -0,0 +1,215 @@
+//! Shared application state for stages.
+
+use futures::future::BoxFuture;
+use std::{future::Future, ops::Deref, sync::Arc};
+
+use crate::{Middleware, StageInfo, Transform};
+
+/// Shared state handed to stages declared as `async fn(State<S>, T) -> O`
+pub struct State<S>(pub Arc<S>);
+
+impl<S> Clone for State<S> {
+    fn clone(&self) -> Self {
+        State(self.0.clone())
+    }
+}
+
+impl<S> Deref for State<S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.0
+    }
+}
+
+/// Middleware that transforms an input to output type with access to shared state.
+pub trait StateTransform<S, Args, T, O>: Send + Sync + 'static {
+    /// Asynchronously execute this handler with the shared state
+    fn transform_with(&self, state: State<S>, input: T) -> impl Future<Output = O> + Send;
+
+    /// Describes the stages making up this transform, in order
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::of::<Self, T, O>()]
+    }
+}
+
+/// Object-safe form of a stateful transform that boxes its future, used where stages are stored
+pub trait DynStateTransform<S, Args, T, O>: Send + Sync + 'static {
+    /// Execute this handler with the shared state, boxing the returned future
+    fn transform_with_dyn(&self, state: State<S>, input: T) -> BoxFuture<'_, O>;
+
+    /// Describes the stages making up this transform, in order
+    fn stages_dyn(&self) -> Vec<StageInfo>;
+}
+
+/// Every stateful transform can be erased by boxing its future
+impl<X, S, Args, T, O> DynStateTransform<S, Args, T, O> for X
+where
+    X: StateTransform<S, Args, T, O>,
+    S: 'static,
+    Args: 'static,
+    T: 'static,
+    O: 'static,
+{
+    fn transform_with_dyn(&self, state: State<S>, input: T) -> BoxFuture<'_, O> {
+        Box::pin(self.transform_with(state, input))
+    }
+
+    fn stages_dyn(&self) -> Vec<StageInfo> {
+        self.stages()
+    }
+}
+
+/// Erased stateful transforms are stateful transforms themselves, so stored stages are called
+/// the same way
+impl<S, Args, T, O> StateTransform<S, Args, T, O> for dyn DynStateTransform<S, Args, T, O>
+where
+    S: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    fn transform_with(&self, state: State<S>, input: T) -> impl Future<Output = O> + Send {
+        self.transform_with_dyn(state, input)
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stages_dyn()
+    }
+}
+
+/// Stateful implementation for an async function that produces an output from the state
+impl<Func, Fut, S, O> StateTransform<S, (), (), O> for Func
+where
+    Func: Send + Sync + 'static + Fn(State<S>) -> Fut,
+    Fut: Future<Output = O> + Send + 'static,
+    S: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    fn transform_with(&self, state: State<S>, _input: ()) -> impl Future<Output = O> + Send {
+        (self)(state)
+    }
+}
+
+/// Stateful implementation for an async function that accepts the state and an input
+impl<Func, Fut, S, T, O> StateTransform<S, (T, O), T, O> for Func
+where
+    Func: Send + Sync + 'static + Fn(State<S>, T) -> Fut,
+    Fut: Future<Output = O> + Send + 'static,
+    S: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    fn transform_with(&self, state: State<S>, input: T) -> impl Future<Output = O> + Send {
+        (self)(state, input)
+    }
+}
+
+/// Registers shared state that can be bound to any number of stateful stages
+pub struct WithState<S> {
+    state: Arc<S>,
+}
+
+/// Creates a new state registration from the shared state
+pub fn with_state<S>(state: Arc<S>) -> WithState<S> {
+    WithState { state }
+}
+
+impl<S> WithState<S>
+where
+    S: Send + Sync + 'static,
+{
+    /// Binds the shared state to the stage, turning it into a regular transform
+    pub fn bind<Args, T, O>(
+        &self,
+        stage: impl StateTransform<S, Args, T, O>,
+    ) -> StateBound<S, Args, T, O>
+    where
+        Args: Send + Sync + 'static,
+        T: Send + Sync + 'static,
+        O: Send + Sync + 'static,
+    {
+        StateBound {
+            state: self.state.clone(),
+            stage: Arc::new(stage),
+        }
+    }
+}
+
+/// Encapsulates a stateful stage with the state it was bound to
+pub struct StateBound<S, Args, T, O> {
+    state: Arc<S>,
+    stage: Arc<dyn DynStateTransform<S, Args, T, O>>,
+}
+
+/// Implements the transform trait on the bound stage (for downstream)
+impl<S, Args, T, O> Transform<(T, O), T, O> for StateBound<S, Args, T, O>
+where
+    S: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> O {
+        let state = State(self.state.clone());
+        self.stage.transform_with(state, input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the bound stage
+impl<S, Args, T, O> Middleware<T, O> for StateBound<S, Args, T, O>
+where
+    S: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    struct Db {
+        factor: i32,
+        prefix: &'static str,
+    }
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn scale(db: State<Db>, i: i32) -> i32 {
+        i * db.factor
+    }
+
+    async fn render(State(db): State<Db>, i: i32) -> String {
+        format!("{}{}", db.prefix, i)
+    }
+
+    #[async_std::test]
+    async fn test_bound_state() {
+        let app = with_state(Arc::new(Db {
+            factor: 2,
+            prefix: "n=",
+        }));
+        let m = (multipler, app.bind(scale), app.bind(render)).pipe();
+        assert_eq!(String::from("n=128"), m.call(2).await);
+
+        let source = app.bind(|db: State<Db>| async move { db.factor });
+        let m = (source, app.bind(render)).pipe();
+        assert_eq!(String::from("n=2"), m.call(()).await);
+    }
+}
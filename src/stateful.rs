@@ -0,0 +1,97 @@
+//! Stages owning mutable state.
+
+use async_lock::{Mutex, MutexGuardArc};
+use std::{future::Future, sync::Arc};
+
+use crate::{Middleware, StageInfo, Transform};
+
+/// Exclusive access to the state of a stateful stage, released when dropped
+pub type StateGuard<S> = MutexGuardArc<S>;
+
+/// Stage that owns mutable state, handing each call exclusive access to it
+pub struct Stateful<S, F> {
+    state: Arc<Mutex<S>>,
+    f: F,
+}
+
+/// Creates a new stateful stage; the function receives a guard over the state and the input,
+/// so calls are serialized for as long as the returned future holds the guard
+pub fn stateful<S, F, Fut, T, O>(initial_state: S, f: F) -> Stateful<S, F>
+where
+    F: Fn(StateGuard<S>, T) -> Fut,
+    Fut: Future<Output = O>,
+{
+    Stateful {
+        state: Arc::new(Mutex::new(initial_state)),
+        f,
+    }
+}
+
+/// Implements the transform trait on the stateful stage (for downstream)
+impl<S, F, Fut, T, O> Transform<(T, O), T, O> for Stateful<S, F>
+where
+    S: Send + Sync + 'static,
+    F: Fn(StateGuard<S>, T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = O> + Send + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: T) -> O {
+        let state = self.state.lock_arc().await;
+        (self.f)(state, input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::of::<F, T, O>()]
+    }
+}
+
+/// Implements the middleware trait on the stateful stage
+impl<S, F, Fut, T, O> Middleware<T, O> for Stateful<S, F>
+where
+    S: Send + Sync + 'static,
+    F: Fn(StateGuard<S>, T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = O> + Send + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: T) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use std::collections::HashSet;
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    #[async_std::test]
+    async fn test_stateful_counter() {
+        let counter = stateful(0, |mut count, i: i32| async move {
+            *count += 1;
+            (*count, i)
+        });
+        let m = (multipler, counter).pipe();
+        assert_eq!((1, 32), m.call(1).await);
+        assert_eq!((2, 64), m.call(2).await);
+    }
+
+    #[async_std::test]
+    async fn test_stateful_dedup() {
+        let dedup = stateful(HashSet::new(), |mut seen, s: String| async move {
+            seen.insert(s.clone()).then_some(s)
+        });
+        assert_eq!(Some(String::from("a")), dedup.call(String::from("a")).await);
+        assert_eq!(None, dedup.call(String::from("a")).await);
+        assert_eq!(Some(String::from("b")), dedup.call(String::from("b")).await);
+    }
+}
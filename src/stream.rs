@@ -0,0 +1,138 @@
+//! Stream processing for pipelines.
+
+use futures::{stream, Sink, Stream, StreamExt};
+use std::time::Duration;
+
+use crate::{rt, Middleware, Pied};
+
+/// Stream ticking once every period on the runtime selected by the enabled features, the first
+/// tick one period from now, e.g. to feed a pipeline on a schedule
+pub fn interval(period: Duration) -> impl Stream<Item = ()> + Send {
+    rt::interval(period)
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Applies the pipeline to every item of the stream with up to `concurrency` calls in
+    /// flight at once, yielding outputs in the same order as the inputs
+    pub fn call_stream<S>(&self, stream: S, concurrency: usize) -> impl Stream<Item = O>
+    where
+        S: Stream<Item = I>,
+    {
+        let middleware = self.middleware.clone();
+        stream
+            .map(move |input| {
+                let middleware = middleware.clone();
+                async move { middleware.call(input).await }
+            })
+            .buffered(concurrency.max(1))
+    }
+
+    /// Applies the pipeline to every item of the stream and writes the outputs in order into the
+    /// sink, waiting on the sink's readiness before each write and flushing and closing it once
+    /// the stream ends
+    pub async fn sink_into<S, K>(
+        &self,
+        stream: S,
+        sink: K,
+        concurrency: usize,
+    ) -> Result<(), K::Error>
+    where
+        S: Stream<Item = I>,
+        K: Sink<O>,
+    {
+        self.call_stream(stream, concurrency)
+            .map(Ok)
+            .forward(sink)
+            .await
+    }
+
+    /// Applies the pipeline to every input with up to `concurrency` calls in flight at once,
+    /// collecting the outputs in the same order as the inputs
+    pub async fn call_many(&self, inputs: Vec<I>, concurrency: usize) -> Vec<O> {
+        self.call_stream(stream::iter(inputs), concurrency)
+            .collect()
+            .await
+    }
+
+    /// Applies the pipeline to every input with up to `concurrency` calls in flight at once,
+    /// collecting the outputs in the order they complete
+    pub async fn call_many_unordered(&self, inputs: Vec<I>, concurrency: usize) -> Vec<O> {
+        let middleware = self.middleware.clone();
+        stream::iter(inputs)
+            .map(move |input| {
+                let middleware = middleware.clone();
+                async move { middleware.call(input).await }
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use async_std::task::sleep;
+    use futures::channel::mpsc;
+    use std::time::Instant;
+
+    async fn slow_multipler(i: u64) -> u64 {
+        sleep(Duration::from_millis(20 - i * 5)).await;
+        i * 32
+    }
+
+    async fn stringer(i: u64) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_call_stream() {
+        let m = (slow_multipler, stringer).pipe();
+        let outputs: Vec<String> = m.call_stream(stream::iter(0..4), 4).collect().await;
+        assert_eq!(vec!["0", "32", "64", "96"], outputs);
+
+        let outputs: Vec<String> = m.call_stream(stream::iter(0..2), 0).collect().await;
+        assert_eq!(vec!["0", "32"], outputs);
+    }
+
+    #[async_std::test]
+    async fn test_sink_into() {
+        let m = (slow_multipler, stringer).pipe();
+        let (tx, rx) = mpsc::channel(4);
+        m.sink_into(stream::iter(0..3), tx, 2).await.unwrap();
+        assert_eq!(vec!["0", "32", "64"], rx.collect::<Vec<_>>().await);
+
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+        assert!(m.sink_into(stream::iter(0..3), tx, 2).await.is_err());
+    }
+
+    #[async_std::test]
+    async fn test_call_many() {
+        let m = (slow_multipler, stringer).pipe();
+        assert_eq!(vec!["0", "32", "64"], m.call_many(vec![0, 1, 2], 3).await);
+        assert_eq!(
+            vec!["64", "32", "0"],
+            m.call_many_unordered(vec![0, 1, 2], 3).await
+        );
+        assert_eq!(
+            vec!["0", "32", "64"],
+            m.call_many_unordered(vec![0, 1, 2], 1).await
+        );
+    }
+
+    #[async_std::test]
+    async fn test_interval() {
+        let start = Instant::now();
+        let ticks: Vec<()> = interval(Duration::from_millis(10)).take(3).collect().await;
+        assert_eq!(3, ticks.len());
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+}
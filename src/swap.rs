@@ -0,0 +1,109 @@
+//! Hot-swappable stages.
+
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+
+use crate::{into_middleware, BoxMiddleware, Middleware, StageInfo, Transform};
+
+/// Stage that can be replaced atomically while the pipeline keeps running
+///
+/// Clones share the same slot, so a clone kept outside of the pipeline can swap the stage;
+/// in-flight calls finish on the stage they started with.
+pub struct Swappable<I, O> {
+    current: Arc<ArcSwap<BoxMiddleware<I, O>>>,
+}
+
+impl<I, O> Swappable<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Creates a new swappable stage starting out with the stage
+    pub fn new<Args>(stage: impl Transform<Args, I, O>) -> Self
+    where
+        Args: Send + Sync + 'static,
+    {
+        Swappable {
+            current: Arc::new(ArcSwap::from_pointee(into_middleware(stage))),
+        }
+    }
+
+    /// Replaces the stage used by subsequent calls, returning the previous one
+    pub fn swap<Args>(&self, stage: impl Transform<Args, I, O>) -> BoxMiddleware<I, O>
+    where
+        Args: Send + Sync + 'static,
+    {
+        let previous = self.current.swap(Arc::new(into_middleware(stage)));
+        BoxMiddleware::clone(&previous)
+    }
+
+    /// Returns the stage currently in use
+    pub fn current(&self) -> BoxMiddleware<I, O> {
+        BoxMiddleware::clone(&self.current.load())
+    }
+}
+
+impl<I, O> Clone for Swappable<I, O> {
+    fn clone(&self) -> Self {
+        Swappable {
+            current: self.current.clone(),
+        }
+    }
+}
+
+/// Implements the transform trait on the swappable stage (for downstream)
+impl<I, O> Transform<(I, O), I, O> for Swappable<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let stage = self.current();
+        stage.call(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        (*self.current()).stages_dyn()
+    }
+}
+
+/// Implements the middleware trait on the swappable stage
+impl<I, O> Middleware<I, O> for Swappable<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Piper, TransformExt};
+
+    async fn incr(i: i32) -> i32 {
+        i + 1
+    }
+
+    async fn double(i: i32) -> i32 {
+        i * 2
+    }
+
+    #[async_std::test]
+    async fn test_swappable() {
+        let rules = Swappable::new(incr.named("incr"));
+        let m = (rules.clone(), double).pipe();
+        assert_eq!(8, m.call(3).await);
+        assert_eq!("incr", m.stages()[0].name);
+
+        rules.swap(double.named("double"));
+        assert_eq!(12, m.call(3).await);
+        assert_eq!("double", m.stages()[0].name);
+    }
+}
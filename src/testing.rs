@@ -0,0 +1,255 @@
+//! Test doubles for unit-testing pipelines.
+
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{DynTransform, Middleware, StageInfo, Transform};
+
+/// Wraps a stage and records every input it receives along with the output it produced;
+/// clones share the recording, so keep a clone before piping the stage
+pub struct RecordingMiddleware<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    calls: Arc<Mutex<Vec<(I, O)>>>,
+}
+
+/// Creates a new recording stage around the stage
+pub fn recording<Args, I, O>(stage: impl Transform<Args, I, O>) -> RecordingMiddleware<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Clone + Send + Sync + 'static,
+{
+    RecordingMiddleware {
+        stage: Arc::new(stage),
+        calls: Arc::new(Mutex::new(Vec::new())),
+    }
+}
+
+impl<Args, I: Clone, O: Clone> RecordingMiddleware<Args, I, O> {
+    /// Returns every recorded input and output pair, in call order
+    pub fn calls(&self) -> Vec<(I, O)> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Returns every recorded input, in call order
+    pub fn inputs(&self) -> Vec<I> {
+        self.calls().into_iter().map(|(input, _)| input).collect()
+    }
+
+    /// Returns every recorded output, in call order
+    pub fn outputs(&self) -> Vec<O> {
+        self.calls().into_iter().map(|(_, output)| output).collect()
+    }
+}
+
+impl<Args, I, O> Clone for RecordingMiddleware<Args, I, O> {
+    fn clone(&self) -> Self {
+        RecordingMiddleware {
+            stage: self.stage.clone(),
+            calls: self.calls.clone(),
+        }
+    }
+}
+
+/// Implements the transform trait on the recording stage (for downstream)
+impl<Args, I, O> Transform<(I, O), I, O> for RecordingMiddleware<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Clone + Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let output = self.stage.transform(input.clone()).await;
+        self.calls.lock().unwrap().push((input, output.clone()));
+        output
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the recording stage
+impl<Args, I, O> Middleware<I, O> for RecordingMiddleware<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
+    O: Clone + Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+type Respond<I, O> = Box<dyn Fn(&I) -> O + Send>;
+
+struct MockState<I, O> {
+    once: VecDeque<O>,
+    respond: Option<Respond<I, O>>,
+    calls: Vec<I>,
+}
+
+/// Stand-in stage with programmed responses that remembers the inputs it was called with;
+/// clones share the responses and calls, so keep a clone before piping the mock
+pub struct MockTransform<I, O> {
+    state: Arc<Mutex<MockState<I, O>>>,
+}
+
+impl<I, O> MockTransform<I, O> {
+    /// Creates a new mock without responses, which panics when called
+    pub fn new() -> Self {
+        MockTransform {
+            state: Arc::new(Mutex::new(MockState {
+                once: VecDeque::new(),
+                respond: None,
+                calls: Vec::new(),
+            })),
+        }
+    }
+
+    /// Locks the state of the mock, recovering it if a panicking response poisoned the lock
+    fn state(&self) -> MutexGuard<'_, MockState<I, O>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Responds to every call by computing the output from the input
+    pub fn returning(self, respond: impl Fn(&I) -> O + Send + 'static) -> Self {
+        self.state().respond = Some(Box::new(respond));
+        self
+    }
+
+    /// Queues an output for a single call, used before falling back to `returning`
+    pub fn return_once(self, output: O) -> Self {
+        self.state().once.push_back(output);
+        self
+    }
+
+    /// Returns the number of times the mock was called
+    pub fn call_count(&self) -> usize {
+        self.state().calls.len()
+    }
+
+    /// Panics unless the mock was called exactly `expected` times
+    #[track_caller]
+    pub fn assert_called(&self, expected: usize) {
+        let actual = self.call_count();
+        assert_eq!(
+            expected, actual,
+            "expected the mock to be called {} times, but it was called {} times",
+            expected, actual
+        );
+    }
+}
+
+impl<I: Clone, O> MockTransform<I, O> {
+    /// Returns the inputs of every call, in call order
+    pub fn calls(&self) -> Vec<I> {
+        self.state().calls.clone()
+    }
+}
+
+impl<I: PartialEq + Debug, O> MockTransform<I, O> {
+    /// Panics unless the mock was called with the input at least once
+    #[track_caller]
+    pub fn assert_called_with(&self, input: &I) {
+        let state = self.state();
+        assert!(
+            state.calls.contains(input),
+            "expected the mock to be called with {:?}, but it was called with {:?}",
+            input,
+            state.calls
+        );
+    }
+}
+
+impl<I, O> Default for MockTransform<I, O> {
+    fn default() -> Self {
+        MockTransform::new()
+    }
+}
+
+impl<I, O> Clone for MockTransform<I, O> {
+    fn clone(&self) -> Self {
+        MockTransform {
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// Implements the transform trait on the mock (for downstream)
+impl<I, O> Transform<(I, O), I, O> for MockTransform<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let mut state = self.state();
+        let output = match state.once.pop_front() {
+            Some(output) => Some(output),
+            None => state.respond.as_ref().map(|respond| respond(&input)),
+        };
+        state.calls.push(input);
+        // release the lock first so the panic doesn't poison the mock for later assertions
+        drop(state);
+        output.expect("mock called without a programmed response")
+    }
+}
+
+/// Implements the middleware trait on the mock
+impl<I, O> Middleware<I, O> for MockTransform<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    async fn double(i: i32) -> i32 {
+        i * 2
+    }
+
+    #[async_std::test]
+    async fn test_recording_middleware() {
+        let recorder = recording(double);
+        let m = (recorder.clone(), double).pipe();
+        assert_eq!(12, m.call(3).await);
+        assert_eq!(20, m.call(5).await);
+        assert_eq!(vec![(3, 6), (5, 10)], recorder.calls());
+        assert_eq!(vec![3, 5], recorder.inputs());
+    }
+
+    #[async_std::test]
+    async fn test_mock_transform() {
+        let lookup = MockTransform::new()
+            .return_once(String::from("cached"))
+            .returning(|i: &i32| i.to_string());
+        let m = (double, lookup.clone()).pipe();
+        assert_eq!("cached", m.call(1).await);
+        assert_eq!("4", m.call(2).await);
+        lookup.assert_called(2);
+        lookup.assert_called_with(&4);
+        assert_eq!(vec![2, 4], lookup.calls());
+    }
+
+    #[test]
+    fn test_mock_without_response() {
+        let mock = MockTransform::<i32, i32>::new();
+        let call = std::panic::AssertUnwindSafe(|| futures::executor::block_on(mock.call(1)));
+        assert!(std::panic::catch_unwind(call).is_err());
+        mock.assert_called(1);
+    }
+}
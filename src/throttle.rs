@@ -0,0 +1,112 @@
+//! Smoothing of bursty streams.
+
+use futures::{stream, Stream, StreamExt};
+use std::{collections::VecDeque, time::Duration};
+
+use crate::rt::{self, Instant};
+
+/// Paces the items of a stream in time
+pub trait ThrottleExt: Stream + Sized {
+    /// Lets at most one item through per `period`, dropping the items arriving in between
+    fn throttle(self, period: Duration) -> impl Stream<Item = Self::Item> {
+        let state = (Box::pin(self), None::<Instant>);
+        stream::unfold(state, move |(mut items, mut last)| async move {
+            while let Some(item) = items.next().await {
+                if last.is_none_or(|last| last.elapsed() >= period) {
+                    last = Some(Instant::now());
+                    return Some((item, (items, last)));
+                }
+            }
+            None
+        })
+    }
+
+    /// Only lets an item through once no other item has followed it for `period`, so every
+    /// burst is reduced to its last item
+    fn debounce(self, period: Duration) -> impl Stream<Item = Self::Item> {
+        let state = (Box::pin(self.fuse()), None::<Self::Item>);
+        stream::unfold(state, move |(mut items, mut pending)| async move {
+            loop {
+                match pending.take() {
+                    Some(item) => match rt::timeout(period, items.next()).await {
+                        Ok(Some(next)) => pending = Some(next),
+                        Ok(None) | Err(_) => return Some((item, (items, None))),
+                    },
+                    None => pending = Some(items.next().await?),
+                }
+            }
+        })
+    }
+
+    /// Debounces every key separately, only letting an item through once no other item with the
+    /// same key has followed it for `period`, e.g. to coalesce bursts of events per file
+    fn debounce_by_key<K, F>(self, period: Duration, key: F) -> impl Stream<Item = Self::Item>
+    where
+        K: Eq,
+        F: FnMut(&Self::Item) -> K,
+    {
+        // pending items ordered by deadline, which is their arrival order as the period is fixed
+        let state = (Box::pin(self.fuse()), VecDeque::new(), key);
+        stream::unfold(state, move |(mut items, mut pending, mut key)| async move {
+            loop {
+                let item = match pending.front() {
+                    Some((_, deadline, _)) => {
+                        let remaining =
+                            Instant::saturating_duration_since(deadline, Instant::now());
+                        match rt::timeout(remaining, items.next()).await {
+                            Ok(Some(item)) => item,
+                            Ok(None) | Err(_) => {
+                                let (_, _, item) = pending.pop_front()?;
+                                return Some((item, (items, pending, key)));
+                            }
+                        }
+                    }
+                    None => items.next().await?,
+                };
+                let k = key(&item);
+                pending.retain(|(pending_key, _, _)| *pending_key != k);
+                pending.push_back((k, Instant::now() + period, item));
+            }
+        })
+    }
+}
+
+impl<S: Stream> ThrottleExt for S {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task::sleep;
+
+    /// Emits the items after waiting the paired number of milliseconds before each
+    fn bursty(items: Vec<(u64, i32)>) -> impl Stream<Item = i32> {
+        stream::iter(items).then(|(wait, item)| async move {
+            sleep(Duration::from_millis(wait)).await;
+            item
+        })
+    }
+
+    #[async_std::test]
+    async fn test_throttle() {
+        let items = bursty(vec![(0, 1), (0, 2), (0, 3), (40, 4), (0, 5)]);
+        let outputs: Vec<i32> = items.throttle(Duration::from_millis(20)).collect().await;
+        assert_eq!(vec![1, 4], outputs);
+    }
+
+    #[async_std::test]
+    async fn test_debounce() {
+        let items = bursty(vec![(0, 1), (0, 2), (0, 3), (40, 4), (0, 5), (40, 6)]);
+        let outputs: Vec<i32> = items.debounce(Duration::from_millis(20)).collect().await;
+        assert_eq!(vec![3, 5, 6], outputs);
+    }
+
+    #[async_std::test]
+    async fn test_debounce_by_key() {
+        let items = bursty(vec![(0, 1), (0, 12), (0, 3), (10, 14), (10, 5), (60, 16)]);
+        let outputs: Vec<i32> = items
+            .debounce_by_key(Duration::from_millis(30), |i| i / 10)
+            .collect()
+            .await;
+        assert_eq!(vec![14, 5, 16], outputs);
+    }
+}
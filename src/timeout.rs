@@ -0,0 +1,121 @@
+//! Timeout middleware.
+
+use std::{error::Error, fmt, sync::Arc, time::Duration};
+
+use crate::{rt, DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// Error returned when a stage did not complete before its timeout
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Elapsed(pub(crate) ());
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl Error for Elapsed {}
+
+/// Bounds how long a stage may run before failing with `Elapsed`
+pub struct TimeoutTransform<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    duration: Duration,
+}
+
+/// Implements the transform trait on the timeout middleware (for downstream)
+impl<Args, I, O> Transform<(I, Result<O, Elapsed>), I, Result<O, Elapsed>>
+    for TimeoutTransform<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> Result<O, Elapsed> {
+        rt::timeout(self.duration, self.stage.transform(input)).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the timeout middleware
+impl<Args, I, O> Middleware<I, Result<O, Elapsed>> for TimeoutTransform<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> Result<O, Elapsed> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+/// Creates a new timeout middleware around the stage
+pub fn timeout<Args, I, O>(
+    stage: impl Transform<Args, I, O>,
+    duration: Duration,
+) -> TimeoutTransform<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    TimeoutTransform {
+        stage: Arc::new(stage),
+        duration,
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Bounds how long the whole pipeline may run before failing with `Elapsed`
+    pub fn timeout(self, duration: Duration) -> Pied<T, Args, I, Result<O, Elapsed>> {
+        self.rebuild(|pied| timeout(pied, duration))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{try_convert, Piper};
+    use async_std::task::sleep;
+
+    async fn slow(ms: u64) -> u64 {
+        sleep(Duration::from_millis(ms)).await;
+        ms
+    }
+
+    async fn stringer(i: u64) -> String {
+        i.to_string()
+    }
+
+    #[async_std::test]
+    async fn test_pied_timeout() {
+        let m = (slow, stringer).pipe().timeout(Duration::from_millis(50));
+        assert_eq!(Ok(String::from("1")), m.call(1).await);
+        assert_eq!(Err(Elapsed(())), m.call(500).await);
+    }
+
+    #[async_std::test]
+    async fn test_stage_timeout() {
+        let m = try_convert(
+            timeout(slow, Duration::from_millis(50)),
+            |ms: u64| async move { Ok::<_, Elapsed>(ms * 2) },
+        );
+        assert_eq!(Ok(4), m.call(2).await);
+        assert_eq!(
+            "deadline has elapsed",
+            m.call(500).await.unwrap_err().to_string()
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Tracing spans per stage.
+
+use std::{borrow::Cow, sync::Arc};
+use tracing::{field, info_span, Instrument};
+
+use crate::{rt::Instant, DynTransform, Middleware, StageInfo, Transform};
+
+/// Instruments every invocation of a stage with a span recording its elapsed time
+pub struct Traced<Args, I, O> {
+    stage: Arc<dyn DynTransform<Args, I, O>>,
+    name: Cow<'static, str>,
+    is_error: Option<fn(&O) -> bool>,
+}
+
+/// Creates a new traced stage, named after the stages it wraps
+pub fn traced<Args, I, O, S>(stage: S) -> Traced<Args, I, O>
+where
+    S: Transform<Args, I, O>,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    let names: Vec<_> = stage.stages().into_iter().map(|s| s.name).collect();
+    Traced {
+        name: names.join(", ").into(),
+        stage: Arc::new(stage),
+        is_error: None,
+    }
+}
+
+/// Creates a new traced stage for a fallible stage, also recording whether it failed
+pub fn try_traced<Args, I, O, E, S>(stage: S) -> Traced<Args, I, Result<O, E>>
+where
+    S: Transform<Args, I, Result<O, E>>,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    Traced {
+        is_error: Some(Result::is_err),
+        ..traced(stage)
+    }
+}
+
+impl<Args, I, O> Traced<Args, I, O> {
+    /// Sets the name recorded on the span of the stage
+    pub fn named(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Returns the name recorded on the span of the stage
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Implements the transform trait on the traced stage (for downstream)
+impl<Args, I, O> Transform<(I, O), I, O> for Traced<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        let span = info_span!(
+            "stage",
+            name = %self.name,
+            elapsed_ms = field::Empty,
+            error = field::Empty,
+        );
+        let start = Instant::now();
+        let output = self.stage.transform(input).instrument(span.clone()).await;
+        span.record("elapsed_ms", start.elapsed().as_secs_f64() * 1000.0);
+        if let Some(is_error) = self.is_error {
+            span.record("error", is_error(&output));
+        }
+        output
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the traced stage
+impl<Args, I, O> Middleware<I, O> for Traced<Args, I, O>
+where
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Transform::stages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{try_convert, Piper, TransformExt};
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn parse(s: String) -> Result<i32, String> {
+        s.parse().map_err(|_| s)
+    }
+
+    #[async_std::test]
+    async fn test_traced_stages() {
+        let stage = traced(multipler);
+        assert!(stage.name().ends_with("multipler"));
+        assert_eq!("scale", stage.named("scale").name());
+        assert_eq!("decode", traced(multipler.named("decode")).name());
+
+        let m = (traced(multipler).named("first"), traced(multipler)).pipe();
+        assert_eq!(2048, m.call(2).await);
+
+        let m = try_convert(try_traced(parse).named("parse"), |i: i32| async move {
+            Ok::<_, String>(i * 2)
+        });
+        assert_eq!(Ok(4), m.call(String::from("2")).await);
+        assert_eq!(Err(String::from("x")), m.call(String::from("x")).await);
+    }
+}
@@ -0,0 +1,22 @@
+//! Helpers shared by the stages.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Returns a random fraction in `[0, 1)` without pulling in a random number generator
+pub(crate) fn random() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Releases a slot of a counter of calls in progress once a call completes or is cancelled
+pub(crate) struct InFlight<'a>(pub(crate) &'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,174 @@
+//! Event-time windows over streams.
+
+use futures::{stream, Stream, StreamExt};
+use std::{collections::VecDeque, time::Duration};
+
+/// Groups the items of a stream into windows by the timestamp extracted from each item.
+///
+/// Items are expected to arrive in timestamp order; a late item joins the window that is
+/// currently open. Each window is emitted as a `Vec`, which can be mapped into a fold result.
+pub trait WindowExt: Stream + Sized {
+    /// Splits the stream into consecutive non-overlapping windows of `size`, emitting each
+    /// non-empty window once an item past its end arrives or the stream ends
+    fn tumbling_window<F>(self, size: Duration, timestamp: F) -> impl Stream<Item = Vec<Self::Item>>
+    where
+        F: Fn(&Self::Item) -> Duration,
+    {
+        let size = size.as_nanos().max(1);
+        let state = (
+            Box::pin(self.fuse()),
+            None::<(u128, Vec<Self::Item>)>,
+            timestamp,
+        );
+        stream::unfold(
+            state,
+            move |(mut items, mut current, timestamp)| async move {
+                loop {
+                    let Some(item) = items.next().await else {
+                        return current.map(|(_, window)| (window, (items, None, timestamp)));
+                    };
+                    let bucket = timestamp(&item).as_nanos() / size;
+                    match &mut current {
+                        Some((open, window)) if bucket <= *open => window.push(item),
+                        _ => {
+                            if let Some((_, window)) = current.replace((bucket, vec![item])) {
+                                return Some((window, (items, current, timestamp)));
+                            }
+                        }
+                    }
+                }
+            },
+        )
+    }
+
+    /// Splits the stream into windows of `size` starting every `slide`, so an item belongs to
+    /// every window covering its timestamp; empty windows are skipped
+    fn sliding_window<F>(
+        self,
+        size: Duration,
+        slide: Duration,
+        timestamp: F,
+    ) -> impl Stream<Item = Vec<Self::Item>>
+    where
+        Self::Item: Clone,
+        F: Fn(&Self::Item) -> Duration,
+    {
+        let size = size.as_nanos().max(1);
+        let slide = slide.as_nanos().max(1);
+        // the earliest window start covering the timestamp
+        let first_start = move |t: u128| {
+            if t < size {
+                0
+            } else {
+                ((t - size) / slide + 1) * slide
+            }
+        };
+        let state = SlidingState {
+            items: Box::pin(self.fuse()),
+            buffer: VecDeque::new(),
+            start: None,
+            ended: false,
+            timestamp,
+        };
+        stream::unfold(
+            state,
+            move |mut state: SlidingState<_, Self::Item, F>| async move {
+                loop {
+                    if let Some(start) = state.start {
+                        let end = start + size;
+                        if state.ended || state.buffer.back().is_some_and(|(t, _)| *t >= end) {
+                            if state.buffer.is_empty() {
+                                return None;
+                            }
+                            let window: Vec<_> = state
+                                .buffer
+                                .iter()
+                                .filter(|(t, _)| *t >= start && *t < end)
+                                .map(|(_, item)| item.clone())
+                                .collect();
+                            let next = start + slide;
+                            while state.buffer.front().is_some_and(|(t, _)| *t < next) {
+                                state.buffer.pop_front();
+                            }
+                            state.start = Some(match state.buffer.front() {
+                                Some((t, _)) => next.max(first_start(*t)),
+                                None => next,
+                            });
+                            if !window.is_empty() {
+                                return Some((window, state));
+                            }
+                            continue;
+                        }
+                    }
+                    match state.items.next().await {
+                        Some(item) => {
+                            let t = (state.timestamp)(&item).as_nanos();
+                            if state.buffer.is_empty() {
+                                let start = first_start(t);
+                                state.start = Some(state.start.map_or(start, |s| s.max(start)));
+                            }
+                            state.buffer.push_back((t, item));
+                        }
+                        None => state.ended = true,
+                    }
+                }
+            },
+        )
+    }
+}
+
+impl<S: Stream> WindowExt for S {}
+
+/// Items buffered by a sliding window until every window covering them has been emitted
+struct SlidingState<S, T, F> {
+    items: S,
+    buffer: VecDeque<(u128, T)>,
+    start: Option<u128>,
+    ended: bool,
+    timestamp: F,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+
+    type Event = (u64, i32);
+
+    fn at(event: &Event) -> Duration {
+        Duration::from_millis(event.0)
+    }
+
+    async fn double(event: Event) -> Event {
+        (event.0, event.1 * 2)
+    }
+
+    fn events() -> impl Stream<Item = Event> {
+        stream::iter(vec![(1, 1), (3, 2), (6, 3), (12, 4), (31, 5)])
+    }
+
+    #[async_std::test]
+    async fn test_tumbling_window() {
+        let m = (double, double).pipe();
+        let sums: Vec<i32> = m
+            .call_stream(events(), 2)
+            .tumbling_window(Duration::from_millis(5), at)
+            .map(|window| window.iter().map(|e| e.1).sum::<i32>())
+            .collect()
+            .await;
+        assert_eq!(vec![12, 12, 16, 20], sums);
+    }
+
+    #[async_std::test]
+    async fn test_sliding_window() {
+        let windows: Vec<Vec<i32>> = events()
+            .sliding_window(Duration::from_millis(10), Duration::from_millis(5), at)
+            .map(|window| window.into_iter().map(|e| e.1).collect())
+            .collect()
+            .await;
+        assert_eq!(
+            vec![vec![1, 2, 3], vec![3, 4], vec![4], vec![5], vec![5]],
+            windows
+        );
+    }
+}
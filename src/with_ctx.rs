@@ -0,0 +1,199 @@
+//! Values carried through a pipeline alongside a read-only context.
+//!
+//! Stages reading the context are built with `with_ctx`. Plain `T -> O` stages are lifted onto
+//! the value with `on_value` inside a tuple pipe, or appended to a pipeline as they are with
+//! `Pied::then_value`, which lifts them automatically.
+
+use std::{future::Future, sync::Arc};
+
+use crate::{DynTransform, Middleware, Pied, StageInfo, Transform};
+
+/// Value flowing through a pipeline together with a context every stage can read
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WithCtx<Ctx, T> {
+    pub ctx: Ctx,
+    pub value: T,
+}
+
+impl<Ctx, T> WithCtx<Ctx, T> {
+    /// Creates a new value carrying the context
+    pub fn new(ctx: Ctx, value: T) -> Self {
+        WithCtx { ctx, value }
+    }
+
+    /// Splits into the context and the value
+    pub fn into_parts(self) -> (Ctx, T) {
+        (self.ctx, self.value)
+    }
+}
+
+/// Async function from a borrowed context and an owned input to a future borrowing the context
+pub trait AsyncCtxFn<'a, Ctx: 'a, T, O>: Fn(&'a Ctx, T) -> Self::Fut {
+    type Fut: Future<Output = O> + Send + 'a;
+}
+
+impl<'a, F, Fut, Ctx, T, O> AsyncCtxFn<'a, Ctx, T, O> for F
+where
+    F: Fn(&'a Ctx, T) -> Fut,
+    Fut: Future<Output = O> + Send + 'a,
+    Ctx: 'a,
+{
+    type Fut = Fut;
+}
+
+/// Runs an async function reading the context on the value, e.g.
+/// `async fn(ctx: &Ctx, input: T) -> O`
+pub struct CtxStage<F> {
+    f: F,
+}
+
+/// Creates a new stage over values carrying a context from a function borrowing the context
+pub fn with_ctx<F>(f: F) -> CtxStage<F> {
+    CtxStage { f }
+}
+
+/// Implements the transform trait on the context-reading stage (for downstream)
+impl<F, Ctx, T, O> Transform<(WithCtx<Ctx, T>, WithCtx<Ctx, O>), WithCtx<Ctx, T>, WithCtx<Ctx, O>>
+    for CtxStage<F>
+where
+    F: for<'a> AsyncCtxFn<'a, Ctx, T, O> + Send + Sync + 'static,
+    Ctx: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: WithCtx<Ctx, T>) -> WithCtx<Ctx, O> {
+        let WithCtx { ctx, value } = input;
+        let value = (self.f)(&ctx, value).await;
+        WithCtx { ctx, value }
+    }
+}
+
+/// Implements the middleware trait on the context-reading stage
+impl<F, Ctx, T, O> Middleware<WithCtx<Ctx, T>, WithCtx<Ctx, O>> for CtxStage<F>
+where
+    F: for<'a> AsyncCtxFn<'a, Ctx, T, O> + Send + Sync + 'static,
+    Ctx: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: WithCtx<Ctx, T>) -> WithCtx<Ctx, O> {
+        self.transform(input).await
+    }
+}
+
+/// Runs a plain stage on the value, passing the context through untouched
+pub struct OnValue<Args, T, O> {
+    stage: Arc<dyn DynTransform<Args, T, O>>,
+}
+
+/// Creates a new stage over values carrying a context from any existing transform
+pub fn on_value<Args, T, O>(stage: impl Transform<Args, T, O>) -> OnValue<Args, T, O>
+where
+    Args: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    OnValue {
+        stage: Arc::new(stage),
+    }
+}
+
+/// Implements the transform trait on the lifted stage (for downstream)
+impl<Args, Ctx, T, O>
+    Transform<(WithCtx<Ctx, T>, WithCtx<Ctx, O>), WithCtx<Ctx, T>, WithCtx<Ctx, O>>
+    for OnValue<Args, T, O>
+where
+    Args: Send + Sync + 'static,
+    Ctx: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: WithCtx<Ctx, T>) -> WithCtx<Ctx, O> {
+        let WithCtx { ctx, value } = input;
+        let value = self.stage.transform(value).await;
+        WithCtx { ctx, value }
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+/// Implements the middleware trait on the lifted stage
+impl<Args, Ctx, T, O> Middleware<WithCtx<Ctx, T>, WithCtx<Ctx, O>> for OnValue<Args, T, O>
+where
+    Args: Send + Sync + 'static,
+    Ctx: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: WithCtx<Ctx, T>) -> WithCtx<Ctx, O> {
+        self.transform(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        self.stage.stages()
+    }
+}
+
+impl<T, Args, I, Ctx, O> Pied<T, Args, I, WithCtx<Ctx, O>>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    Ctx: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Appends a plain stage run on the value, passing the context through untouched
+    pub fn then_value<ArgsN, O2>(
+        self,
+        next: impl Transform<ArgsN, O, O2>,
+    ) -> Pied<T, Args, I, WithCtx<Ctx, O2>>
+    where
+        ArgsN: Send + Sync + 'static,
+        O2: Send + Sync + 'static,
+    {
+        self.then(on_value(next))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pipe_fn, Piper};
+
+    struct Tenant {
+        name: &'static str,
+        factor: i32,
+    }
+
+    async fn scale(tenant: &Tenant, i: i32) -> i32 {
+        i * tenant.factor
+    }
+
+    async fn increment(i: i32) -> i32 {
+        i + 1
+    }
+
+    async fn label(tenant: &Tenant, i: i32) -> String {
+        format!("{}: {}", tenant.name, i)
+    }
+
+    #[async_std::test]
+    async fn test_with_ctx() {
+        let m = (with_ctx(scale), on_value(increment), with_ctx(label)).pipe();
+        let tenant = Tenant {
+            name: "acme",
+            factor: 3,
+        };
+        let out = m.call(WithCtx::new(tenant, 2)).await;
+        assert_eq!("acme: 7", out.value);
+        assert_eq!(3, out.ctx.factor);
+
+        let m = pipe_fn(with_ctx(scale))
+            .then_value(increment)
+            .then_value(|i: i32| async move { i.to_string() });
+        let out = m.call(WithCtx::new(out.ctx, 2)).await;
+        assert_eq!("7", out.value);
+    }
+}
@@ -0,0 +1,147 @@
+//! Replicating spawned stages across worker tasks.
+
+use async_lock::Mutex;
+use futures::{channel::mpsc, future::BoxFuture, SinkExt, StreamExt};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use crate::{spawned::stage_task, DynTransform, Spawned, Transform};
+
+impl<I, O> Spawned<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Appends a stage replicated across `workers` tasks pulling from the same channel, so
+    /// independent items are processed in parallel; outputs are sent in completion order
+    pub fn workers<Args, O2>(
+        self,
+        workers: usize,
+        stage: impl Transform<Args, O, O2>,
+    ) -> Spawned<I, O2>
+    where
+        Args: Send + Sync + 'static,
+        O2: Send + Sync + 'static,
+    {
+        let stage: Arc<dyn DynTransform<Args, O, O2>> = Arc::new(stage);
+        self.tasks(move |rx, tx, _| {
+            let rx = Arc::new(Mutex::new(rx));
+            (0..workers.max(1))
+                .map(|_| {
+                    let (stage, rx, mut tx) = (stage.clone(), rx.clone(), tx.clone());
+                    Box::pin(async move {
+                        loop {
+                            let input = rx.lock().await.next().await;
+                            let Some(input) = input else {
+                                break;
+                            };
+                            if tx.send(stage.transform(input).await).await.is_err() {
+                                break;
+                            }
+                        }
+                    }) as BoxFuture<'static, ()>
+                })
+                .collect()
+        })
+    }
+
+    /// Appends a stage replicated across `workers` tasks, routing items with the same key to
+    /// the same worker so the outputs for each key keep the order of their inputs
+    pub fn workers_by_key<Args, O2, K>(
+        self,
+        workers: usize,
+        key: impl Fn(&O) -> K + Send + 'static,
+        stage: impl Transform<Args, O, O2>,
+    ) -> Spawned<I, O2>
+    where
+        Args: Send + Sync + 'static,
+        O2: Send + Sync + 'static,
+        K: Hash,
+    {
+        let stage: Arc<dyn DynTransform<Args, O, O2>> = Arc::new(stage);
+        self.tasks(move |mut rx, tx, capacity| {
+            let mut partitions = Vec::new();
+            let mut tasks = Vec::new();
+            for _ in 0..workers.max(1) {
+                let (partition, input) = mpsc::channel(capacity);
+                partitions.push(partition);
+                tasks.push(stage_task(stage.clone(), input, tx.clone()));
+            }
+            tasks.push(Box::pin(async move {
+                while let Some(input) = rx.next().await {
+                    let mut hasher = DefaultHasher::new();
+                    key(&input).hash(&mut hasher);
+                    let index = hasher.finish() as usize % partitions.len();
+                    if partitions[index].send(input).await.is_err() {
+                        break;
+                    }
+                }
+            }));
+            tasks
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spawned;
+    use async_std::task::{self, sleep};
+    use futures::future::BoxFuture;
+    use std::time::{Duration, Instant};
+
+    fn spawner(future: BoxFuture<'static, ()>) {
+        task::spawn(future);
+    }
+
+    async fn pass(i: u64) -> u64 {
+        i
+    }
+
+    async fn slow(i: u64) -> u64 {
+        sleep(Duration::from_millis(20)).await;
+        i * 2
+    }
+
+    #[async_std::test]
+    async fn test_workers() {
+        let mut handle = spawned(pass).workers(8, slow).spawn(8, spawner);
+        let start = Instant::now();
+        for i in 0..8 {
+            handle.send(i).await.unwrap();
+        }
+        let mut outputs = handle.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(100));
+        outputs.sort();
+        assert_eq!(vec![0, 2, 4, 6, 8, 10, 12, 14], outputs);
+    }
+
+    #[async_std::test]
+    async fn test_workers_by_key() {
+        // later items of a key finish faster, but must still follow the earlier ones
+        let uneven = |(key, i): (u64, u64)| async move {
+            sleep(Duration::from_millis(30 - i * 10)).await;
+            (key, i)
+        };
+        let mut handle = spawned(|item: (u64, u64)| async move { item })
+            .workers_by_key(4, |(key, _): &(u64, u64)| *key, uneven)
+            .spawn(8, spawner);
+        for i in 0..3 {
+            for key in 0..3 {
+                handle.send((key, i)).await.unwrap();
+            }
+        }
+        let outputs = handle.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(9, outputs.len());
+        for key in 0..3 {
+            let order: Vec<u64> = outputs
+                .iter()
+                .filter(|(k, _)| *k == key)
+                .map(|(_, i)| *i)
+                .collect();
+            assert_eq!(vec![0, 1, 2], order);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Onion-style middleware types.
+
+use futures::future::BoxFuture;
+use std::{future::Future, sync::Arc};
+
+use crate::{DynMiddleware, Middleware, Pied, StageInfo, Transform};
+
+/// Continuation to the downstream chain, handed to a wrapping middleware
+pub struct Next<I, O> {
+    middleware: Arc<dyn DynMiddleware<I, O>>,
+}
+
+impl<I, O> Next<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Runs the remainder of the chain with the given input
+    pub async fn run(self, input: I) -> O {
+        self.middleware.call(input).await
+    }
+}
+
+impl<I, O> Clone for Next<I, O> {
+    fn clone(&self) -> Self {
+        Next {
+            middleware: self.middleware.clone(),
+        }
+    }
+}
+
+/// Middleware that runs around the downstream chain, before and after it.
+pub trait WrapMiddleware<I, O>: Send + Sync + 'static {
+    /// Asynchronously handle the input, optionally calling into the next middleware
+    fn handle(&self, input: I, next: Next<I, O>) -> impl Future<Output = O> + Send;
+}
+
+/// Object-safe form of a wrapping middleware that boxes its future, used where wrappers are stored
+pub trait DynWrapMiddleware<I, O>: Send + Sync + 'static {
+    /// Handle the input with the continuation, boxing the returned future
+    fn handle_dyn(&self, input: I, next: Next<I, O>) -> BoxFuture<'_, O>;
+}
+
+/// Every wrapping middleware can be erased by boxing its future
+impl<X, I, O> DynWrapMiddleware<I, O> for X
+where
+    X: WrapMiddleware<I, O>,
+    I: 'static,
+    O: 'static,
+{
+    fn handle_dyn(&self, input: I, next: Next<I, O>) -> BoxFuture<'_, O> {
+        Box::pin(self.handle(input, next))
+    }
+}
+
+/// Erased wrapping middleware are wrapping middleware themselves, so stored wrappers are called
+/// the same way
+impl<I, O> WrapMiddleware<I, O> for dyn DynWrapMiddleware<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    fn handle(&self, input: I, next: Next<I, O>) -> impl Future<Output = O> + Send {
+        self.handle_dyn(input, next)
+    }
+}
+
+/// Wrapping middleware implementation for an async function that receives the continuation
+impl<Func, Fut, I, O> WrapMiddleware<I, O> for Func
+where
+    Func: Send + Sync + 'static + Fn(I, Next<I, O>) -> Fut,
+    Fut: Future<Output = O> + Send + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    fn handle(&self, input: I, next: Next<I, O>) -> impl Future<Output = O> + Send {
+        (self)(input, next)
+    }
+}
+
+/// Encapsulates a wrapping middleware around an inner middleware
+pub struct WrappedMiddleware<I, O> {
+    wrapper: Arc<dyn DynWrapMiddleware<I, O>>,
+    inner: Arc<dyn DynMiddleware<I, O>>,
+}
+
+/// Implements the transform trait on the wrapped middleware (for downstream)
+impl<I, O> Transform<(I, O), I, O> for WrappedMiddleware<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn transform(&self, input: I) -> O {
+        self.call(input).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        Middleware::stages(self)
+    }
+}
+
+/// Implements the middleware trait on the wrapped middleware
+impl<I, O> Middleware<I, O> for WrappedMiddleware<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    async fn call(&self, input: I) -> O {
+        let next = Next {
+            middleware: self.inner.clone(),
+        };
+        self.wrapper.handle(input, next).await
+    }
+
+    fn stages(&self) -> Vec<StageInfo> {
+        vec![StageInfo::branching::<I, O>(
+            "wrap",
+            vec![("inner".into(), self.inner.stages_dyn())],
+        )]
+    }
+}
+
+/// Creates a new middleware that runs the wrapper around the inner middleware
+pub fn wrap<I, O>(
+    wrapper: impl WrapMiddleware<I, O>,
+    inner: impl Middleware<I, O>,
+) -> WrappedMiddleware<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    WrappedMiddleware {
+        wrapper: Arc::new(wrapper),
+        inner: Arc::new(inner),
+    }
+}
+
+impl<T, Args, I, O> Pied<T, Args, I, O>
+where
+    T: Send + Sync + 'static,
+    Args: Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+{
+    /// Runs the given wrapper around the whole pipeline
+    pub fn wrap(self, wrapper: impl WrapMiddleware<I, O>) -> Pied<T, Args, I, O> {
+        self.rebuild(|pied| WrappedMiddleware {
+            wrapper: Arc::new(wrapper),
+            inner: pied.middleware,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piper;
+    use std::sync::Mutex;
+
+    async fn multipler(i: i32) -> i32 {
+        i * 32
+    }
+
+    async fn stringer(i: i32) -> String {
+        i.to_string()
+    }
+
+    async fn around(i: i32, next: Next<i32, String>) -> String {
+        let out = next.run(i + 1).await;
+        format!("<{}>", out)
+    }
+
+    async fn guard(i: i32, next: Next<i32, String>) -> String {
+        if i < 0 {
+            return String::from("denied");
+        }
+        next.run(i).await
+    }
+
+    #[async_std::test]
+    async fn test_wrap_before_and_after() {
+        let m = (multipler, stringer).pipe().wrap(around);
+        assert_eq!(String::from("<64>"), m.call(1).await);
+
+        // wrappers nest from the inside out
+        let m = (multipler, stringer).pipe().wrap(around).wrap(guard);
+        assert_eq!(String::from("denied"), m.call(-1).await);
+        assert_eq!(String::from("<96>"), m.call(2).await);
+    }
+
+    #[async_std::test]
+    async fn test_wrap_in_pipe() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let logger = move |i: i32, next: Next<i32, i32>| {
+            let log = log.clone();
+            async move {
+                log.lock().unwrap().push(i);
+                next.run(i).await
+            }
+        };
+        let m = (wrap(logger, (multipler, multipler).pipe()), stringer).pipe();
+        assert_eq!(String::from("2048"), m.call(2).await);
+        assert_eq!(vec![2], *seen.lock().unwrap());
+
+        // the wrapper shows up as a stage around its inner pipeline
+        let stages = m.stages();
+        assert_eq!(2, stages.len());
+        assert_eq!(("wrap", 1), (&*stages[0].name, stages[0].branches.len()));
+        assert_eq!(
+            ("inner", 2),
+            (&*stages[0].branches[0].0, stages[0].branches[0].1.len())
+        );
+    }
+}